@@ -0,0 +1,266 @@
+use super::role::{request_scope, required_role, Role, HUB_METHODS};
+use crate::json_rpc::request::JsonRpcRequest;
+use crate::my_smart_home::location::is_within;
+use serde::{Deserialize, Serialize};
+use stp::auth::Principal;
+
+/// Права клиента по имени пользователя или токена.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Grant {
+    pub name: String,
+    pub role: Role,
+    /// дома, на которые распространяются права; пусто - все дома сервера
+    #[serde(default)]
+    pub homes: Vec<String>,
+    /// комнаты и локации с вложенными комнатами; пусто - весь дом
+    #[serde(default)]
+    pub rooms: Vec<String>,
+}
+
+/// Права клиентов сервера.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AccessPolicy {
+    #[serde(default)]
+    pub grants: Vec<Grant>,
+    /// роль клиента на открытом сервере без аутентификации
+    #[serde(default = "AccessPolicy::default_anonymous_role")]
+    pub anonymous: Role,
+    /// роль клиентов Unix-сокета; доступ к нему ограничен правами на файл сокета
+    #[serde(default = "AccessPolicy::default_local_role")]
+    pub local: Role,
+}
+
+impl Default for AccessPolicy {
+    fn default() -> Self {
+        Self {
+            grants: vec![],
+            anonymous: Self::default_anonymous_role(),
+            local: Self::default_local_role(),
+        }
+    }
+}
+
+impl AccessPolicy {
+    /// анонимы только смотрят, если не разрешено больше
+    fn default_anonymous_role() -> Role {
+        Role::Viewer
+    }
+
+    fn default_local_role() -> Role {
+        Role::Admin
+    }
+
+    /// контекст запросов клиента с адреса `peer`; без выданных прав клиенту запрещено все
+    pub fn context(&self, principal: &Principal, peer: &str) -> RequestContext {
+        let grant = match principal {
+            Principal::Anonymous | Principal::Local => None,
+            _ => self.grants.iter().find(|g| g.name == principal.name()),
+        };
+        RequestContext {
+            principal: principal.clone(),
+            peer: peer.to_string(),
+            role: match principal {
+                Principal::Anonymous => Some(self.anonymous),
+                Principal::Local => Some(self.local),
+                _ => grant.map(|g| g.role),
+            },
+            homes: grant.map(|g| g.homes.clone()).unwrap_or_default(),
+            rooms: grant.map(|g| g.rooms.clone()).unwrap_or_default(),
+        }
+    }
+}
+
+/// Кто выполняет запросы и что ему разрешено.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RequestContext {
+    pub principal: Principal,
+    /// адрес клиента
+    pub peer: String,
+    /// None - прав нет
+    pub role: Option<Role>,
+    /// дома, которыми ограничен клиент; пусто - все дома
+    pub homes: Vec<String>,
+    /// комнаты и локации, которыми ограничен клиент; пусто - весь дом
+    pub rooms: Vec<String>,
+}
+
+impl RequestContext {
+    /// локальный клиент с полным доступом
+    pub fn full() -> Self {
+        Self {
+            principal: Principal::Anonymous,
+            peer: "local".to_string(),
+            role: Some(Role::Admin),
+            homes: vec![],
+            rooms: vec![],
+        }
+    }
+
+    /// разрешен ли запрос к дому `home`; `None` - запрос к хабу, а не к дому,
+    /// он разрешен только клиенту без ограничений по домам и комнатам.
+    /// Ошибка - причина отказа
+    pub fn authorize(&self, request: &JsonRpcRequest, home: Option<&str>) -> Result<(), String> {
+        let name = self.principal.name();
+        let required = match HUB_METHODS.contains(&request.method.as_str()) {
+            true => Role::Admin,
+            false => required_role(request),
+        };
+        match self.role {
+            Some(role) if role >= required => (),
+            Some(role) => {
+                return Err(format!(
+                    "{}: {name} has role {role:?}, {required:?} required",
+                    request.method
+                ))
+            }
+            None => return Err(format!("{}: {name} has no access", request.method)),
+        }
+
+        match home {
+            Some(home) if !self.allows_home(home) => {
+                return Err(format!(
+                    "{}: {name} has no access to home {home}",
+                    request.method
+                ))
+            }
+            Some(_) => (),
+            None if self.homes.is_empty() && self.rooms.is_empty() => return Ok(()),
+            None => {
+                return Err(format!(
+                    "{}: {name} is limited to homes {:?} and rooms {:?}",
+                    request.method, self.homes, self.rooms
+                ))
+            }
+        }
+
+        if self.rooms.is_empty() {
+            return Ok(());
+        }
+        match request_scope(request) {
+            Some(scope) => match scope.iter().find(|room| !self.allows_room(room)) {
+                Some(room) => Err(format!(
+                    "{}: {name} has no access to room {room}",
+                    request.method
+                )),
+                None => Ok(()),
+            },
+            None => Err(format!(
+                "{}: {name} is limited to rooms {:?}",
+                request.method, self.rooms
+            )),
+        }
+    }
+
+    pub(crate) fn allows_home(&self, home: &str) -> bool {
+        self.homes.is_empty() || self.homes.iter().any(|allowed| allowed == home)
+    }
+
+    pub(crate) fn allows_room(&self, room: &str) -> bool {
+        self.rooms.iter().any(|allowed| is_within(room, allowed))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn request(method: &str, params: Value) -> JsonRpcRequest {
+        JsonRpcRequest {
+            method: method.into(),
+            params,
+            ..Default::default()
+        }
+    }
+
+    const HOME: Option<&str> = Some("default");
+
+    #[test]
+    fn test_authorize() {
+        let policy: AccessPolicy = serde_json::from_value(json!({
+            "grants": [
+                {"name": "anna", "role": "admin"},
+                {"name": "kids", "role": "operator", "rooms": ["floor-2/kids"]}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(policy.anonymous, Role::Viewer);
+        let anonymous = policy.context(&Principal::Anonymous, "127.0.0.1:50000");
+        assert!(anonymous
+            .authorize(&request("delRoom", json!({"name": "kitchen"})), HOME)
+            .unwrap_err()
+            .contains("Admin required"));
+        assert!(anonymous
+            .authorize(&request("createReport", json!({})), HOME)
+            .is_ok());
+        let local = policy.context(&Principal::Local, "unix:/run/smart_home.sock");
+        assert!(local
+            .authorize(&request("delRoom", json!({"name": "kitchen"})), HOME)
+            .is_ok());
+
+        let anna = policy.context(&Principal::User("anna".into()), "local");
+        assert!(anna
+            .authorize(&request("delRoom", json!({"name": "kitchen"})), HOME)
+            .is_ok());
+
+        let kids = policy.context(&Principal::Token("kids".into()), "local");
+        let del = request("delRoom", json!({"name": "kitchen"}));
+        assert!(kids
+            .authorize(&del, HOME)
+            .unwrap_err()
+            .contains("Admin required"));
+
+        let switch = |room: &str| {
+            let params = json!({"room": room, "device": "socket-1", "command": "switch"});
+            request("deviceExecute", params)
+        };
+        assert!(kids.authorize(&switch("floor-2/kids"), HOME).is_ok());
+        assert!(kids.authorize(&switch("floor-2/kids/closet"), HOME).is_ok());
+        let err = kids.authorize(&switch("kitchen"), HOME).unwrap_err();
+        assert!(err.contains("no access to room kitchen"));
+        let report = request("createReport", json!({}));
+        assert!(kids
+            .authorize(&report, HOME)
+            .unwrap_err()
+            .contains("limited"));
+
+        let guest = policy.context(&Principal::User("guest".into()), "local");
+        assert!(guest
+            .authorize(&report, HOME)
+            .unwrap_err()
+            .contains("no access"));
+    }
+
+    #[test]
+    fn test_authorize_homes() {
+        let policy: AccessPolicy = serde_json::from_value(json!({
+            "grants": [
+                {"name": "anna", "role": "admin"},
+                {"name": "tenant", "role": "admin", "homes": ["flat-2"]},
+                {"name": "kids", "role": "operator", "rooms": ["floor-2/kids"]}
+            ]
+        }))
+        .unwrap();
+        let del = request("delRoom", json!({"name": "kitchen"}));
+        let list = request("listHomes", json!({}));
+
+        let tenant = policy.context(&Principal::User("tenant".into()), "local");
+        assert!(tenant.authorize(&del, Some("flat-2")).is_ok());
+        let err = tenant.authorize(&del, Some("default")).unwrap_err();
+        assert!(err.contains("no access to home default"));
+        assert!(tenant
+            .authorize(&list, None)
+            .unwrap_err()
+            .contains("limited"));
+
+        let kids = policy.context(&Principal::Token("kids".into()), "local");
+        let err = kids.authorize(&list, None).unwrap_err();
+        assert!(err.contains("Admin required"));
+
+        let anna = policy.context(&Principal::User("anna".into()), "local");
+        assert!(anna.authorize(&del, Some("flat-2")).is_ok());
+        assert!(anna.authorize(&list, None).is_ok());
+        let anonymous = policy.context(&Principal::Anonymous, "127.0.0.1:50000");
+        assert!(anonymous.authorize(&list, None).is_err());
+    }
+}
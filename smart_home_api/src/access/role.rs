@@ -0,0 +1,129 @@
+use crate::json_rpc::request::JsonRpcRequest;
+use serde::{Deserialize, Serialize};
+
+/// Роль клиента; каждая следующая роль может все, что и предыдущая.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// отчеты и чтение состояния
+    Viewer,
+    /// управление устройствами и сценами
+    Operator,
+    /// изменение состава дома и автоматизации
+    Admin,
+}
+
+/// методы самого хаба, остальные выполняет дом из запроса;
+/// они касаются всех домов и разрешены только администратору без ограничений
+pub const HUB_METHODS: [&str; 4] = ["listHomes", "addHome", "delHome", "getMetrics"];
+
+/// команды устройств, которые только читают его состояние
+const READ_COMMANDS: [&str; 4] = ["get_name", "get_description", "get_current_info", "report"];
+
+/// минимальная роль для запроса; неизвестные методы - только для администратора
+pub fn required_role(request: &JsonRpcRequest) -> Role {
+    match request.method.as_str() {
+        "getDevices"
+        | "getDeviceTypes"
+        | "createReport"
+        | "createProviderReport"
+        | "reset"
+        | "getFaults"
+        | "listRules"
+        | "listSchedules"
+        | "listScenes"
+        | "listGroups"
+        | "groupReport"
+        | "queryDevices"
+        | "getHistory"
+        | "getEnergyReport"
+        | "createEnergyReport"
+        | "listAlarms"
+        | "getAlarms"
+        | "getLocations" => Role::Viewer,
+
+        "deviceExecute" | "groupExecute" | "locationExecute" => {
+            match request.params["command"].as_str() {
+                Some(command) if READ_COMMANDS.contains(&command) => Role::Viewer,
+                _ => Role::Operator,
+            }
+        }
+
+        "applyScene" | "ackAlarm" => Role::Operator,
+
+        _ => Role::Admin,
+    }
+}
+
+/// комнаты и локации, которых касается запрос; None - запрос ко всему дому
+pub fn request_scope(request: &JsonRpcRequest) -> Option<Vec<String>> {
+    let params = &request.params;
+    let keys: &[&str] = match request.method.as_str() {
+        "reset" => return Some(vec![]),
+        "addRoom" | "delRoom" => &["name"],
+        "renameRoom" => &["name", "new_name"],
+        "moveDevice" => &["room", "to"],
+        _ => &["room", "location"],
+    };
+    let scope: Vec<String> = keys
+        .iter()
+        .filter_map(|key| params[key].as_str())
+        .map(String::from)
+        .collect();
+    match scope.is_empty() {
+        true => None,
+        false => Some(scope),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn request(method: &str, params: Value) -> JsonRpcRequest {
+        JsonRpcRequest {
+            method: method.into(),
+            params,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_required_role() {
+        assert_eq!(
+            required_role(&request("createReport", json!({}))),
+            Role::Viewer
+        );
+        assert_eq!(required_role(&request("delRoom", json!({}))), Role::Admin);
+        assert_eq!(required_role(&request("unknown", json!({}))), Role::Admin);
+        assert_eq!(
+            required_role(&request("applyScene", json!({}))),
+            Role::Operator
+        );
+
+        let read = json!({"room": "kitchen", "device": "socket-1", "command": "report"});
+        assert_eq!(required_role(&request("deviceExecute", read)), Role::Viewer);
+        let switch = json!({"room": "kitchen", "device": "socket-1", "command": "switch"});
+        assert_eq!(
+            required_role(&request("deviceExecute", switch)),
+            Role::Operator
+        );
+        assert!(Role::Viewer < Role::Operator && Role::Operator < Role::Admin);
+    }
+
+    #[test]
+    fn test_request_scope() {
+        let params = json!({"room": "kitchen", "device": "socket-1", "to": "living"});
+        assert_eq!(
+            request_scope(&request("moveDevice", params.clone())),
+            Some(vec!["kitchen".into(), "living".into()])
+        );
+        assert_eq!(
+            request_scope(&request("getHistory", params)),
+            Some(vec!["kitchen".into()])
+        );
+        assert_eq!(request_scope(&request("createReport", json!({}))), None);
+        assert_eq!(request_scope(&request("reset", json!({}))), Some(vec![]));
+    }
+}
@@ -0,0 +1,144 @@
+use crate::automation::rule::CompareOp;
+use chrono::{NaiveDateTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+
+/// Условие тревоги
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlarmCondition {
+    /// показание переходит порог; тревога снимается, когда показание
+    /// возвращается за порог с запасом `hysteresis`
+    Reading {
+        reading: String,
+        op: CompareOp,
+        value: f32,
+        #[serde(default)]
+        hysteresis: f32,
+    },
+
+    /// устройство перешло в статус Broken
+    Broken,
+}
+
+impl AlarmCondition {
+    /// проверить параметры условия: порог конечен, гистерезис конечен и не отрицателен
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            AlarmCondition::Reading { value, .. } if !value.is_finite() => {
+                Err(format!("value must be finite: {value}"))
+            }
+            AlarmCondition::Reading { hysteresis, .. }
+                if !hysteresis.is_finite() || *hysteresis < 0.0 =>
+            {
+                Err(format!(
+                    "hysteresis must be finite and not negative: {hysteresis}"
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// нужно ли поднять тревогу при текущем значении показания
+    pub fn raised(&self, value: f32) -> bool {
+        match self {
+            AlarmCondition::Reading {
+                op, value: limit, ..
+            } => op.check(value, *limit),
+            AlarmCondition::Broken => false,
+        }
+    }
+
+    /// можно ли снять тревогу при текущем значении показания
+    pub fn cleared(&self, value: f32) -> bool {
+        match self {
+            AlarmCondition::Reading {
+                op,
+                value: limit,
+                hysteresis,
+                ..
+            } => match op {
+                CompareOp::Gt | CompareOp::Ge => value < limit - hysteresis,
+                CompareOp::Lt | CompareOp::Le => value > limit + hysteresis,
+            },
+            AlarmCondition::Broken => true,
+        }
+    }
+}
+
+/// Определение тревоги для устройства
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AlarmDefinition {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    pub room: String,
+    /// идентификатор или имя устройства
+    pub device: String,
+    pub condition: AlarmCondition,
+    /// сколько секунд условие должно выполняться, прежде чем тревога будет поднята
+    #[serde(default)]
+    pub debounce_seconds: i64,
+}
+
+/// самая долгая задержка подъема тревоги: сутки
+const MAX_DEBOUNCE_SECONDS: i64 = 24 * 60 * 60;
+
+impl AlarmDefinition {
+    /// проверить параметры тревоги
+    pub fn validate(&self) -> Result<(), String> {
+        self.condition.validate()?;
+        match self.debounce_seconds {
+            0..=MAX_DEBOUNCE_SECONDS => Ok(()),
+            seconds => Err(format!(
+                "debounce_seconds must be in 0..={MAX_DEBOUNCE_SECONDS}: {seconds}"
+            )),
+        }
+    }
+
+    /// сколько условие должно выполняться до подъема тревоги
+    pub fn debounce(&self) -> Option<TimeDelta> {
+        TimeDelta::try_seconds(self.debounce_seconds)
+    }
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+/// Стадия жизни тревоги
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlarmStatus {
+    /// поднята и не подтверждена
+    Active,
+    /// подтверждена, условие еще выполняется
+    Acknowledged,
+    /// условие больше не выполняется
+    Cleared,
+}
+
+/// Запись о сработавшей тревоге
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AlarmRecord {
+    /// номер записи, уникальный в доме
+    pub seq: u64,
+    pub alarm: String,
+    pub room: String,
+    pub device: String,
+    pub status: AlarmStatus,
+    pub message: String,
+    pub raised_at: NaiveDateTime,
+    #[serde(default)]
+    pub acknowledged_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub cleared_at: Option<NaiveDateTime>,
+}
+
+impl AlarmRecord {
+    /// тревога не снята
+    pub fn is_open(&self) -> bool {
+        self.status != AlarmStatus::Cleared
+    }
+}
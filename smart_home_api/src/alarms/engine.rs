@@ -0,0 +1,411 @@
+use super::alarm::{AlarmCondition, AlarmDefinition, AlarmRecord, AlarmStatus};
+use crate::my_smart_home::error::{SmartHomeError, SmartHomeResult};
+use crate::my_smart_home::home::Home;
+use crate::my_smart_home::location::{move_device_ref, relocate_ref};
+use crate::my_smart_home::smart_home::SmartHome;
+use crate::my_smart_home::storage::{load_json, save_json};
+use crate::smart_device::device::DeviceState;
+use chrono::NaiveDateTime;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// сколько записей о тревогах хранится, лишние снятые удаляются, начиная с самых старых
+const MAX_RECORDS: usize = 1000;
+
+/// Тревоги дома: определения и журнал сработавших тревог
+#[derive(Default, Serialize, Deserialize)]
+pub struct AlarmEngine {
+    #[serde(default)]
+    definitions: Vec<AlarmDefinition>,
+    #[serde(default)]
+    records: Vec<AlarmRecord>,
+    #[serde(default)]
+    next_seq: u64,
+    /// с какого момента выполняется условие еще не поднятой тревоги
+    #[serde(skip)]
+    pending: HashMap<String, NaiveDateTime>,
+}
+
+impl AlarmEngine {
+    pub fn definitions(&self) -> &[AlarmDefinition] {
+        &self.definitions
+    }
+
+    /// записи о тревогах, только не снятые - если `open_only`
+    pub fn records(&self, open_only: bool) -> Vec<&AlarmRecord> {
+        self.records
+            .iter()
+            .filter(|r| !open_only || r.is_open())
+            .collect()
+    }
+
+    pub fn add(&mut self, definition: AlarmDefinition) -> SmartHomeResult<()> {
+        if self.definitions.iter().any(|d| d.id == definition.id) {
+            return Err(SmartHomeError::AlarmSameIdExist(definition.id));
+        }
+        definition
+            .validate()
+            .map_err(|reason| SmartHomeError::InvalidAlarm {
+                id: definition.id.clone(),
+                reason,
+            })?;
+        self.definitions.push(definition);
+        Ok(())
+    }
+
+    /// удалить определение, его не снятые тревоги снимаются; результат - снятые тревоги
+    pub fn remove(&mut self, id: &str, now: NaiveDateTime) -> SmartHomeResult<Vec<AlarmRecord>> {
+        let Some(pos) = self.definitions.iter().position(|d| d.id == id) else {
+            return Err(SmartHomeError::AlarmNonExist(id.to_string()));
+        };
+        self.definitions.remove(pos);
+        self.pending.remove(id);
+        Ok(clear_open(&mut self.records, id, now))
+    }
+
+    pub fn set_enabled(&mut self, id: &str, enabled: bool) -> SmartHomeResult<()> {
+        match self.definitions.iter_mut().find(|d| d.id == id) {
+            Some(definition) => {
+                definition.enabled = enabled;
+                Ok(())
+            }
+            None => Err(SmartHomeError::AlarmNonExist(id.to_string())),
+        }
+    }
+
+    /// комната переименована вместе с вложенными; меняются и записи о тревогах
+    pub fn relocate_room(&mut self, from: &str, to: &str) {
+        for definition in self.definitions.iter_mut() {
+            relocate_ref(&mut definition.room, from, to);
+        }
+        for record in self.records.iter_mut() {
+            relocate_ref(&mut record.room, from, to);
+        }
+    }
+
+    /// устройство с идентификатором или именем из `names` перенесено из `room` в `to`;
+    /// меняются и записи о тревогах
+    pub fn move_device(&mut self, room: &str, names: &[&str], to: &str) {
+        for definition in self.definitions.iter_mut() {
+            move_device_ref(&mut definition.room, &definition.device, room, names, to);
+        }
+        for record in self.records.iter_mut() {
+            move_device_ref(&mut record.room, &record.device, room, names, to);
+        }
+    }
+
+    /// подтвердить поднятую тревогу
+    pub fn acknowledge(&mut self, seq: u64, now: NaiveDateTime) -> SmartHomeResult<AlarmRecord> {
+        let Some(record) = self.records.iter_mut().find(|r| r.seq == seq) else {
+            return Err(SmartHomeError::AlarmRecordNonExist(seq));
+        };
+        if record.status != AlarmStatus::Active {
+            return Err(SmartHomeError::AlarmNotActive(seq));
+        }
+        record.status = AlarmStatus::Acknowledged;
+        record.acknowledged_at = Some(now);
+        Ok(record.clone())
+    }
+
+    /// проверить условия тревог по текущему состоянию дома;
+    /// результат - поднятые и снятые за эту проверку тревоги.
+    /// Тревоги выключенных определений и удаленных устройств снимаются
+    pub fn evaluate(&mut self, home: &Home, now: NaiveDateTime) -> Vec<AlarmRecord> {
+        let mut changed = vec![];
+
+        for definition in self.definitions.iter() {
+            let checked = match definition.enabled {
+                true => check(home, definition),
+                false => None,
+            };
+            let Some((raised, cleared, message)) = checked else {
+                // тревога выключена или устройство удалено: не снятая тревога снимается
+                self.pending.remove(&definition.id);
+                changed.extend(clear_open(&mut self.records, &definition.id, now));
+                continue;
+            };
+
+            let open = self
+                .records
+                .iter_mut()
+                .find(|r| r.alarm == definition.id && r.is_open());
+            match open {
+                Some(record) => {
+                    if cleared {
+                        record.status = AlarmStatus::Cleared;
+                        record.cleared_at = Some(now);
+                        changed.push(record.clone());
+                    }
+                }
+                None if raised => {
+                    let since = *self.pending.entry(definition.id.clone()).or_insert(now);
+                    let debounced = definition
+                        .debounce()
+                        .is_some_and(|debounce| now - since >= debounce);
+                    if !debounced {
+                        continue;
+                    }
+                    self.pending.remove(&definition.id);
+
+                    self.next_seq += 1;
+                    let record = AlarmRecord {
+                        seq: self.next_seq,
+                        alarm: definition.id.clone(),
+                        room: definition.room.clone(),
+                        device: definition.device.clone(),
+                        status: AlarmStatus::Active,
+                        message,
+                        raised_at: now,
+                        acknowledged_at: None,
+                        cleared_at: None,
+                    };
+                    changed.push(record.clone());
+                    self.records.push(record);
+                }
+                None => {
+                    self.pending.remove(&definition.id);
+                }
+            }
+        }
+
+        while self.records.len() > MAX_RECORDS {
+            match self.records.iter().position(|r| !r.is_open()) {
+                Some(pos) => self.records.remove(pos),
+                None => break,
+            };
+        }
+        changed
+    }
+
+    /// загрузить определения и журнал тревог из json-файла;
+    /// определения с недопустимыми параметрами выключаются
+    pub fn load_file(&mut self, path: &Path) -> SmartHomeResult<()> {
+        *self = load_json(path)?;
+        for definition in self.definitions.iter_mut().filter(|d| d.enabled) {
+            if let Err(reason) = definition.validate() {
+                warn!(alarm = definition.id.as_str(), reason = reason.as_str(); "invalid alarm disabled");
+                definition.enabled = false;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save_file(&self, path: &Path) -> SmartHomeResult<()> {
+        save_json(path, self)
+    }
+}
+
+/// снять не снятые тревоги определения
+fn clear_open(records: &mut [AlarmRecord], alarm: &str, now: NaiveDateTime) -> Vec<AlarmRecord> {
+    records
+        .iter_mut()
+        .filter(|r| r.alarm == alarm && r.is_open())
+        .map(|record| {
+            record.status = AlarmStatus::Cleared;
+            record.cleared_at = Some(now);
+            record.clone()
+        })
+        .collect()
+}
+
+/// выполняется ли условие тревоги, можно ли ее снять и текст тревоги;
+/// None - устройство или показание недоступны
+fn check(home: &Home, definition: &AlarmDefinition) -> Option<(bool, bool, String)> {
+    let device = home.device(&definition.room, &definition.device).ok()?;
+    let path = Home::device_path(&definition.room, &device.get_id());
+
+    match &definition.condition {
+        AlarmCondition::Reading {
+            reading, op, value, ..
+        } => {
+            let current = *device.get_readings().get(reading)?;
+            let condition = &definition.condition;
+            Some((
+                condition.raised(current),
+                condition.cleared(current),
+                format!("{path}: {reading} = {current} {} {value}", op.symbol()),
+            ))
+        }
+        AlarmCondition::Broken => {
+            let broken = device.device_state() == DeviceState::Broken;
+            let message = match device.fault() {
+                Some(fault) => format!("{path}: broken: {fault}"),
+                None => format!("{path}: broken"),
+            };
+            Some((broken, !broken, message))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AlarmEngine;
+    use crate::alarms::alarm::{AlarmCondition, AlarmDefinition, AlarmStatus};
+    use crate::clock::{Clock, ManualClock};
+    use crate::my_smart_home::home::Home;
+    use crate::my_smart_home::smart_home::SmartHome;
+    use crate::smart_device::device::VecOfDevice;
+    use crate::smart_device::socket::Socket;
+    use crate::smart_device::thermometer::Thermometer;
+    use chrono::{NaiveDate, TimeDelta};
+    use serde_json::json;
+
+    fn setup() -> (Home, ManualClock) {
+        let clock = ManualClock::new(
+            NaiveDate::from_ymd_opt(2025, 1, 10)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap(),
+        );
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.clock = Box::new(clock.clone());
+        let bedroom: VecOfDevice =
+            vec![Box::new(Socket::new("3")), Box::new(Thermometer::new("2"))];
+        home.add_room("bedroom".into(), bedroom).unwrap();
+        (home, clock)
+    }
+
+    fn set_temperature(home: &mut Home, value: f32) {
+        home.with_device("bedroom", "thermometer-2", |dev| {
+            dev.update_reading("temperature", value)
+        })
+        .unwrap();
+    }
+
+    fn evaluate(engine: &mut AlarmEngine, home: &Home, clock: &ManualClock) -> Vec<AlarmStatus> {
+        engine
+            .evaluate(home, clock.now())
+            .iter()
+            .map(|r| r.status)
+            .collect()
+    }
+
+    #[test]
+    fn test_reading_alarm_with_hysteresis_and_debounce() {
+        let (mut home, clock) = setup();
+        let mut engine = AlarmEngine::default();
+        let definition: AlarmDefinition = serde_json::from_value(json!({
+            "id": "hot-bedroom",
+            "room": "bedroom",
+            "device": "Thermometer 2",
+            "condition": {"type": "reading", "reading": "temperature", "op": ">",
+                          "value": 28.0, "hysteresis": 1.0},
+            "debounce_seconds": 60
+        }))
+        .unwrap();
+
+        // отрицательный гистерезис снимал бы тревогу выше порога
+        for hysteresis in [-1.0, f32::NAN, f32::INFINITY] {
+            let mut invalid = definition.clone();
+            if let AlarmCondition::Reading { hysteresis: h, .. } = &mut invalid.condition {
+                *h = hysteresis;
+            }
+            let res = engine.add(invalid);
+            assert!(res.err().unwrap().to_string().contains("hysteresis"));
+        }
+        engine.add(definition).unwrap();
+
+        set_temperature(&mut home, 29.0);
+        assert!(evaluate(&mut engine, &home, &clock).is_empty());
+
+        // кратковременный выброс не поднимает тревогу
+        set_temperature(&mut home, 25.0);
+        clock.advance(TimeDelta::seconds(30));
+        assert!(evaluate(&mut engine, &home, &clock).is_empty());
+
+        set_temperature(&mut home, 29.0);
+        assert!(evaluate(&mut engine, &home, &clock).is_empty());
+        clock.advance(TimeDelta::seconds(60));
+        assert_eq!(
+            evaluate(&mut engine, &home, &clock),
+            vec![AlarmStatus::Active]
+        );
+        let seq = engine.records(true)[0].seq;
+
+        engine.acknowledge(seq, clock.now()).unwrap();
+        assert!(engine.acknowledge(seq, clock.now()).is_err());
+
+        // в пределах гистерезиса тревога не снимается
+        set_temperature(&mut home, 27.5);
+        assert!(evaluate(&mut engine, &home, &clock).is_empty());
+        set_temperature(&mut home, 26.5);
+        assert_eq!(
+            evaluate(&mut engine, &home, &clock),
+            vec![AlarmStatus::Cleared]
+        );
+        assert!(engine.records(true).is_empty());
+
+        let record = engine.records(false)[0];
+        assert!(record.acknowledged_at.is_some() && record.cleared_at.is_some());
+        assert!(record.message.contains("temperature = 29 > 28"));
+    }
+
+    #[test]
+    fn test_broken_alarm() {
+        let (mut home, clock) = setup();
+        let mut engine = AlarmEngine::default();
+        let definition: AlarmDefinition = serde_json::from_value(json!({
+            "id": "socket-broken", "room": "bedroom", "device": "socket-3",
+            "condition": {"type": "broken"}
+        }))
+        .unwrap();
+        engine.add(definition.clone()).unwrap();
+        assert!(engine.add(definition.clone()).is_err());
+        let endless = AlarmDefinition {
+            id: "endless".into(),
+            debounce_seconds: i64::MAX,
+            ..definition
+        };
+        let res = engine.add(endless);
+        assert!(res.err().unwrap().to_string().contains("debounce_seconds"));
+
+        assert!(evaluate(&mut engine, &home, &clock).is_empty());
+        home.with_device("bedroom", "socket-3", |dev| dev.switch("broken"))
+            .unwrap();
+        assert_eq!(
+            evaluate(&mut engine, &home, &clock),
+            vec![AlarmStatus::Active]
+        );
+        assert!(evaluate(&mut engine, &home, &clock).is_empty());
+
+        home.with_device("bedroom", "socket-3", |dev| dev.repair())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            evaluate(&mut engine, &home, &clock),
+            vec![AlarmStatus::Cleared]
+        );
+    }
+
+    #[test]
+    fn test_open_alarm_cleared_when_disabled_or_device_removed() {
+        let (mut home, clock) = setup();
+        let mut engine = AlarmEngine::default();
+        for id in ["first", "second"] {
+            let definition: AlarmDefinition = serde_json::from_value(json!({
+                "id": id, "room": "bedroom", "device": "socket-3",
+                "condition": {"type": "broken"}
+            }))
+            .unwrap();
+            engine.add(definition).unwrap();
+        }
+        home.with_device("bedroom", "socket-3", |dev| dev.switch("broken"))
+            .unwrap();
+        assert_eq!(evaluate(&mut engine, &home, &clock).len(), 2);
+
+        engine.set_enabled("first", false).unwrap();
+        assert_eq!(
+            evaluate(&mut engine, &home, &clock),
+            vec![AlarmStatus::Cleared]
+        );
+
+        home.del_device("bedroom", "socket-3").unwrap();
+        assert_eq!(
+            evaluate(&mut engine, &home, &clock),
+            vec![AlarmStatus::Cleared]
+        );
+        assert!(engine.records(true).is_empty());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::access::policy::RequestContext;
+use crate::json_rpc::reply::JsonRpcReplyMsg;
+use crate::json_rpc::request::JsonRpcRequest;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use stp::auth::Principal;
+
+/// Чем закончился запрос.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Ok,
+    Error,
+    /// клиенту не хватило прав
+    Denied,
+}
+
+/// Запись журнала аудита: кто, когда и что изменил.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub ts: NaiveDateTime,
+    /// адрес клиента
+    pub peer: String,
+    /// пользователь или токен; None - клиент без аутентификации
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub home: Option<String>,
+    pub method: String,
+    pub params: Value,
+    /// идентификатор устройства из запроса, если оно нашлось в доме
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    pub outcome: AuditOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    /// запись о запросе до его выполнения
+    pub fn new(
+        ts: NaiveDateTime,
+        ctx: &RequestContext,
+        request: &JsonRpcRequest,
+        device: Option<String>,
+    ) -> Self {
+        Self {
+            ts,
+            peer: ctx.peer.clone(),
+            user: match &ctx.principal {
+                Principal::Anonymous => None,
+                principal => Some(principal.name().to_string()),
+            },
+            home: request.home.clone(),
+            method: request.method.clone(),
+            params: request.params.clone(),
+            device,
+            outcome: AuditOutcome::Ok,
+            error: None,
+        }
+    }
+
+    /// дополнить запись результатом из ответа на запрос
+    pub fn finish(mut self, reply: &dyn JsonRpcReplyMsg) -> Self {
+        let reply = serde_json::to_value(reply).unwrap_or_default();
+        let error = &reply["error"];
+        if !error.is_null() {
+            self.outcome = match error["code"].as_i64() {
+                Some(-32001) => AuditOutcome::Denied,
+                _ => AuditOutcome::Error,
+            };
+            self.error = error["data"].as_str().map(String::from);
+        }
+        self
+    }
+}
+
+/// Фильтр записей журнала, все заданные условия должны выполняться.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AuditQuery {
+    #[serde(default)]
+    pub from: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub to: Option<NaiveDateTime>,
+    /// идентификатор или имя устройства
+    #[serde(default)]
+    pub device: Option<String>,
+    #[serde(default)]
+    pub room: Option<String>,
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub method: Option<String>,
+    /// только последние записи
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        if self.from.is_some_and(|from| entry.ts < from) {
+            return false;
+        }
+        if self.to.is_some_and(|to| entry.ts > to) {
+            return false;
+        }
+        if let Some(device) = &self.device {
+            let named = entry.params["device"].as_str() == Some(device);
+            if !named && entry.device.as_ref() != Some(device) {
+                return false;
+            }
+        }
+        if let Some(room) = &self.room {
+            if entry.params["room"].as_str() != Some(room) {
+                return false;
+            }
+        }
+        if self.user.is_some() && self.user != entry.user {
+            return false;
+        }
+        self.method.as_ref().is_none_or(|m| m == &entry.method)
+    }
+}
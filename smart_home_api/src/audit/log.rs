@@ -0,0 +1,189 @@
+use super::entry::{AuditEntry, AuditQuery};
+use crate::my_smart_home::error::{SmartHomeError, SmartHomeResult};
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// размер текущего файла журнала, после которого он ротируется
+const DEFAULT_MAX_BYTES: u64 = 1024 * 1024;
+/// сколько ротированных файлов хранится
+const DEFAULT_MAX_FILES: usize = 5;
+/// текущий файл журнала
+const AUDIT_FILE: &str = "audit.jsonl";
+
+/// Журнал аудита, только дописывается.
+/// Текущий файл `audit.jsonl`, ротированные - `audit.1.jsonl` (новее) ... `audit.N.jsonl`.
+/// Пока каталог не задан, журнал не ведется.
+pub struct AuditLog {
+    dir: Option<PathBuf>,
+    pub max_bytes: u64,
+    pub max_files: usize,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            max_bytes: DEFAULT_MAX_BYTES,
+            max_files: DEFAULT_MAX_FILES,
+        }
+    }
+}
+
+impl AuditLog {
+    /// вести журнал в каталоге `dir`
+    pub fn set_dir(&mut self, dir: &Path) {
+        self.dir = Some(dir.to_path_buf());
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// дописать запись, при необходимости сначала ротировать журнал
+    pub fn append(&self, entry: &AuditEntry) -> SmartHomeResult<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        let path = dir.join(AUDIT_FILE);
+        let line = serde_json::to_string(entry).map_err(|e| audit_error(&path, e))?;
+        fs::create_dir_all(dir).map_err(|e| audit_error(&path, e))?;
+        if fs::metadata(&path).is_ok_and(|meta| meta.len() >= self.max_bytes) {
+            self.rotate(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| audit_error(&path, e))?;
+        writeln!(file, "{line}").map_err(|e| audit_error(&path, e))
+    }
+
+    /// записи, подходящие под фильтр, от старых к новым
+    pub fn query(&self, query: &AuditQuery) -> SmartHomeResult<Vec<AuditEntry>> {
+        let Some(dir) = &self.dir else {
+            return Ok(vec![]);
+        };
+        let mut found = vec![];
+        for n in (1..=self.max_files).rev() {
+            found.extend(read_entries(&rotated_file(dir, n), query)?);
+        }
+        found.extend(read_entries(&dir.join(AUDIT_FILE), query)?);
+
+        if let Some(limit) = query.limit {
+            let skip = found.len().saturating_sub(limit);
+            found.drain(..skip);
+        }
+        Ok(found)
+    }
+
+    fn rotate(&self, dir: &Path) -> SmartHomeResult<()> {
+        let oldest = rotated_file(dir, self.max_files);
+        if oldest.exists() {
+            fs::remove_file(&oldest).map_err(|e| audit_error(&oldest, e))?;
+        }
+        for n in (1..self.max_files).rev() {
+            let path = rotated_file(dir, n);
+            if path.exists() {
+                fs::rename(&path, rotated_file(dir, n + 1)).map_err(|e| audit_error(&path, e))?;
+            }
+        }
+        let current = dir.join(AUDIT_FILE);
+        match self.max_files {
+            0 => fs::remove_file(&current),
+            _ => fs::rename(&current, rotated_file(dir, 1)),
+        }
+        .map_err(|e| audit_error(&current, e))
+    }
+}
+
+fn rotated_file(dir: &Path, n: usize) -> PathBuf {
+    dir.join(format!("audit.{n}.jsonl"))
+}
+
+/// прочитать записи из файла; недописанные строки пропускаются
+fn read_entries(path: &Path, query: &AuditQuery) -> SmartHomeResult<Vec<AuditEntry>> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(audit_error(path, e)),
+    };
+    let mut entries = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| audit_error(path, e))?;
+        if let Ok(entry) = serde_json::from_str::<AuditEntry>(&line) {
+            if query.matches(&entry) {
+                entries.push(entry);
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn audit_error(path: &Path, e: impl ToString) -> SmartHomeError {
+    SmartHomeError::Storage(format!("{}: {}", path.display(), e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::AuditLog;
+    use crate::access::policy::RequestContext;
+    use crate::audit::entry::{AuditEntry, AuditQuery};
+    use crate::json_rpc::request::JsonRpcRequest;
+    use chrono::{NaiveDate, NaiveDateTime};
+    use serde_json::json;
+    use std::{env, fs};
+
+    fn at(minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 1, 1)
+            .unwrap()
+            .and_hms_opt(12, minute, 0)
+            .unwrap()
+    }
+
+    fn entry(minute: u32, device: &str) -> AuditEntry {
+        let request = JsonRpcRequest {
+            method: "deviceExecute".into(),
+            params: json!({"room": "kitchen", "device": device, "command": "switch"}),
+            ..Default::default()
+        };
+        AuditEntry::new(at(minute), &RequestContext::full(), &request, None)
+    }
+
+    #[test]
+    fn test_append_rotate_and_query() {
+        let dir = env::temp_dir().join(format!("smart_home_audit_{}", std::process::id()));
+        let mut audit = AuditLog {
+            max_bytes: 1,
+            max_files: 2,
+            ..Default::default()
+        };
+        audit.set_dir(&dir);
+
+        for minute in 0..4 {
+            audit.append(&entry(minute, "socket-1")).unwrap();
+        }
+        audit.append(&entry(4, "socket-2")).unwrap();
+
+        // каждая запись вытесняет предыдущую в ротированный файл, хранятся два
+        let all = audit.query(&AuditQuery::default()).unwrap();
+        let times: Vec<NaiveDateTime> = all.iter().map(|e| e.ts).collect();
+        assert_eq!(times, vec![at(2), at(3), at(4)]);
+
+        let query = AuditQuery {
+            device: Some("socket-1".into()),
+            ..Default::default()
+        };
+        assert_eq!(audit.query(&query).unwrap().len(), 2);
+
+        let query = AuditQuery {
+            from: Some(at(3)),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let found = audit.query(&query).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].params["device"], "socket-2");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,432 @@
+use super::rule::{Condition, Rule, Trigger};
+use crate::command::device_command::DeviceCommand;
+use crate::events::event::HomeEvent;
+use crate::my_smart_home::error::{SmartHomeError, SmartHomeResult};
+use crate::my_smart_home::home::Home;
+use crate::my_smart_home::storage::{load_json, save_json};
+use crate::smart_device::device::{DeviceState, Readings};
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Сработавшее правило и команды, которые нужно выполнить
+#[derive(Debug, PartialEq)]
+pub struct FiredRule {
+    pub rule: String,
+    pub actions: Vec<DeviceCommand>,
+}
+
+/// Движок правил автоматизации.
+/// Правила проверяются домом на каждом событии устройства и на каждом тике часов.
+#[derive(Default)]
+pub struct RuleEngine {
+    rules: Vec<Rule>,
+    /// результат проверки порога на предыдущем показании, правило срабатывает по фронту
+    reading_matched: HashMap<String, bool>,
+    /// статус устройства и время перехода в него, по идентификатору устройства
+    since: HashMap<String, (DeviceState, NaiveDateTime)>,
+    /// время перехода в статус, для которого правило state_duration уже сработало
+    duration_fired: HashMap<String, NaiveDateTime>,
+    last_tick: Option<NaiveDateTime>,
+}
+
+impl RuleEngine {
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn add(&mut self, rule: Rule) -> SmartHomeResult<()> {
+        if self.rules.iter().any(|r| r.id == rule.id) {
+            return Err(SmartHomeError::RuleSameIdExist(rule.id));
+        }
+        rule.trigger
+            .validate()
+            .map_err(|reason| SmartHomeError::InvalidRule {
+                id: rule.id.clone(),
+                reason,
+            })?;
+        self.rules.push(rule);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: &str) -> SmartHomeResult<Rule> {
+        match self.rules.iter().position(|r| r.id == id) {
+            Some(pos) => {
+                self.reading_matched.remove(id);
+                self.duration_fired.remove(id);
+                Ok(self.rules.remove(pos))
+            }
+            None => Err(SmartHomeError::RuleNonExist(id.to_string())),
+        }
+    }
+
+    pub fn set_enabled(&mut self, id: &str, enabled: bool) -> SmartHomeResult<()> {
+        match self.rules.iter_mut().find(|r| r.id == id) {
+            Some(rule) => {
+                rule.enabled = enabled;
+                Ok(())
+            }
+            None => Err(SmartHomeError::RuleNonExist(id.to_string())),
+        }
+    }
+
+    /// комната переименована вместе с вложенными
+    pub fn relocate_room(&mut self, from: &str, to: &str) {
+        for rule in self.rules.iter_mut() {
+            rule.relocate_room(from, to);
+        }
+    }
+
+    /// устройство с идентификатором или именем из `names` перенесено из `room` в `to`
+    pub fn move_device(&mut self, room: &str, names: &[&str], to: &str) {
+        for rule in self.rules.iter_mut() {
+            rule.move_device(room, names, to);
+        }
+    }
+
+    /// добавить правила из json-файла с массивом правил, возвращает число добавленных
+    pub fn load_file(&mut self, path: &Path) -> SmartHomeResult<usize> {
+        let rules: Vec<Rule> = load_json(path)?;
+        let count = rules.len();
+        for rule in rules {
+            self.add(rule)?;
+        }
+        Ok(count)
+    }
+
+    /// сохранить правила в json-файл
+    pub fn save_file(&self, path: &Path) -> SmartHomeResult<()> {
+        save_json(path, &self.rules)
+    }
+
+    /// проверить правила, запускаемые событием устройства
+    pub fn on_event(
+        &mut self,
+        home: &Home,
+        event: &HomeEvent,
+        now: NaiveDateTime,
+    ) -> Vec<FiredRule> {
+        let mut fired = vec![];
+
+        match event {
+            HomeEvent::StateChanged {
+                room, device, to, ..
+            } => {
+                self.since.insert(device.clone(), (*to, now));
+
+                for rule in self.rules.iter().filter(|r| r.enabled) {
+                    let Trigger::StateChange {
+                        room: r_room,
+                        device: r_device,
+                        to: r_to,
+                    } = &rule.trigger
+                    else {
+                        continue;
+                    };
+                    if is_same_device(home, r_room, r_device, room, device)
+                        && r_to.is_none_or(|state| state == *to)
+                        && conditions_met(home, &rule.conditions, now)
+                    {
+                        fired.push(fire(rule));
+                    }
+                }
+            }
+
+            HomeEvent::ReadingUpdated {
+                room,
+                device,
+                reading,
+                value,
+            } => {
+                for rule in self.rules.iter().filter(|r| r.enabled) {
+                    let Trigger::Reading {
+                        room: r_room,
+                        device: r_device,
+                        reading: r_reading,
+                        op,
+                        value: threshold,
+                    } = &rule.trigger
+                    else {
+                        continue;
+                    };
+                    if r_reading != reading || !is_same_device(home, r_room, r_device, room, device)
+                    {
+                        continue;
+                    }
+                    let matched = op.check(*value, *threshold);
+                    let was_matched = self
+                        .reading_matched
+                        .insert(rule.id.clone(), matched)
+                        .unwrap_or(false);
+                    if matched && !was_matched && conditions_met(home, &rule.conditions, now) {
+                        fired.push(fire(rule));
+                    }
+                }
+            }
+
+            _ => {}
+        }
+
+        fired
+    }
+
+    /// проверить правила, зависящие от времени
+    pub fn on_tick(&mut self, home: &Home, now: NaiveDateTime) -> Vec<FiredRule> {
+        let mut fired = vec![];
+        let last_tick = self.last_tick.replace(now);
+
+        for rule in self.rules.iter().filter(|r| r.enabled) {
+            match &rule.trigger {
+                Trigger::StateDuration {
+                    room,
+                    device,
+                    state,
+                    ..
+                } => {
+                    let Some((id, current, _)) = device_status(home, room, device) else {
+                        continue;
+                    };
+                    let entry = self.since.entry(id).or_insert((current, now));
+                    if entry.0 != current {
+                        *entry = (current, now);
+                    }
+                    let entered = entry.1;
+                    if current == *state
+                        && rule
+                            .trigger
+                            .duration()
+                            .is_some_and(|duration| now - entered >= duration)
+                        && self.duration_fired.get(&rule.id) != Some(&entered)
+                        && conditions_met(home, &rule.conditions, now)
+                    {
+                        self.duration_fired.insert(rule.id.clone(), entered);
+                        fired.push(fire(rule));
+                    }
+                }
+
+                Trigger::Time { at } => {
+                    let Some(last_tick) = last_tick else {
+                        continue;
+                    };
+                    // тик мог перешагнуть через полночь
+                    let passed = [last_tick.date(), now.date()]
+                        .iter()
+                        .map(|day| day.and_time(*at))
+                        .any(|moment| last_tick < moment && moment <= now);
+                    if passed && conditions_met(home, &rule.conditions, now) {
+                        fired.push(fire(rule));
+                    }
+                }
+
+                _ => {}
+            }
+        }
+
+        fired
+    }
+}
+
+fn fire(rule: &Rule) -> FiredRule {
+    FiredRule {
+        rule: rule.id.clone(),
+        actions: rule.actions.clone(),
+    }
+}
+
+/// правило ссылается на устройство по имени или идентификатору, событие - по идентификатору
+fn is_same_device(home: &Home, r_room: &str, r_device: &str, room: &str, id: &str) -> bool {
+    r_room == room && home.resolve_device(r_room, r_device).as_deref() == Some(id)
+}
+
+fn device_status(home: &Home, room: &str, device: &str) -> Option<(String, DeviceState, Readings)> {
+    let id = home.resolve_device(room, device)?;
+    let dev = &home.rooms[room][&id];
+    Some((id, dev.device_state(), dev.get_readings()))
+}
+
+fn conditions_met(home: &Home, conditions: &[Condition], now: NaiveDateTime) -> bool {
+    conditions.iter().all(|condition| match condition {
+        Condition::State {
+            room,
+            device,
+            state,
+        } => device_status(home, room, device).is_some_and(|(_, current, _)| current == *state),
+
+        Condition::Reading {
+            room,
+            device,
+            reading,
+            op,
+            value,
+        } => device_status(home, room, device)
+            .and_then(|(_, _, readings)| readings.get(reading).copied())
+            .is_some_and(|current| op.check(current, *value)),
+
+        Condition::TimeBetween { from, to } => {
+            let time = now.time();
+            match from <= to {
+                true => *from <= time && time < *to,
+                false => time >= *from || time < *to,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::automation::rule::Rule;
+    use crate::clock::ManualClock;
+    use crate::command::device_command::DeviceCommand;
+    use crate::my_smart_home::home::Home;
+    use crate::my_smart_home::smart_home::SmartHome;
+    use crate::smart_device::device::{DeviceState, VecOfDevice};
+    use crate::smart_device::kettle::Kettle;
+    use crate::smart_device::socket::Socket;
+    use crate::smart_device::thermometer::Thermometer;
+    use chrono::{NaiveDate, TimeDelta};
+    use serde_json::json;
+    use std::env;
+
+    fn setup() -> (Home, ManualClock) {
+        let clock = ManualClock::new(
+            NaiveDate::from_ymd_opt(2025, 1, 10)
+                .unwrap()
+                .and_hms_opt(22, 0, 0)
+                .unwrap(),
+        );
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.clock = Box::new(clock.clone());
+
+        let living: VecOfDevice = vec![Box::new(Socket::new("2")), Box::new(Thermometer::new("1"))];
+        home.add_room("living".into(), living).unwrap();
+        home.add_room("kitchen".into(), vec![Box::new(Kettle::new("1"))])
+            .unwrap();
+        (home, clock)
+    }
+
+    fn rule(value: serde_json::Value) -> Rule {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn state(home: &Home, room: &str, device: &str) -> DeviceState {
+        home.get_device(room, device).unwrap().device_state()
+    }
+
+    fn set_temperature(home: &mut Home, value: f32) {
+        home.with_device("living", "Thermometer 1", |d| {
+            d.update_reading("temperature", value)
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_reading_rule_fires_on_crossing() {
+        let (mut home, _) = setup();
+        home.rules
+            .add(rule(json!({
+                "id": "heating",
+                "trigger": {"type": "reading", "room": "living", "device": "Thermometer 1",
+                            "reading": "temperature", "op": "<", "value": 18.0},
+                "actions": [{"room": "living", "device": "Smart Socket 2",
+                             "command": "switch", "data": ["on"]}]
+            })))
+            .unwrap();
+
+        set_temperature(&mut home, 21.0);
+        assert_eq!(state(&home, "living", "socket-2"), DeviceState::Off);
+
+        set_temperature(&mut home, 17.0);
+        assert_eq!(state(&home, "living", "socket-2"), DeviceState::On);
+
+        // пока температура ниже порога, правило повторно не срабатывает
+        home.execute_device_command(&DeviceCommand {
+            room: "living".into(),
+            device: "socket-2".into(),
+            command: "switch".into(),
+            data: vec!["off".into()],
+        })
+        .unwrap();
+        set_temperature(&mut home, 16.0);
+        assert_eq!(state(&home, "living", "socket-2"), DeviceState::Off);
+
+        // выключенное правило не срабатывает
+        home.rules.set_enabled("heating", false).unwrap();
+        set_temperature(&mut home, 20.0);
+        set_temperature(&mut home, 15.0);
+        assert_eq!(state(&home, "living", "socket-2"), DeviceState::Off);
+    }
+
+    #[test]
+    fn test_state_duration_rule() {
+        let (mut home, clock) = setup();
+        home.rules
+            .add(rule(json!({
+                "id": "kettle-guard",
+                "trigger": {"type": "state_duration", "room": "kitchen",
+                            "device": "Smart Kettle 1", "state": "on", "minutes": 10},
+                "actions": [{"room": "kitchen", "device": "kettle-1",
+                             "command": "switch", "data": ["off"]}]
+            })))
+            .unwrap();
+
+        home.tick();
+        home.with_device("kitchen", "kettle-1", |d| d.switch("on"))
+            .unwrap();
+
+        clock.advance(TimeDelta::minutes(9));
+        home.tick();
+        assert_eq!(state(&home, "kitchen", "kettle-1"), DeviceState::On);
+
+        clock.advance(TimeDelta::minutes(2));
+        home.tick();
+        assert_eq!(state(&home, "kitchen", "kettle-1"), DeviceState::Off);
+    }
+
+    #[test]
+    fn test_time_rule_with_condition() {
+        let (mut home, clock) = setup();
+        home.rules
+            .add(rule(json!({
+                "id": "night",
+                "trigger": {"type": "time", "at": "23:00"},
+                "conditions": [{"type": "state", "room": "living",
+                                "device": "socket-2", "state": "off"}],
+                "actions": [{"room": "living", "device": "socket-2",
+                             "command": "switch", "data": ["on"]}]
+            })))
+            .unwrap();
+
+        home.tick();
+        clock.advance(TimeDelta::minutes(59));
+        home.tick();
+        assert_eq!(state(&home, "living", "socket-2"), DeviceState::Off);
+
+        clock.advance(TimeDelta::minutes(2));
+        home.tick();
+        assert_eq!(state(&home, "living", "socket-2"), DeviceState::On);
+    }
+
+    #[test]
+    fn test_load_and_save_rules_file() {
+        let (mut home, _) = setup();
+        home.rules
+            .add(rule(json!({
+                "id": "night",
+                "name": "свет на ночь",
+                "trigger": {"type": "time", "at": "23:00"},
+                "actions": []
+            })))
+            .unwrap();
+
+        let path = env::temp_dir().join(format!("smart_home_rules_{}.json", std::process::id()));
+        home.rules.save_file(&path).unwrap();
+
+        let (mut other, _) = setup();
+        assert_eq!(other.rules.load_file(&path).unwrap(), 1);
+        assert_eq!(other.rules.rules(), home.rules.rules());
+
+        // повторная загрузка тех же правил - ошибка
+        assert!(other.rules.load_file(&path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,180 @@
+use crate::command::device_command::DeviceCommand;
+use crate::my_smart_home::location::{move_device_ref, relocate_ref};
+use crate::smart_device::device::DeviceState;
+use chrono::{NaiveTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+
+/// Операция сравнения показания с порогом
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CompareOp {
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Le,
+    #[serde(rename = ">")]
+    Gt,
+    #[serde(rename = ">=")]
+    Ge,
+}
+
+impl CompareOp {
+    /// знак операции, как в json
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        }
+    }
+
+    pub fn check(&self, value: f32, threshold: f32) -> bool {
+        match self {
+            CompareOp::Lt => value < threshold,
+            CompareOp::Le => value <= threshold,
+            CompareOp::Gt => value > threshold,
+            CompareOp::Ge => value >= threshold,
+        }
+    }
+}
+
+/// Событие, запускающее правило
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Trigger {
+    /// устройство сменило статус (на заданный, если указан)
+    StateChange {
+        room: String,
+        device: String,
+        #[serde(default)]
+        to: Option<DeviceState>,
+    },
+
+    /// показание устройства начало удовлетворять условию
+    Reading {
+        room: String,
+        device: String,
+        reading: String,
+        op: CompareOp,
+        value: f32,
+    },
+
+    /// устройство находится в статусе дольше заданного числа минут
+    StateDuration {
+        room: String,
+        device: String,
+        state: DeviceState,
+        minutes: i64,
+    },
+
+    /// ежедневно в заданное время
+    Time { at: NaiveTime },
+}
+
+impl Trigger {
+    /// проверить параметры триггера
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Trigger::StateDuration { minutes, .. } => match TimeDelta::try_minutes(*minutes) {
+                Some(_) if *minutes >= 0 => Ok(()),
+                Some(_) => Err("duration must not be negative".into()),
+                None => Err(format!("duration is too long: {minutes} minutes")),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// длительность для триггера state_duration
+    pub fn duration(&self) -> Option<TimeDelta> {
+        match self {
+            Trigger::StateDuration { minutes, .. } => TimeDelta::try_minutes(*minutes),
+            _ => None,
+        }
+    }
+}
+
+/// Дополнительное условие, проверяемое при срабатывании триггера
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Condition {
+    State {
+        room: String,
+        device: String,
+        state: DeviceState,
+    },
+
+    Reading {
+        room: String,
+        device: String,
+        reading: String,
+        op: CompareOp,
+        value: f32,
+    },
+
+    /// текущее время в интервале [from, to), интервал может переходить через полночь
+    TimeBetween { from: NaiveTime, to: NaiveTime },
+}
+
+/// Правило автоматизации: триггер, условия и команды устройствам
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    pub trigger: Trigger,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    pub actions: Vec<DeviceCommand>,
+}
+
+impl Rule {
+    /// комната переименована вместе с вложенными
+    pub fn relocate_room(&mut self, from: &str, to: &str) {
+        match &mut self.trigger {
+            Trigger::StateChange { room, .. }
+            | Trigger::Reading { room, .. }
+            | Trigger::StateDuration { room, .. } => relocate_ref(room, from, to),
+            Trigger::Time { .. } => {}
+        }
+        for condition in self.conditions.iter_mut() {
+            match condition {
+                Condition::State { room, .. } | Condition::Reading { room, .. } => {
+                    relocate_ref(room, from, to)
+                }
+                Condition::TimeBetween { .. } => {}
+            }
+        }
+        for action in self.actions.iter_mut() {
+            action.relocate_room(from, to);
+        }
+    }
+
+    /// устройство с идентификатором или именем из `names` перенесено из `from` в `to`
+    pub fn move_device(&mut self, from: &str, names: &[&str], to: &str) {
+        match &mut self.trigger {
+            Trigger::StateChange { room, device, .. }
+            | Trigger::Reading { room, device, .. }
+            | Trigger::StateDuration { room, device, .. } => {
+                move_device_ref(room, device, from, names, to)
+            }
+            Trigger::Time { .. } => {}
+        }
+        for condition in self.conditions.iter_mut() {
+            match condition {
+                Condition::State { room, device, .. } | Condition::Reading { room, device, .. } => {
+                    move_device_ref(room, device, from, names, to)
+                }
+                Condition::TimeBetween { .. } => {}
+            }
+        }
+        for action in self.actions.iter_mut() {
+            action.move_device(from, names, to);
+        }
+    }
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
@@ -0,0 +1,47 @@
+use chrono::{Local, NaiveDateTime, TimeDelta};
+use std::sync::{Arc, Mutex};
+
+/// Источник текущего времени дома.
+/// Подменяется в тестах, чтобы перематывать время.
+pub trait Clock: Send {
+    /// текущее локальное время
+    fn now(&self) -> NaiveDateTime;
+}
+
+/// Системные часы
+#[derive(Default, Clone)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+}
+
+/// Часы, управляемые вручную. Клоны разделяют одно и то же время.
+#[derive(Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<NaiveDateTime>>,
+}
+
+impl ManualClock {
+    pub fn new(now: NaiveDateTime) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    pub fn set(&self, now: NaiveDateTime) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, delta: TimeDelta) {
+        *self.now.lock().unwrap() += delta;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> NaiveDateTime {
+        *self.now.lock().unwrap()
+    }
+}
@@ -0,0 +1,75 @@
+use crate::my_smart_home::error::SmartHomeError;
+use crate::my_smart_home::location::{move_device_ref, relocate_ref};
+use crate::smart_device::device::SmartDevice;
+use crate::smart_device::fault::Fault;
+use serde::{Deserialize, Serialize};
+use thiserror;
+
+/// Команда устройству в комнате, как в запросе deviceExecute
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeviceCommand {
+    pub room: String,
+    /// идентификатор или имя устройства
+    pub device: String,
+    pub command: String,
+    #[serde(default)]
+    pub data: Vec<String>,
+}
+
+impl DeviceCommand {
+    /// комната переименована вместе с вложенными
+    pub fn relocate_room(&mut self, from: &str, to: &str) {
+        relocate_ref(&mut self.room, from, to);
+    }
+
+    /// устройство с идентификатором или именем из `names` перенесено из `room` в `to`
+    pub fn move_device(&mut self, room: &str, names: &[&str], to: &str) {
+        move_device_ref(&mut self.room, &self.device, room, names, to);
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CommandError {
+    #[error("wrong device command: {0}")]
+    UnknownCommand(String),
+
+    #[error("{0}")]
+    InvalidData(String),
+
+    #[error("{0}")]
+    Rejected(String),
+
+    #[error(transparent)]
+    Home(#[from] SmartHomeError),
+}
+
+/// выполнить команду deviceExecute над устройством;
+/// показания задает само устройство, клиенты их не меняют
+pub fn execute_command(
+    dev: &mut dyn SmartDevice,
+    command: &str,
+    data: &[String],
+) -> Result<String, CommandError> {
+    let arg = |i: usize| data.get(i).map(String::as_str);
+
+    match command {
+        "get_name" => Ok(dev.get_name()),
+        "get_description" => Ok(dev.get_description()),
+        "get_current_info" => Ok(dev.get_current_info()),
+        "report" => Ok(dev.report()),
+        "switch" => match arg(0) {
+            Some(state) => Ok(dev.switch(state)),
+            None => Err(CommandError::InvalidData(
+                "wrong status provided: none".to_string(),
+            )),
+        },
+        "fault" => {
+            let code = arg(0).unwrap_or("unknown");
+            let description = arg(1).unwrap_or_default();
+            let recoverable = arg(2) != Some("permanent");
+            Ok(dev.report_fault(Fault::new(code, description, recoverable)))
+        }
+        "repair" | "reset" => dev.repair().map_err(CommandError::Rejected),
+        _ => Err(CommandError::UnknownCommand(command.to_string())),
+    }
+}
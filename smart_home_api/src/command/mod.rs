@@ -1 +1,2 @@
+pub mod device_command;
 pub mod queue;
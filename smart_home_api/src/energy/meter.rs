@@ -0,0 +1,85 @@
+use crate::my_smart_home::error::SmartHomeResult;
+use crate::my_smart_home::storage::{load_json, save_json};
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Счетчик электроэнергии: накопленные кВт*ч по устройствам и дням
+#[derive(Default, Serialize, Deserialize)]
+pub struct EnergyMeter {
+    /// цена одного кВт*ч для оценки стоимости
+    #[serde(default)]
+    pub tariff: Option<f64>,
+    /// идентификатор устройства -> день -> кВт*ч
+    #[serde(default)]
+    daily: BTreeMap<String, BTreeMap<NaiveDate, f64>>,
+    /// момент последнего замера и мощность в Вт, потребляемая с этого момента
+    #[serde(skip)]
+    last: HashMap<String, (NaiveDateTime, f32)>,
+}
+
+impl EnergyMeter {
+    /// учесть потребление устройства до момента `now`;
+    /// `power` - мощность в Вт с этого момента, 0 - если устройство выключено
+    pub fn integrate(&mut self, id: &str, power: f32, now: NaiveDateTime) {
+        if let Some((mut from, last_power)) = self.last.get(id).copied() {
+            if last_power > 0.0 {
+                let days = self.daily.entry(id.to_string()).or_default();
+                // потребление за интервал раскладывается по календарным дням
+                while from < now {
+                    let midnight = (from.date() + TimeDelta::days(1)).and_time(Default::default());
+                    let to = midnight.min(now);
+                    let hours = (to - from).num_milliseconds() as f64 / 3_600_000.0;
+                    *days.entry(from.date()).or_default() += last_power as f64 * hours / 1000.0;
+                    from = to;
+                }
+            }
+        }
+        self.last.insert(id.to_string(), (now, power));
+    }
+
+    /// кВт*ч устройства за дни [from, to]
+    pub fn consumption(&self, id: &str, from: NaiveDate, to: NaiveDate) -> f64 {
+        self.daily
+            .get(id)
+            .map(|days| days.range(from..=to).map(|(_, kwh)| kwh).sum())
+            .unwrap_or_default()
+    }
+
+    /// загрузить накопленные показания из json-файла
+    pub fn load_file(&mut self, path: &Path) -> SmartHomeResult<()> {
+        let meter: EnergyMeter = load_json(path)?;
+        self.tariff = meter.tariff;
+        self.daily = meter.daily;
+        Ok(())
+    }
+
+    pub fn save_file(&self, path: &Path) -> SmartHomeResult<()> {
+        save_json(path, self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::EnergyMeter;
+    use chrono::{NaiveDate, TimeDelta};
+
+    #[test]
+    fn test_integrate_across_midnight() {
+        let day = NaiveDate::from_ymd_opt(2025, 1, 10).unwrap();
+        let next = day.succ_opt().unwrap();
+        let start = day.and_hms_opt(23, 0, 0).unwrap();
+
+        let mut meter = EnergyMeter::default();
+        meter.integrate("socket-1", 1000.0, start);
+        meter.integrate("socket-1", 0.0, start + TimeDelta::hours(2));
+        // выключенное устройство не потребляет
+        meter.integrate("socket-1", 0.0, start + TimeDelta::hours(5));
+
+        assert!((meter.consumption("socket-1", day, day) - 1.0).abs() < 1e-9);
+        assert!((meter.consumption("socket-1", next, next) - 1.0).abs() < 1e-9);
+        assert!((meter.consumption("socket-1", day, next) - 2.0).abs() < 1e-9);
+        assert_eq!(meter.consumption("socket-2", day, next), 0.0);
+    }
+}
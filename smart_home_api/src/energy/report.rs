@@ -0,0 +1,91 @@
+use chrono::{Datelike, NaiveDate, TimeDelta};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// Период отчета о потреблении
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnergyPeriod {
+    /// текущий день
+    Day,
+    /// текущая неделя, с понедельника
+    Week,
+    /// текущий месяц
+    Month,
+}
+
+impl EnergyPeriod {
+    /// первый и последний дни периода, в который входит `today`
+    pub fn range(&self, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let from = match self {
+            EnergyPeriod::Day => today,
+            EnergyPeriod::Week => {
+                today - TimeDelta::days(today.weekday().num_days_from_monday() as i64)
+            }
+            EnergyPeriod::Month => today.with_day(1).unwrap(),
+        };
+        (from, today)
+    }
+}
+
+/// Потребление одного устройства
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DeviceEnergy {
+    pub device: String,
+    pub name: String,
+    pub kwh: f64,
+    pub cost: Option<f64>,
+}
+
+/// Потребление комнаты
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RoomEnergy {
+    pub room: String,
+    pub kwh: f64,
+    pub cost: Option<f64>,
+    pub devices: Vec<DeviceEnergy>,
+}
+
+/// Отчет о потреблении дома за период
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EnergyReport {
+    pub period: EnergyPeriod,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub tariff: Option<f64>,
+    pub kwh: f64,
+    pub cost: Option<f64>,
+    pub rooms: Vec<RoomEnergy>,
+}
+
+impl EnergyReport {
+    /// текстовый отчет, в стиле отчета о состоянии дома
+    pub fn render(&self) -> String {
+        let mut report = format!(
+            "Потребление с {} по {}: {}",
+            self.from,
+            self.to,
+            amount(self.kwh, self.cost)
+        );
+        for room in &self.rooms {
+            let _ = write!(report, "\n\n{}: {}", room.room, amount(room.kwh, room.cost));
+            for device in &room.devices {
+                let _ = write!(
+                    report,
+                    "\n--> {} ({}): {}",
+                    device.name,
+                    device.device,
+                    amount(device.kwh, device.cost)
+                );
+            }
+        }
+        report
+    }
+}
+
+fn amount(kwh: f64, cost: Option<f64>) -> String {
+    match cost {
+        Some(cost) => format!("{kwh:.3} кВт*ч, {cost:.2}"),
+        None => format!("{kwh:.3} кВт*ч"),
+    }
+}
@@ -0,0 +1,61 @@
+use super::event::HomeEvent;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+pub type SubscriptionId = usize;
+
+type Callback = Box<dyn FnMut(&HomeEvent) + Send>;
+
+/// Шина событий дома.
+/// Подписчики получают события через callback или через канал.
+#[derive(Default)]
+pub struct EventBus {
+    next_id: SubscriptionId,
+    callbacks: Vec<(SubscriptionId, Callback)>,
+    channels: Vec<(SubscriptionId, Sender<HomeEvent>)>,
+}
+
+impl EventBus {
+    /// подписаться callback-функцией
+    pub fn subscribe<F>(&mut self, callback: F) -> SubscriptionId
+    where
+        F: FnMut(&HomeEvent) + Send + 'static,
+    {
+        let id = self.next_subscription();
+        self.callbacks.push((id, Box::new(callback)));
+        id
+    }
+
+    /// подписаться через канал, события можно читать из другого потока
+    pub fn subscribe_channel(&mut self) -> (SubscriptionId, Receiver<HomeEvent>) {
+        let id = self.next_subscription();
+        let (tx, rx) = channel();
+        self.channels.push((id, tx));
+        (id, rx)
+    }
+
+    /// отписаться, false - если подписки нет
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let before = self.callbacks.len() + self.channels.len();
+        self.callbacks.retain(|(sub, _)| *sub != id);
+        self.channels.retain(|(sub, _)| *sub != id);
+        before != self.callbacks.len() + self.channels.len()
+    }
+
+    /// разослать событие подписчикам, закрытые каналы удаляются
+    pub fn publish(&mut self, event: &HomeEvent) {
+        for (_, callback) in self.callbacks.iter_mut() {
+            callback(event);
+        }
+        self.channels
+            .retain(|(_, tx)| tx.send(event.clone()).is_ok());
+    }
+
+    pub fn subscribers(&self) -> usize {
+        self.callbacks.len() + self.channels.len()
+    }
+
+    fn next_subscription(&mut self) -> SubscriptionId {
+        self.next_id += 1;
+        self.next_id
+    }
+}
@@ -0,0 +1,70 @@
+use crate::alarms::alarm::AlarmRecord;
+use crate::smart_device::device::DeviceState;
+use crate::smart_device::fault::Fault;
+use serde::Serialize;
+
+/// Событие дома, публикуемое в шину событий
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HomeEvent {
+    /// устройство сменило статус
+    StateChanged {
+        room: String,
+        device: String,
+        from: DeviceState,
+        to: DeviceState,
+    },
+
+    /// изменилось показание устройства
+    ReadingUpdated {
+        room: String,
+        device: String,
+        reading: String,
+        value: f32,
+    },
+
+    /// устройство перешло в статус Broken
+    FaultDetected {
+        room: String,
+        device: String,
+        fault: Option<Fault>,
+    },
+
+    /// сработало правило автоматизации, результаты его команд
+    RuleFired { rule: String, outcomes: Vec<String> },
+
+    /// выполнено расписание, результаты его команд
+    ScheduleFired {
+        schedule: String,
+        outcomes: Vec<String>,
+    },
+
+    /// тревога поднята, подтверждена или снята
+    AlarmChanged { record: AlarmRecord },
+}
+
+impl HomeEvent {
+    /// вид события, как в поле "event" json-представления
+    pub fn kind(&self) -> &'static str {
+        match self {
+            HomeEvent::StateChanged { .. } => "state_changed",
+            HomeEvent::ReadingUpdated { .. } => "reading_updated",
+            HomeEvent::FaultDetected { .. } => "fault_detected",
+            HomeEvent::RuleFired { .. } => "rule_fired",
+            HomeEvent::ScheduleFired { .. } => "schedule_fired",
+            HomeEvent::AlarmChanged { .. } => "alarm_changed",
+        }
+    }
+
+    /// комната и идентификатор устройства, к которому относится событие
+    pub fn device(&self) -> Option<(&str, &str)> {
+        match self {
+            HomeEvent::StateChanged { room, device, .. }
+            | HomeEvent::ReadingUpdated { room, device, .. }
+            | HomeEvent::FaultDetected { room, device, .. } => Some((room, device)),
+            HomeEvent::RuleFired { .. }
+            | HomeEvent::ScheduleFired { .. }
+            | HomeEvent::AlarmChanged { .. } => None,
+        }
+    }
+}
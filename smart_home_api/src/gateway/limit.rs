@@ -0,0 +1,194 @@
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Соединение клиента, еще не прошедшего handshake: срок на весь handshake
+/// и предел прочитанных байт, так что медленный или болтливый клиент не займет поток надолго.
+/// После handshake ограничения снимает `unlimit`.
+pub(crate) struct LimitedStream {
+    stream: TcpStream,
+    /// None - ограничения сняты
+    deadline: Option<Instant>,
+    /// сколько еще байт можно прочитать
+    left: usize,
+}
+
+impl LimitedStream {
+    pub(crate) fn new(stream: TcpStream, timeout: Duration, max_read: usize) -> Self {
+        Self {
+            stream,
+            deadline: Some(Instant::now() + timeout),
+            left: max_read,
+        }
+    }
+
+    pub(crate) fn get_ref(&self) -> &TcpStream {
+        &self.stream
+    }
+
+    /// снять срок и предел после handshake
+    pub(crate) fn unlimit(&mut self) -> io::Result<()> {
+        self.deadline = None;
+        self.stream.set_read_timeout(None)?;
+        self.stream.set_write_timeout(None)
+    }
+
+    fn remaining(&self, deadline: Instant) -> io::Result<Duration> {
+        match deadline.saturating_duration_since(Instant::now()) {
+            Duration::ZERO => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "handshake timed out",
+            )),
+            left => Ok(left),
+        }
+    }
+}
+
+impl Read for LimitedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(deadline) = self.deadline else {
+            return self.stream.read(buf);
+        };
+        if self.left == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "handshake too large",
+            ));
+        }
+        self.stream
+            .set_read_timeout(Some(self.remaining(deadline)?))?;
+        let len = buf.len().min(self.left);
+        let read = self.stream.read(&mut buf[..len])?;
+        self.left -= read;
+        Ok(read)
+    }
+}
+
+impl Write for LimitedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(deadline) = self.deadline {
+            self.stream
+                .set_write_timeout(Some(self.remaining(deadline)?))?;
+        }
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Чтение со сроком на все чтение, когда сокет недоступен и таймаут на него не поставить:
+/// срок проверяется перед каждым чтением, так что клиент, присылающий данные по байту,
+/// не затянет чтение дольше срока.
+pub(crate) struct DeadlineReader<R> {
+    inner: R,
+    deadline: Instant,
+}
+
+impl<R: Read> DeadlineReader<R> {
+    pub(crate) fn new(inner: R, timeout: Duration) -> Self {
+        Self {
+            inner,
+            deadline: Instant::now() + timeout,
+        }
+    }
+}
+
+impl<R: Read> Read for DeadlineReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if Instant::now() >= self.deadline {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "read timed out"));
+        }
+        self.inner.read(buf)
+    }
+}
+
+/// Выполнить `job` в отдельном потоке, пока таких потоков не больше `max`;
+/// false - предел исчерпан или поток не запустился, `job` не выполняется.
+pub(crate) fn spawn_bounded(
+    active: &Arc<AtomicUsize>,
+    max: usize,
+    job: impl FnOnce() + Send + 'static,
+) -> bool {
+    if active.fetch_add(1, Ordering::SeqCst) >= max {
+        active.fetch_sub(1, Ordering::SeqCst);
+        return false;
+    }
+    let counter = active.clone();
+    let spawned = thread::Builder::new().spawn(move || {
+        job();
+        counter.fetch_sub(1, Ordering::SeqCst);
+    });
+    if spawned.is_err() {
+        active.fetch_sub(1, Ordering::SeqCst);
+    }
+    spawned.is_ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        (listener.accept().unwrap().0, client)
+    }
+
+    #[test]
+    fn test_limited_stream() {
+        let (server, mut client) = pair();
+        client.write_all(b"0123456789").unwrap();
+        let mut stream = LimitedStream::new(server, Duration::from_secs(5), 4);
+        let mut buf = [0; 16];
+        assert_eq!(stream.read(&mut buf).unwrap(), 4);
+        let err = stream.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        stream.unlimit().unwrap();
+        assert_eq!(stream.read(&mut buf).unwrap(), 6);
+
+        // молчащий клиент не держит поток дольше срока
+        let (server, _client) = pair();
+        let mut stream = LimitedStream::new(server, Duration::from_millis(100), 4);
+        let started = Instant::now();
+        assert!(stream.read(&mut buf).is_err());
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_deadline_reader() {
+        /// отдает по байту с паузой
+        struct Slow;
+        impl Read for Slow {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                thread::sleep(Duration::from_millis(20));
+                buf[0] = b'a';
+                Ok(1)
+            }
+        }
+        let mut body = String::new();
+        let mut reader = DeadlineReader::new(Slow.take(1000), Duration::from_millis(100));
+        let err = reader.read_to_string(&mut body).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_spawn_bounded() {
+        let active = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = std::sync::mpsc::channel::<()>();
+        assert!(spawn_bounded(&active, 1, move || {
+            let _ = rx.recv();
+        }));
+        assert!(!spawn_bounded(&active, 1, || ()));
+        drop(tx);
+        while active.load(Ordering::SeqCst) > 0 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(spawn_bounded(&active, 1, || ()));
+    }
+}
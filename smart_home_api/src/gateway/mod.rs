@@ -0,0 +1,4 @@
+pub(crate) mod limit;
+pub mod route;
+pub mod server;
+pub mod ws;
@@ -0,0 +1,164 @@
+use crate::json_rpc::request::JsonRpcRequest;
+use percent_encoding::percent_decode_str;
+use serde_json::{json, Map, Value};
+
+/// идентификатор запросов JSON-RPC, в которые превращаются запросы REST
+const REST_ID: &str = "rest";
+
+/// Что выполнить для HTTP-запроса.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Route {
+    /// `POST /rpc`: тело - пакет JSON-RPC как есть
+    Rpc(String),
+    /// ресурс REST, приведенный к одному запросу JSON-RPC
+    Call(JsonRpcRequest),
+}
+
+/// Ошибка разбора запроса REST.
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum RouteError {
+    #[error("not found: {0}")]
+    NotFound(String),
+
+    #[error("bad request: {0}")]
+    BadRequest(String),
+}
+
+/// сопоставить HTTP-запрос ресурсу; имена комнат с '/' передаются в пути как %2F
+pub fn route(method: &str, url: &str, body: &str) -> Result<Route, RouteError> {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(decode)
+        .collect::<Result<Vec<_>, _>>()?;
+    let query = parse_query(query)?;
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+    let (rpc_method, params) = match (method, segments.as_slice()) {
+        ("POST", ["rpc"]) => return Ok(Route::Rpc(body.to_string())),
+
+        ("GET", ["rooms"]) => ("getLocations", json!({})),
+        ("POST", ["rooms"]) => ("addRoom", Value::Object(body_object(body)?)),
+        ("DELETE", ["rooms", room]) => ("delRoom", json!({"name": room})),
+        ("GET", ["rooms", room, "devices"]) => ("getDevices", json!({"room": room})),
+        ("POST", ["rooms", room, "devices", device, "switch"]) => {
+            let state = body_object(body)?
+                .get("state")
+                .and_then(Value::as_str)
+                .map(String::from)
+                .ok_or_else(|| RouteError::BadRequest("state \"on\" or \"off\" expected".into()))?;
+            let params =
+                json!({"room": room, "device": device, "command": "switch", "data": [state]});
+            ("deviceExecute", params)
+        }
+        ("GET", ["report"]) => match query_value(&query, "location") {
+            Some(location) => ("createReport", json!({"location": location})),
+            None => ("createReport", json!({})),
+        },
+
+        _ => return Err(RouteError::NotFound(format!("{method} {path}"))),
+    };
+
+    Ok(Route::Call(JsonRpcRequest {
+        id: REST_ID.into(),
+        jsonrpc: "2.0".into(),
+        method: rpc_method.into(),
+        params,
+        home: query_value(&query, "home").map(String::from),
+    }))
+}
+
+fn decode(value: &str) -> Result<String, RouteError> {
+    percent_decode_str(value)
+        .decode_utf8()
+        .map(|value| value.into_owned())
+        .map_err(|e| RouteError::BadRequest(e.to_string()))
+}
+
+/// пары key=value строки запроса; '+' означает пробел
+pub(crate) fn parse_query(query: &str) -> Result<Vec<(String, String)>, RouteError> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Ok((
+                decode(&key.replace('+', " "))?,
+                decode(&value.replace('+', " "))?,
+            ))
+        })
+        .collect()
+}
+
+pub(crate) fn query_value<'a>(query: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    query
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, value)| value.as_str())
+}
+
+/// тело запроса - JSON-объект
+fn body_object(body: &str) -> Result<Map<String, Value>, RouteError> {
+    match serde_json::from_str(body) {
+        Ok(Value::Object(object)) => Ok(object),
+        Ok(_) => Err(RouteError::BadRequest("json object expected".into())),
+        Err(e) => Err(RouteError::BadRequest(e.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn call(method: &str, url: &str, body: &str) -> JsonRpcRequest {
+        match route(method, url, body) {
+            Ok(Route::Call(request)) => request,
+            other => panic!("unexpected route: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_route() {
+        let request = call("GET", "/rooms/floor-1%2Fkids/devices?home=cottage", "");
+        assert_eq!(request.method, "getDevices");
+        assert_eq!(request.params, json!({"room": "floor-1/kids"}));
+        assert_eq!(request.home.as_deref(), Some("cottage"));
+
+        let request = call("POST", "/rooms", r#"{"name": "library"}"#);
+        assert_eq!(request.method, "addRoom");
+        assert_eq!(request.params, json!({"name": "library"}));
+        assert_eq!(request.home, None);
+
+        let request = call(
+            "POST",
+            "/rooms/kitchen/devices/Smart%20Socket%201/switch",
+            r#"{"state": "on"}"#,
+        );
+        assert_eq!(request.method, "deviceExecute");
+        assert_eq!(
+            request.params,
+            json!({"room": "kitchen", "device": "Smart Socket 1", "command": "switch", "data": ["on"]})
+        );
+
+        let request = call("GET", "/report?location=floor+1", "");
+        assert_eq!(request.params, json!({"location": "floor 1"}));
+
+        assert_eq!(
+            route("POST", "/rpc", "[]"),
+            Ok(Route::Rpc("[]".to_string()))
+        );
+        assert!(matches!(
+            route("PUT", "/rooms", ""),
+            Err(RouteError::NotFound(_))
+        ));
+        assert!(matches!(
+            route("POST", "/rooms/kitchen/devices/socket-1/switch", "{}"),
+            Err(RouteError::BadRequest(_))
+        ));
+        assert!(matches!(
+            route("POST", "/rooms", "[1]"),
+            Err(RouteError::BadRequest(_))
+        ));
+    }
+}
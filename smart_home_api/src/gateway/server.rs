@@ -0,0 +1,419 @@
+use super::limit::{spawn_bounded, DeadlineReader};
+use super::route::{route, Route, RouteError};
+use crate::access::policy::RequestContext;
+use crate::my_smart_home::smart_home_tcp::{execute_batch, ServerConfig, SmartHomePublicApi};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use jsonschema::Validator;
+use log::{debug, warn};
+use serde_json::{json, Value};
+use std::io::{self, Read};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::atomic::AtomicUsize;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+use stp::auth::{AuthConfig, Credentials, Principal};
+use tiny_http::{Header, Request, Response, Server};
+
+/// наибольший размер тела запроса
+const MAX_BODY: u64 = 1024 * 1024;
+/// срок на чтение тела запроса
+const BODY_TIMEOUT: Duration = Duration::from_secs(5);
+/// не больше одновременно читаемых запросов
+const MAX_PENDING: usize = 16;
+
+type HttpResponse = Response<io::Cursor<Vec<u8>>>;
+
+/// Запрос, прочитанный и разобранный в рабочем потоке: осталось выполнить его
+/// в цикле сервера и вернуть ответ потоку, который его отправит.
+struct HttpCall {
+    ctx: RequestContext,
+    route: Route,
+    reply: Sender<HttpResponse>,
+}
+
+/// HTTP-шлюз к дому: ресурсы REST и JSON-RPC через `POST /rpc`.
+/// Не блокирует: аутентификация, чтение тела и ответ идут в отдельных потоках,
+/// а сервер в своем цикле только выполняет разобранные запросы, как и запросы stp.
+pub struct HttpGateway {
+    server: Server,
+    calls: Receiver<HttpCall>,
+    sender: Sender<HttpCall>,
+    pending: Arc<AtomicUsize>,
+}
+
+impl HttpGateway {
+    pub fn bind<Addr: ToSocketAddrs>(addr: Addr) -> io::Result<Self> {
+        let server = Server::http(addr).map_err(io::Error::other)?;
+        let (sender, calls) = channel();
+        Ok(Self {
+            server,
+            calls,
+            sender,
+            pending: Arc::default(),
+        })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    /// принять новые HTTP-запросы и выполнить уже прочитанные
+    pub fn poll<A: SmartHomePublicApi + ?Sized>(
+        &self,
+        api: &mut A,
+        config: &ServerConfig,
+        validator: &Validator,
+    ) {
+        self.accept(config);
+        while let Ok(call) = self.calls.try_recv() {
+            let response = execute(api, validator, &call.ctx, call.route);
+            // соединение могло закрыться, пока ждало ответа
+            let _ = call.reply.send(response);
+        }
+    }
+
+    fn accept(&self, config: &ServerConfig) {
+        loop {
+            match self.server.try_recv() {
+                Ok(Some(request)) => {
+                    let sender = self.sender.clone();
+                    let config = config.clone();
+                    let job = move || {
+                        if let Err(e) = receive(request, &config, sender) {
+                            debug!(error:% = e; "http response failed");
+                        }
+                    };
+                    // запрос без ответа tiny_http завершит ошибкой 500
+                    if !spawn_bounded(&self.pending, MAX_PENDING, job) {
+                        warn!("http request dropped: too many pending requests");
+                    }
+                }
+                Ok(None) => return,
+                Err(e) => {
+                    debug!(error:% = e; "http request failed");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// аутентифицировать клиента, прочитать и разобрать запрос, дождаться ответа
+/// от цикла сервера и отправить его
+fn receive(mut request: Request, config: &ServerConfig, calls: Sender<HttpCall>) -> io::Result<()> {
+    let peer = request
+        .remote_addr()
+        .map(ToString::to_string)
+        .unwrap_or_else(|| "unknown".into());
+
+    let Some(principal) = authenticate(&request, &config.auth) else {
+        warn!(peer = peer.as_str(); "http request rejected: authentication failed");
+        let response = json_response(401, &json!({"error": "authentication required"}))
+            .with_header(header("WWW-Authenticate", "Bearer"));
+        return request.respond(response);
+    };
+    let ctx = config.access.context(&principal, &peer);
+
+    let mut body = String::new();
+    let reader = request.as_reader().take(MAX_BODY);
+    if let Err(e) = DeadlineReader::new(reader, BODY_TIMEOUT).read_to_string(&mut body) {
+        let status = match e.kind() {
+            io::ErrorKind::TimedOut => 408,
+            _ => 400,
+        };
+        return request.respond(json_response(status, &json!({"error": e.to_string()})));
+    }
+
+    let route = match route(request.method().as_str(), request.url(), &body) {
+        Ok(route) => route,
+        Err(e) => {
+            let status = match e {
+                RouteError::NotFound(_) => 404,
+                RouteError::BadRequest(_) => 400,
+            };
+            return request.respond(json_response(status, &json!({"error": e.to_string()})));
+        }
+    };
+    let (reply, response) = channel();
+    calls
+        .send(HttpCall { ctx, route, reply })
+        .map_err(io::Error::other)?;
+    let response = response.recv().map_err(io::Error::other)?;
+    request.respond(response)
+}
+
+/// выполнить разобранный запрос в цикле сервера
+fn execute<A: SmartHomePublicApi + ?Sized>(
+    api: &mut A,
+    validator: &Validator,
+    ctx: &RequestContext,
+    route: Route,
+) -> HttpResponse {
+    match route {
+        Route::Rpc(batch) => {
+            let replies = execute_batch(api, validator, &batch, ctx);
+            Response::from_string(replies).with_header(header("Content-Type", "application/json"))
+        }
+        Route::Call(call) => {
+            let replies = execute_batch(api, validator, &json!([call]).to_string(), ctx);
+            let replies: Value = serde_json::from_str(&replies).unwrap_or_default();
+            // ошибка проверки по схеме приходит самой ошибкой, а не пакетом ответов
+            let reply = match replies {
+                Value::Array(mut replies) if !replies.is_empty() => replies.swap_remove(0),
+                error => json!({ "error": error }),
+            };
+            match reply["error"].is_null() {
+                true => json_response(200, &reply["result"]["data"]),
+                false => json_response(error_status(&reply["error"]), &reply["error"]),
+            }
+        }
+    }
+}
+
+/// клиент по заголовку Authorization: `Bearer <token>` или `Basic <user:password>`;
+/// None - учетные данные нужны, но не подошли
+fn authenticate(request: &Request, auth: &AuthConfig) -> Option<Principal> {
+    if auth.is_open() {
+        return Some(Principal::Anonymous);
+    }
+    let value = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Authorization"))?
+        .value
+        .as_str();
+    auth.authenticate(&header_credentials(value)?)
+}
+
+/// учетные данные из значения заголовка Authorization
+pub(crate) fn header_credentials(value: &str) -> Option<Credentials> {
+    match value.split_once(' ')? {
+        ("Bearer", token) => Some(Credentials::Token(token.to_string())),
+        ("Basic", encoded) => {
+            let decoded = String::from_utf8(BASE64_STANDARD.decode(encoded).ok()?).ok()?;
+            let (user, password) = decoded.split_once(':')?;
+            Some(Credentials::Password {
+                user: user.to_string(),
+                password: password.to_string(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// HTTP-статус для ошибки JSON-RPC
+fn error_status(error: &Value) -> u16 {
+    match error["code"].as_i64() {
+        Some(-32001) => 403,
+        Some(-32601) => 404,
+        Some(-32603) => 500,
+        _ => 400,
+    }
+}
+
+fn json_response(status: u16, body: &Value) -> HttpResponse {
+    Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("valid header")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::json_rpc::utils::get_validator;
+    use crate::my_smart_home::home::Home;
+    use crate::my_smart_home::smart_home::SmartHome;
+    use crate::smart_device::socket::Socket;
+    use std::io::Write;
+    use std::net::TcpStream;
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::Duration;
+    use stp::auth::{SecretHash, TokenEntry};
+
+    /// отправить запрос и вернуть статус и тело ответа, пока дом обслуживается шлюзом
+    fn send(
+        gateway: &HttpGateway,
+        home: &mut Home,
+        config: &ServerConfig,
+        request: String,
+    ) -> (u16, Value) {
+        let validator = get_validator(PathBuf::from("public_api.json")).unwrap();
+        let addr = gateway.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+        while !client.is_finished() {
+            gateway.poll(home, config, &validator);
+            thread::sleep(Duration::from_millis(10));
+        }
+        let response = client.join().unwrap();
+        let status = response[9..12].parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    fn http(method: &str, path: &str, headers: &str, body: &str) -> String {
+        format!(
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{headers}Content-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    #[test]
+    fn test_http_gateway() {
+        let gateway = HttpGateway::bind("127.0.0.1:0").unwrap();
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.add_room("kitchen".into(), vec![Box::new(Socket::new("1"))])
+            .unwrap();
+        let config: ServerConfig = serde_json::from_value(json!({
+            "open": true,
+            "anonymous": "admin"
+        }))
+        .unwrap();
+
+        let (status, body) = send(
+            &gateway,
+            &mut home,
+            &config,
+            http("POST", "/rooms", "", r#"{"name": "library"}"#),
+        );
+        assert_eq!((status, body), (200, json!("addRoom: success")));
+        assert!(home.get_rooms().iter().any(|room| room == "library"));
+
+        let request = http(
+            "POST",
+            "/rooms/kitchen/devices/socket-1/switch",
+            "",
+            r#"{"state": "on"}"#,
+        );
+        let (status, _) = send(&gateway, &mut home, &config, request);
+        assert_eq!(status, 200);
+
+        let (status, body) = send(
+            &gateway,
+            &mut home,
+            &config,
+            http("GET", "/rooms/attic/devices", "", ""),
+        );
+        assert_eq!(status, 400);
+        assert!(body["data"].as_str().unwrap().contains("not exist"));
+
+        let batch = json!([{"id": "7", "jsonrpc": "2.0", "method": "getDevices",
+                            "params": {"room": "kitchen"}}]);
+        let (status, body) = send(
+            &gateway,
+            &mut home,
+            &config,
+            http("POST", "/rpc", "", &batch.to_string()),
+        );
+        assert_eq!(status, 200);
+        assert_eq!(body[0]["id"], "7");
+        assert!(body[0]["result"]["data"]
+            .as_str()
+            .unwrap()
+            .contains("socket-1"));
+
+        let (status, body) = send(
+            &gateway,
+            &mut home,
+            &config,
+            http("POST", "/rpc", "", r#"[{"method": "getDevices"}]"#),
+        );
+        assert_eq!(status, 200);
+        assert_eq!(body["code"], -32600);
+
+        let (status, body) = send(
+            &gateway,
+            &mut home,
+            &config,
+            http("POST", "/rooms", "", r#"{"title": "attic"}"#),
+        );
+        assert_eq!(status, 400);
+        assert_eq!(body["code"], -32600);
+
+        let (status, _) = send(
+            &gateway,
+            &mut home,
+            &config,
+            http("GET", "/nowhere", "", ""),
+        );
+        assert_eq!(status, 404);
+    }
+
+    #[test]
+    fn test_stalled_body_does_not_block() {
+        let gateway = HttpGateway::bind("127.0.0.1:0").unwrap();
+        let mut home = Home::new("MyHome".into()).unwrap();
+        let config: ServerConfig =
+            serde_json::from_value(json!({"open": true, "anonymous": "admin"})).unwrap();
+
+        // тело больше того, что tiny_http читает заранее, и не приходит
+        let mut stalled = TcpStream::connect(gateway.local_addr().unwrap()).unwrap();
+        let head = "POST /rooms HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5000\r\n\r\n";
+        stalled.write_all(head.as_bytes()).unwrap();
+        let validator = get_validator(PathBuf::from("public_api.json")).unwrap();
+        for _ in 0..20 {
+            gateway.poll(&mut home, &config, &validator);
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let started = std::time::Instant::now();
+        let (status, _) = send(
+            &gateway,
+            &mut home,
+            &config,
+            http("POST", "/rooms", "", r#"{"name": "library"}"#),
+        );
+        assert_eq!(status, 200);
+        assert!(started.elapsed() < BODY_TIMEOUT);
+    }
+
+    #[test]
+    fn test_http_gateway_auth() {
+        let gateway = HttpGateway::bind("127.0.0.1:0").unwrap();
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.add_room("kitchen".into(), vec![Box::new(Socket::new("1"))])
+            .unwrap();
+        let mut config: ServerConfig = serde_json::from_value(json!({
+            "grants": [{"name": "kid", "role": "viewer"}]
+        }))
+        .unwrap();
+        config.auth.tokens.push(TokenEntry {
+            name: "kid".into(),
+            secret: SecretHash::new("s3cr3t"),
+        });
+
+        let (status, _) = send(&gateway, &mut home, &config, http("GET", "/rooms", "", ""));
+        assert_eq!(status, 401);
+
+        let auth = "Authorization: Bearer s3cr3t\r\n";
+        let (status, body) = send(
+            &gateway,
+            &mut home,
+            &config,
+            http("GET", "/rooms", auth, ""),
+        );
+        assert_eq!(status, 200);
+        assert_eq!(body[0]["path"], "kitchen");
+
+        let request = http(
+            "POST",
+            "/rooms/kitchen/devices/socket-1/switch",
+            auth,
+            r#"{"state": "on"}"#,
+        );
+        let (status, body) = send(&gateway, &mut home, &config, request);
+        assert_eq!(status, 403);
+        assert_eq!(body["code"], -32001);
+    }
+}
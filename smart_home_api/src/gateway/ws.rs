@@ -0,0 +1,419 @@
+use super::limit::{spawn_bounded, LimitedStream};
+use super::route::{parse_query, query_value};
+use super::server::header_credentials;
+use crate::access::policy::RequestContext;
+use crate::events::event::HomeEvent;
+use crate::metrics;
+use crate::my_smart_home::smart_home_tcp::{execute_batch, ServerConfig, SmartHomePublicApi};
+use jsonschema::Validator;
+use log::{debug, info, warn};
+use serde_json::{json, Value};
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::AtomicUsize;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+use stp::auth::{Credentials, Principal};
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::StatusCode;
+use tungstenite::{Error, Message, WebSocket};
+
+/// срок на upgrade подключившегося клиента
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+/// наибольший размер запроса на upgrade со всеми заголовками
+const MAX_HANDSHAKE: usize = 8 * 1024;
+/// не больше одновременных handshake
+const MAX_PENDING: usize = 16;
+
+/// метод уведомлений JSON-RPC о событиях дома
+const EVENT_METHOD: &str = "event";
+
+/// Клиент WebSocket и его права.
+struct WsClient {
+    socket: WebSocket<LimitedStream>,
+    ctx: RequestContext,
+}
+
+/// WebSocket-шлюз к дому: те же пакеты JSON-RPC, что и через stp,
+/// и уведомления о событиях домов без запроса клиента.
+/// Не блокирует: handshake идет в отдельных потоках, а готовых клиентов
+/// сервер опрашивает в своем цикле, как и соединения stp.
+pub struct WsGateway {
+    listener: TcpListener,
+    clients: Vec<WsClient>,
+    /// клиенты, прошедшие handshake
+    accepted: Receiver<WsClient>,
+    sender: Sender<WsClient>,
+    pending: Arc<AtomicUsize>,
+    /// события домов: имя дома и событие
+    events: Receiver<(String, HomeEvent)>,
+}
+
+impl WsGateway {
+    pub fn bind<Addr: ToSocketAddrs>(
+        addr: Addr,
+        events: Receiver<(String, HomeEvent)>,
+    ) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let (sender, accepted) = channel();
+        Ok(Self {
+            listener,
+            clients: vec![],
+            accepted,
+            sender,
+            pending: Arc::default(),
+            events,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// принять новых клиентов, выполнить их запросы и разослать события
+    pub fn poll<A: SmartHomePublicApi + ?Sized>(
+        &mut self,
+        api: &mut A,
+        config: &ServerConfig,
+        validator: &Validator,
+    ) {
+        self.accept(config);
+        while let Ok(client) = self.accepted.try_recv() {
+            info!(peer = client.ctx.peer.as_str(), user = client.ctx.principal.name(); "websocket accepted");
+            metrics::registry::global().connection_opened();
+            self.clients.push(client);
+        }
+
+        self.clients.retain_mut(|client| {
+            let open = serve(client, api, validator);
+            if !open {
+                info!(peer = client.ctx.peer.as_str(); "websocket closed");
+                metrics::registry::global().connection_closed();
+            }
+            open
+        });
+
+        while let Ok((home, event)) = self.events.try_recv() {
+            let notice = notification(&home, &event);
+            for client in self.clients.iter_mut() {
+                if allows_event(&client.ctx, &home, &event) {
+                    // отключившийся клиент будет удален при следующем опросе
+                    let _ = client.socket.send(Message::text(notice.clone()));
+                }
+            }
+        }
+    }
+
+    fn accept(&mut self, config: &ServerConfig) {
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    debug!(error:% = e; "websocket connection failed");
+                    return;
+                }
+            };
+            let peer = match stream.peer_addr() {
+                Ok(addr) => addr.to_string(),
+                Err(_) => "unknown".into(),
+            };
+            let sender = self.sender.clone();
+            let config = config.clone();
+            let job = move || match handshake(stream, &config, &peer) {
+                // шлюз закрыт - клиента некуда передать
+                Ok(client) => drop(sender.send(client)),
+                Err(reason) => {
+                    warn!(peer = peer.as_str(), reason = reason; "websocket rejected");
+                    metrics::registry::global().handshake_failed(reason);
+                }
+            };
+            if !spawn_bounded(&self.pending, MAX_PENDING, job) {
+                warn!("websocket rejected: too many pending handshakes");
+                metrics::registry::global().handshake_failed("busy");
+            }
+        }
+    }
+}
+
+/// upgrade до WebSocket с аутентификацией по заголовку Authorization
+/// или, для браузеров, по параметру `?token=`, в пределах `HANDSHAKE_TIMEOUT`
+/// и `MAX_HANDSHAKE`; ошибка - причина отказа для метрик
+// тип ошибки callback задан tungstenite
+#[allow(clippy::result_large_err)]
+fn handshake(
+    stream: TcpStream,
+    config: &ServerConfig,
+    peer: &str,
+) -> Result<WsClient, &'static str> {
+    stream.set_nonblocking(false).map_err(|_| "io")?;
+    let stream = LimitedStream::new(stream, HANDSHAKE_TIMEOUT, MAX_HANDSHAKE);
+
+    let mut principal = None;
+    let callback = |request: &Request, response: Response| {
+        principal = authenticate(request, config);
+        match principal {
+            Some(_) => Ok(response),
+            None => {
+                let mut error = ErrorResponse::new(Some("authentication required".into()));
+                *error.status_mut() = StatusCode::UNAUTHORIZED;
+                Err(error)
+            }
+        }
+    };
+    // ошибка handshake хранит callback, а с ним и заимствование principal
+    let accepted = tungstenite::accept_hdr(stream, callback).map_err(|_| "handshake");
+    let Some(principal) = principal else {
+        return Err("auth");
+    };
+    let mut socket = accepted?;
+    socket
+        .get_mut()
+        .unlimit()
+        .and_then(|_| socket.get_ref().get_ref().set_nonblocking(true))
+        .map_err(|_| "io")?;
+
+    Ok(WsClient {
+        socket,
+        ctx: config.access.context(&principal, peer),
+    })
+}
+
+fn authenticate(request: &Request, config: &ServerConfig) -> Option<Principal> {
+    if config.auth.is_open() {
+        return Some(Principal::Anonymous);
+    }
+    let from_header = request
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(header_credentials);
+    let from_query = request.uri().query().and_then(|query| {
+        let query = parse_query(query).ok()?;
+        query_value(&query, "token").map(|token| Credentials::Token(token.to_string()))
+    });
+    config.auth.authenticate(&from_header.or(from_query)?)
+}
+
+/// выполнить пришедшие от клиента пакеты; false - соединение закрыто
+fn serve<A: SmartHomePublicApi + ?Sized>(
+    client: &mut WsClient,
+    api: &mut A,
+    validator: &Validator,
+) -> bool {
+    loop {
+        match client.socket.read() {
+            Ok(Message::Text(batch)) => {
+                let replies = execute_batch(api, validator, batch.as_str(), &client.ctx);
+                if client.socket.send(Message::text(replies)).is_err() {
+                    return false;
+                }
+            }
+            // ping, pong и close обрабатывает tungstenite
+            Ok(_) => (),
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => break,
+            Err(Error::ConnectionClosed | Error::AlreadyClosed) => return false,
+            Err(e) => {
+                debug!(peer = client.ctx.peer.as_str(), error:% = e; "websocket failed");
+                return false;
+            }
+        }
+    }
+    match client.socket.flush() {
+        Ok(()) => true,
+        Err(Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => true,
+        Err(_) => false,
+    }
+}
+
+/// уведомление JSON-RPC о событии: без id, дом и поля события в params
+fn notification(home: &str, event: &HomeEvent) -> String {
+    let mut params = serde_json::to_value(event).unwrap_or_default();
+    if let Value::Object(fields) = &mut params {
+        fields.insert("home".into(), home.into());
+    }
+    json!({"jsonrpc": "2.0", "method": EVENT_METHOD, "params": params}).to_string()
+}
+
+/// клиенту, ограниченному домами и комнатами, приходят только события
+/// этих домов и устройств этих комнат
+fn allows_event(ctx: &RequestContext, home: &str, event: &HomeEvent) -> bool {
+    if ctx.role.is_none() || !ctx.allows_home(home) {
+        return false;
+    }
+    match event.device() {
+        Some((room, _)) => ctx.rooms.is_empty() || ctx.allows_room(room),
+        None => ctx.rooms.is_empty(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::json_rpc::utils::get_validator;
+    use crate::my_smart_home::home::Home;
+    use crate::my_smart_home::smart_home::SmartHome;
+    use crate::smart_device::socket::Socket;
+    use std::path::PathBuf;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use stp::auth::{SecretHash, TokenEntry};
+
+    fn home() -> Home {
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.add_room("kitchen".into(), vec![Box::new(Socket::new("1"))])
+            .unwrap();
+        home.add_room("bedroom".into(), vec![Box::new(Socket::new("2"))])
+            .unwrap();
+        home
+    }
+
+    /// обслуживать дом шлюзом, пока клиент не завершится
+    fn serve_until<T>(
+        gateway: &mut WsGateway,
+        home: &mut Home,
+        config: &ServerConfig,
+        client: thread::JoinHandle<T>,
+    ) -> T {
+        let validator = get_validator(PathBuf::from("public_api.json")).unwrap();
+        while !client.is_finished() {
+            gateway.poll(home, config, &validator);
+            thread::sleep(Duration::from_millis(10));
+        }
+        client.join().unwrap()
+    }
+
+    fn switch(room: &str, device: &str) -> String {
+        json!([{"id": "1", "jsonrpc": "2.0", "method": "deviceExecute",
+                "params": {"room": room, "device": device, "command": "switch", "data": ["on"]}}])
+        .to_string()
+    }
+
+    #[test]
+    fn test_ws_requests_and_events() {
+        let mut home = home();
+        let (tx, rx) = channel();
+        home.forward_events(tx);
+        let mut gateway = WsGateway::bind("127.0.0.1:0", rx).unwrap();
+        let addr = gateway.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let (mut socket, _) = tungstenite::connect(format!("ws://{addr}/")).unwrap();
+            socket
+                .send(Message::text(switch("kitchen", "socket-1")))
+                .unwrap();
+            let mut messages = vec![];
+            while messages.len() < 2 {
+                if let Message::Text(text) = socket.read().unwrap() {
+                    messages.push(serde_json::from_str::<Value>(text.as_str()).unwrap());
+                }
+            }
+            socket.close(None).unwrap();
+            messages
+        });
+        let config: ServerConfig =
+            serde_json::from_value(json!({"open": true, "anonymous": "operator"})).unwrap();
+        let messages = serve_until(&mut gateway, &mut home, &config, client);
+
+        let reply = messages.iter().find(|m| m.is_array()).unwrap();
+        assert_eq!(reply[0]["id"], "1");
+        assert!(reply[0]["error"].is_null());
+        let event = messages.iter().find(|m| m["method"] == "event").unwrap();
+        assert!(event.get("id").is_none());
+        assert_eq!(event["params"]["event"], "state_changed");
+        assert_eq!(event["params"]["home"], "MyHome");
+        assert_eq!(event["params"]["device"], "socket-1");
+    }
+
+    #[test]
+    fn test_ws_slow_handshake() {
+        let mut home = home();
+        let (_tx, rx) = channel();
+        let mut gateway = WsGateway::bind("127.0.0.1:0", rx).unwrap();
+        let addr = gateway.local_addr().unwrap();
+        let config: ServerConfig = serde_json::from_value(json!({"open": true})).unwrap();
+
+        // молчащий клиент не задерживает handshake остальных
+        let _silent = TcpStream::connect(addr).unwrap();
+        let started = std::time::Instant::now();
+        let client = thread::spawn(move || tungstenite::connect(format!("ws://{addr}/")).is_ok());
+        assert!(serve_until(&mut gateway, &mut home, &config, client));
+        assert!(started.elapsed() < HANDSHAKE_TIMEOUT);
+
+        // запрос на upgrade больше MAX_HANDSHAKE отклоняется
+        let client = thread::spawn(move || {
+            let mut request = tungstenite::client::IntoClientRequest::into_client_request(format!(
+                "ws://{addr}/"
+            ))
+            .unwrap();
+            let padding = "a".repeat(MAX_HANDSHAKE).parse().unwrap();
+            request.headers_mut().insert("X-Padding", padding);
+            tungstenite::connect(request).is_err()
+        });
+        assert!(serve_until(&mut gateway, &mut home, &config, client));
+    }
+
+    #[test]
+    fn test_ws_auth_and_rooms() {
+        let mut home = home();
+        let (tx, rx) = channel();
+        home.forward_events(tx);
+        let mut gateway = WsGateway::bind("127.0.0.1:0", rx).unwrap();
+        let addr = gateway.local_addr().unwrap();
+        let mut config: ServerConfig = serde_json::from_value(json!({
+            "grants": [{"name": "kid", "role": "operator", "rooms": ["bedroom"]}]
+        }))
+        .unwrap();
+        config.auth.tokens.push(TokenEntry {
+            name: "kid".into(),
+            secret: SecretHash::new("s3cr3t"),
+        });
+
+        let client = thread::spawn(move || tungstenite::connect(format!("ws://{addr}/")).is_err());
+        assert!(serve_until(&mut gateway, &mut home, &config, client));
+
+        let client = thread::spawn(move || {
+            let (mut socket, _) =
+                tungstenite::connect(format!("ws://{addr}/?token=s3cr3t")).unwrap();
+            let mut messages = vec![];
+            for batch in [switch("kitchen", "socket-1"), switch("bedroom", "socket-2")] {
+                socket.send(Message::text(batch)).unwrap();
+            }
+            while messages.len() < 3 {
+                if let Message::Text(text) = socket.read().unwrap() {
+                    messages.push(serde_json::from_str::<Value>(text.as_str()).unwrap());
+                }
+            }
+            socket.close(None).unwrap();
+            messages
+        });
+        let messages = serve_until(&mut gateway, &mut home, &config, client);
+
+        assert_eq!(messages[0][0]["error"]["code"], -32001);
+        assert!(messages[1][0]["error"].is_null());
+        assert_eq!(messages[2]["params"]["room"], "bedroom");
+
+        // события домов и комнат вне разрешенных клиенту не рассылаются
+        let kid = config
+            .access
+            .context(&Principal::Token("kid".into()), "10.0.0.7:50000");
+        let event = |room: &str| HomeEvent::FaultDetected {
+            room: room.into(),
+            device: "socket-1".into(),
+            fault: None,
+        };
+        assert!(allows_event(&kid, "MyHome", &event("bedroom")));
+        assert!(!allows_event(&kid, "MyHome", &event("kitchen")));
+        let full = RequestContext::full();
+        assert!(allows_event(&full, "MyHome", &event("kitchen")));
+
+        let tenant = RequestContext {
+            homes: vec!["flat-2".into()],
+            ..RequestContext::full()
+        };
+        assert!(allows_event(&tenant, "flat-2", &event("kitchen")));
+        assert!(!allows_event(&tenant, "MyHome", &event("kitchen")));
+    }
+}
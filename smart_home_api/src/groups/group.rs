@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+/// Устройство - член группы
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GroupMember {
+    pub room: String,
+    /// идентификатор устройства; при добавлении можно указать и имя
+    pub device: String,
+}
+
+/// Именованная группа устройств из разных комнат
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Group {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub members: Vec<GroupMember>,
+}
+
+/// Результат выполнения команды одним членом группы
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MemberOutcome {
+    pub room: String,
+    pub device: String,
+    pub ok: bool,
+    pub result: String,
+}
+
+/// Сводный результат выполнения команды группой
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct GroupOutcome {
+    pub group: String,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub outcomes: Vec<MemberOutcome>,
+}
+
+impl GroupOutcome {
+    pub fn push(&mut self, outcome: MemberOutcome) {
+        match outcome.ok {
+            true => self.succeeded += 1,
+            false => self.failed += 1,
+        }
+        self.outcomes.push(outcome);
+    }
+}
@@ -0,0 +1,127 @@
+use super::group::{Group, GroupMember};
+use crate::my_smart_home::error::{SmartHomeError, SmartHomeResult};
+use crate::my_smart_home::location::relocate_ref;
+use crate::my_smart_home::storage::{load_json, save_json};
+use std::path::Path;
+
+/// Хранилище групп устройств дома
+#[derive(Default)]
+pub struct GroupStore {
+    groups: Vec<Group>,
+}
+
+impl GroupStore {
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    pub fn get(&self, id: &str) -> SmartHomeResult<&Group> {
+        self.groups
+            .iter()
+            .find(|g| g.id == id)
+            .ok_or_else(|| SmartHomeError::GroupNonExist(id.to_string()))
+    }
+
+    fn get_mut(&mut self, id: &str) -> SmartHomeResult<&mut Group> {
+        self.groups
+            .iter_mut()
+            .find(|g| g.id == id)
+            .ok_or_else(|| SmartHomeError::GroupNonExist(id.to_string()))
+    }
+
+    pub fn add(&mut self, group: Group) -> SmartHomeResult<()> {
+        if self.groups.iter().any(|g| g.id == group.id) {
+            return Err(SmartHomeError::GroupSameIdExist(group.id));
+        }
+        self.groups.push(group);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: &str) -> SmartHomeResult<Group> {
+        match self.groups.iter().position(|g| g.id == id) {
+            Some(pos) => Ok(self.groups.remove(pos)),
+            None => Err(SmartHomeError::GroupNonExist(id.to_string())),
+        }
+    }
+
+    /// добавить устройство в группу
+    pub fn add_member(&mut self, id: &str, member: GroupMember) -> SmartHomeResult<()> {
+        let group = self.get_mut(id)?;
+        if group.members.contains(&member) {
+            return Err(SmartHomeError::DeviceAlreadyInGroup {
+                name: member.device,
+                group: id.to_string(),
+            });
+        }
+        group.members.push(member);
+        Ok(())
+    }
+
+    /// исключить устройство из группы
+    pub fn remove_member(&mut self, id: &str, member: &GroupMember) -> SmartHomeResult<()> {
+        let group = self.get_mut(id)?;
+        match group.members.iter().position(|m| m == member) {
+            Some(pos) => {
+                group.members.remove(pos);
+                Ok(())
+            }
+            None => Err(SmartHomeError::NoDeviceInGroup {
+                name: member.device.clone(),
+                group: id.to_string(),
+            }),
+        }
+    }
+
+    /// устройство перенесено в другую комнату
+    pub fn move_device(&mut self, room: &str, device: &str, to: &str) {
+        for member in self.members_mut() {
+            if member.room == room && member.device == device {
+                member.room = to.to_string();
+            }
+        }
+    }
+
+    /// комната переименована вместе с вложенными
+    pub fn relocate_room(&mut self, from: &str, to: &str) {
+        for member in self.members_mut() {
+            relocate_ref(&mut member.room, from, to);
+        }
+    }
+
+    /// заменить ссылки на устройства идентификаторами, повторы убрать
+    pub fn resolve_members<F>(&mut self, resolve: F)
+    where
+        F: Fn(&GroupMember) -> Option<String>,
+    {
+        for group in self.groups.iter_mut() {
+            let mut members: Vec<GroupMember> = vec![];
+            for mut member in group.members.drain(..) {
+                if let Some(id) = resolve(&member) {
+                    member.device = id;
+                }
+                if !members.contains(&member) {
+                    members.push(member);
+                }
+            }
+            group.members = members;
+        }
+    }
+
+    fn members_mut(&mut self) -> impl Iterator<Item = &mut GroupMember> {
+        self.groups.iter_mut().flat_map(|g| g.members.iter_mut())
+    }
+
+    /// загрузить группы из json-файла
+    pub fn load_file(&mut self, path: &Path) -> SmartHomeResult<usize> {
+        let groups: Vec<Group> = load_json(path)?;
+        let count = groups.len();
+        for group in groups {
+            self.add(group)?;
+        }
+        Ok(count)
+    }
+
+    pub fn save_file(&self, path: &Path) -> SmartHomeResult<()> {
+        save_json(path, &self.groups)
+    }
+}
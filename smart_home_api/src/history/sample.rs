@@ -0,0 +1,46 @@
+use crate::smart_device::device::{DeviceState, Readings};
+use chrono::{NaiveDateTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Снимок статуса и показаний устройства в момент времени
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    pub ts: NaiveDateTime,
+    pub state: DeviceState,
+    #[serde(default)]
+    pub readings: Readings,
+}
+
+/// сумма и число значений показания в интервале прореживания
+type ReadingSums = BTreeMap<String, (f32, u32)>;
+
+/// Прореживание: одна точка на интервал `step`, начиная с `from`.
+/// Показания усредняются, статус берется последний в интервале.
+pub fn downsample(samples: &[Sample], from: NaiveDateTime, step: TimeDelta) -> Vec<Sample> {
+    let step_secs = step.num_seconds().max(1);
+    let mut buckets: BTreeMap<i64, (DeviceState, ReadingSums)> = BTreeMap::new();
+
+    for sample in samples {
+        let bucket = (sample.ts - from).num_seconds().div_euclid(step_secs);
+        let (state, sums) = buckets.entry(bucket).or_default();
+        *state = sample.state;
+        for (reading, value) in &sample.readings {
+            let (sum, count) = sums.entry(reading.clone()).or_default();
+            *sum += value;
+            *count += 1;
+        }
+    }
+
+    buckets
+        .into_iter()
+        .map(|(bucket, (state, sums))| Sample {
+            ts: from + TimeDelta::seconds(bucket * step_secs),
+            state,
+            readings: sums
+                .into_iter()
+                .map(|(reading, (sum, count))| (reading, sum / count as f32))
+                .collect(),
+        })
+        .collect()
+}
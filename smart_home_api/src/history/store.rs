@@ -0,0 +1,251 @@
+use super::sample::{downsample, Sample};
+use crate::my_smart_home::error::{SmartHomeError, SmartHomeResult};
+use chrono::{NaiveDateTime, TimeDelta};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// сколько хранится история по умолчанию
+const DEFAULT_RETENTION_DAYS: i64 = 7;
+/// как часто записываются показания, если они не меняются
+const DEFAULT_SAMPLE_SECONDS: i64 = 60;
+/// как часто удаляются устаревшие точки
+const PRUNE_INTERVAL_MINUTES: i64 = 60;
+
+/// История статусов и показаний устройств.
+/// Хранится в каталоге по файлу на устройство: `<id>.jsonl`, по снимку в строке.
+/// Пока каталог не задан, история не ведется.
+pub struct HistoryStore {
+    dir: Option<PathBuf>,
+    /// сколько хранятся точки
+    pub retention: TimeDelta,
+    /// интервал записи неизменившихся показаний
+    pub sample_interval: TimeDelta,
+    last_sample: HashMap<String, NaiveDateTime>,
+    last_prune: Option<NaiveDateTime>,
+}
+
+impl Default for HistoryStore {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            retention: TimeDelta::days(DEFAULT_RETENTION_DAYS),
+            sample_interval: TimeDelta::seconds(DEFAULT_SAMPLE_SECONDS),
+            last_sample: HashMap::new(),
+            last_prune: None,
+        }
+    }
+}
+
+impl HistoryStore {
+    /// вести историю в каталоге `dir`
+    pub fn set_dir(&mut self, dir: &Path) {
+        self.dir = Some(dir.to_path_buf());
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// записать снимок устройства; без `force` - только если с прошлой записи прошел интервал
+    pub fn record(&mut self, id: &str, sample: Sample, force: bool) -> SmartHomeResult<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        if !force {
+            if let Some(last) = self.last_sample.get(id) {
+                if sample.ts - *last < self.sample_interval {
+                    return Ok(());
+                }
+            }
+        }
+
+        let path = device_file(dir, id);
+        let line = serde_json::to_string(&sample).map_err(|e| history_error(&path, e))?;
+        fs::create_dir_all(dir).map_err(|e| history_error(&path, e))?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| history_error(&path, e))?;
+        writeln!(file, "{line}").map_err(|e| history_error(&path, e))?;
+
+        self.last_sample.insert(id.to_string(), sample.ts);
+        Ok(())
+    }
+
+    /// точки устройства в интервале [from, to], прореженные до `step`, если он задан
+    pub fn query(
+        &self,
+        id: &str,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        step: Option<TimeDelta>,
+    ) -> SmartHomeResult<Vec<Sample>> {
+        let Some(dir) = &self.dir else {
+            return Ok(vec![]);
+        };
+        let samples: Vec<Sample> = read_samples(&device_file(dir, id))?
+            .into_iter()
+            .filter(|s| s.ts >= from && s.ts <= to)
+            .collect();
+
+        Ok(match step {
+            Some(step) => downsample(&samples, from, step),
+            None => samples,
+        })
+    }
+
+    /// удалить точки старше срока хранения, не чаще раза в час
+    pub fn prune(&mut self, now: NaiveDateTime) -> SmartHomeResult<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        if self
+            .last_prune
+            .is_some_and(|last| now - last < TimeDelta::minutes(PRUNE_INTERVAL_MINUTES))
+        {
+            return Ok(());
+        }
+        self.last_prune = Some(now);
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(history_error(dir, e)),
+        };
+        let oldest = now - self.retention;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "jsonl") {
+                continue;
+            }
+            let samples = read_samples(&path)?;
+            let kept: Vec<&Sample> = samples.iter().filter(|s| s.ts >= oldest).collect();
+            if kept.len() == samples.len() {
+                continue;
+            }
+            if kept.is_empty() {
+                fs::remove_file(&path).map_err(|e| history_error(&path, e))?;
+                continue;
+            }
+
+            let mut data = String::new();
+            for sample in kept {
+                data += &serde_json::to_string(sample).map_err(|e| history_error(&path, e))?;
+                data += "\n";
+            }
+            let tmp = path.with_extension("tmp");
+            fs::write(&tmp, data).map_err(|e| history_error(&path, e))?;
+            fs::rename(&tmp, &path).map_err(|e| history_error(&path, e))?;
+        }
+        Ok(())
+    }
+}
+
+fn device_file(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{id}.jsonl"))
+}
+
+/// прочитать точки из файла; недописанные строки пропускаются
+fn read_samples(path: &Path) -> SmartHomeResult<Vec<Sample>> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(history_error(path, e)),
+    };
+    let mut samples = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| history_error(path, e))?;
+        if let Ok(sample) = serde_json::from_str::<Sample>(&line) {
+            samples.push(sample);
+        }
+    }
+    Ok(samples)
+}
+
+fn history_error(path: &Path, e: impl ToString) -> SmartHomeError {
+    SmartHomeError::Storage(format!("{}: {}", path.display(), e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::HistoryStore;
+    use crate::history::sample::Sample;
+    use crate::smart_device::device::{DeviceState, Readings};
+    use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+    use std::env;
+
+    fn at(day: u32, hour: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, min, 0)
+            .unwrap()
+    }
+
+    fn sample(ts: NaiveDateTime, temperature: f32) -> Sample {
+        Sample {
+            ts,
+            state: DeviceState::On,
+            readings: Readings::from([("temperature".to_string(), temperature)]),
+        }
+    }
+
+    #[test]
+    fn test_record_query_and_prune() {
+        let dir = env::temp_dir().join(format!("smart_home_history_{}", std::process::id()));
+        let mut history = HistoryStore::default();
+        history.set_dir(&dir);
+
+        for (min, value) in [(0, 20.0), (0, 21.0), (10, 22.0), (20, 24.0), (40, 30.0)] {
+            history
+                .record("thermometer-1", sample(at(10, 12, min), value), false)
+                .unwrap();
+        }
+        history
+            .record("thermometer-1", sample(at(10, 12, 50), 31.0), true)
+            .unwrap();
+
+        // вторая точка в ту же минуту отброшена интервалом записи
+        let all = history
+            .query("thermometer-1", at(10, 0, 0), at(11, 0, 0), None)
+            .unwrap();
+        assert_eq!(all.len(), 5);
+
+        let range = history
+            .query("thermometer-1", at(10, 12, 5), at(10, 12, 45), None)
+            .unwrap();
+        assert_eq!(range.len(), 3);
+
+        let hourly = history
+            .query(
+                "thermometer-1",
+                at(10, 12, 0),
+                at(10, 13, 0),
+                Some(TimeDelta::minutes(30)),
+            )
+            .unwrap();
+        assert_eq!(hourly.len(), 2);
+        assert_eq!(hourly[0].ts, at(10, 12, 0));
+        assert_eq!(hourly[0].readings["temperature"], 22.0);
+        assert_eq!(hourly[1].readings["temperature"], 30.5);
+
+        history
+            .record("socket-1", sample(at(16, 12, 0), 0.0), true)
+            .unwrap();
+        history.prune(at(18, 0, 0)).unwrap();
+        assert!(history
+            .query("thermometer-1", at(1, 0, 0), at(31, 0, 0), None)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            history
+                .query("socket-1", at(1, 0, 0), at(31, 0, 0), None)
+                .unwrap()
+                .len(),
+            1
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
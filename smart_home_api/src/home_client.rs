@@ -1,7 +1,12 @@
 use serde_json::Value;
 use std::net::ToSocketAddrs;
+#[cfg(unix)]
+use std::path::Path;
+use stp::auth::Credentials;
 use stp::client::StpClient;
 use stp::error::{ConnectError, RequestError};
+#[cfg(feature = "tls")]
+use stp::tls::TlsClientConfig;
 
 /// Клиент дома.
 pub struct RpcOverStpClient {
@@ -15,6 +20,36 @@ impl RpcOverStpClient {
         Ok(Self { stp })
     }
 
+    /// Подключаемся к серверу, требующему аутентификацию.
+    pub fn with_credentials<Addr: ToSocketAddrs>(
+        addr: Addr,
+        credentials: &Credentials,
+    ) -> Result<Self, ConnectError> {
+        let stp = StpClient::connect_with(addr, Some(credentials))?;
+        Ok(Self { stp })
+    }
+
+    /// Подключаемся к серверу по TLS.
+    #[cfg(feature = "tls")]
+    pub fn with_tls<Addr: ToSocketAddrs>(
+        addr: Addr,
+        tls: &TlsClientConfig,
+        credentials: Option<&Credentials>,
+    ) -> Result<Self, ConnectError> {
+        let stp = StpClient::connect_tls(addr, tls, credentials)?;
+        Ok(Self { stp })
+    }
+
+    /// Подключаемся к локальному серверу через Unix-сокет.
+    #[cfg(unix)]
+    pub fn with_unix<P: AsRef<Path>>(
+        path: P,
+        credentials: Option<&Credentials>,
+    ) -> Result<Self, ConnectError> {
+        let stp = StpClient::connect_unix(path, credentials)?;
+        Ok(Self { stp })
+    }
+
     /// json rpc request-reply.
     pub fn rr(&mut self, json_req: Value) -> Result<String, RequestError> {
         let req = json_req.to_string();
@@ -1,12 +1,11 @@
 use crate::info_provider::provider::{DeviceInfoProvider, IterableProvider};
-use crate::my_smart_home::home::Home;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JsonDeviceInfoProvider {
-    /// маппинг: комната-устройства
+    /// маппинг: комната-устройства (идентификаторы или имена)
     pub schema: HashMap<String, HashSet<String>>,
 }
 
@@ -19,11 +18,11 @@ impl JsonDeviceInfoProvider {
 impl DeviceInfoProvider for JsonDeviceInfoProvider {}
 
 impl IterableProvider for JsonDeviceInfoProvider {
-    fn as_set(&self) -> HashSet<String> {
+    fn as_set(&self) -> HashSet<(String, String)> {
         let mut device_set = HashSet::new();
 
         for (room, devices) in &self.schema {
-            device_set.extend(devices.iter().map(|dev| Home::device_path(room, dev)));
+            device_set.extend(devices.iter().map(|dev| (room.clone(), dev.clone())));
         }
         device_set
     }
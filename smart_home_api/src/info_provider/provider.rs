@@ -14,5 +14,6 @@ pub trait DeviceInfoProvider {
 }
 
 pub trait IterableProvider {
-    fn as_set(&self) -> HashSet<String>;
+    /// множество пар (комната, идентификатор или имя устройства)
+    fn as_set(&self) -> HashSet<(String, String)>;
 }
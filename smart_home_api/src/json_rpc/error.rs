@@ -47,6 +47,14 @@ pub fn internal_error(data: String) -> JsonRpcError {
     }
 }
 
+pub fn access_denied(data: String) -> JsonRpcError {
+    JsonRpcError {
+        code: -32001,
+        message: "Access denied".to_string(),
+        data,
+    }
+}
+
 pub fn api_error(data: String) -> JsonRpcError {
     JsonRpcError {
         code: 1,
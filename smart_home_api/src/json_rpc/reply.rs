@@ -3,6 +3,7 @@ extern crate erased_serde;
 use crate::json_rpc::error::JsonRpcError;
 use erased_serde::serialize_trait_object;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 #[derive(Serialize, Deserialize)]
 pub struct JsonRpcErrorReply {
@@ -13,7 +14,7 @@ pub struct JsonRpcErrorReply {
 
 #[derive(Default, Serialize, Deserialize)]
 pub struct Result {
-    pub data: String,
+    pub data: Value,
 }
 
 #[derive(Default, Serialize, Deserialize)]
@@ -23,11 +24,13 @@ pub struct JsonRpcReply {
     pub result: Result,
 }
 
-pub fn reply(id: String, result: String) -> JsonRpcReply {
+pub fn reply(id: String, result: impl Into<Value>) -> JsonRpcReply {
     JsonRpcReply {
         id,
         jsonrpc: String::from("2.0"),
-        result: Result { data: result },
+        result: Result {
+            data: result.into(),
+        },
     }
 }
 
@@ -1,10 +1,13 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-#[derive(Default, Serialize, Deserialize, Clone)]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct JsonRpcRequest {
     pub id: String,
     pub jsonrpc: String,
     pub method: String,
     pub params: Value,
+    /// дом, к которому относится запрос; по умолчанию - основной дом сервера
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub home: Option<String>,
 }
@@ -1,8 +1,23 @@
+pub mod access;
+pub mod alarms;
+pub mod audit;
+pub mod automation;
+pub mod clock;
 pub mod command;
+pub mod energy;
+pub mod events;
+pub mod gateway;
+pub mod groups;
+pub mod history;
 pub mod home_client;
 pub mod info_provider;
 pub mod json_rpc;
+pub mod logging;
+pub mod metrics;
 pub mod my_smart_home;
+pub mod notify;
+pub mod scenes;
+pub mod scheduler;
 pub mod smart_device;
 
 pub const DEFAULT_TCP_SOCKET: &str = "127.0.0.1:54321";
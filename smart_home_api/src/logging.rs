@@ -0,0 +1,179 @@
+use chrono::{Local, SecondsFormat};
+use log::kv::{Error, Key, Value, VisitSource};
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use serde_json as json;
+use std::env;
+use std::io::{self, Write};
+
+/// Формат строк журнала.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// строка для чтения человеком: время, уровень, сообщение и поля key=value
+    #[default]
+    Text,
+    /// один JSON-объект на строку для сборщиков журналов
+    Json,
+}
+
+/// Журнал сервера: структурированные записи в stderr.
+pub struct Logger {
+    level: LevelFilter,
+    format: LogFormat,
+}
+
+impl Logger {
+    pub fn new(level: LevelFilter, format: LogFormat) -> Self {
+        Self { level, format }
+    }
+
+    /// настройки из окружения: SMART_HOME_LOG - уровень (error..trace, по умолчанию info),
+    /// SMART_HOME_LOG_FORMAT=json - вывод в JSON
+    pub fn from_env() -> Self {
+        let level = env::var("SMART_HOME_LOG")
+            .ok()
+            .and_then(|level| level.parse().ok())
+            .unwrap_or(LevelFilter::Info);
+        let format = match env::var("SMART_HOME_LOG_FORMAT").as_deref() {
+            Ok("json") => LogFormat::Json,
+            _ => LogFormat::Text,
+        };
+        Self::new(level, format)
+    }
+
+    /// сделать журналом процесса
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        log::set_max_level(self.level);
+        log::set_boxed_logger(Box::new(self))
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let ts = Local::now().to_rfc3339_opts(SecondsFormat::Millis, false);
+        let line = format_record(self.format, &ts, record);
+        let _ = writeln!(io::stderr().lock(), "{line}");
+    }
+
+    fn flush(&self) {
+        let _ = io::stderr().flush();
+    }
+}
+
+/// строка журнала для записи с полями
+fn format_record(format: LogFormat, ts: &str, record: &Record) -> String {
+    let mut fields = Fields::default();
+    let _ = record.key_values().visit(&mut fields);
+
+    match format {
+        LogFormat::Text => {
+            let mut line = format!(
+                "{ts} {:<5} {}: {}",
+                record.level(),
+                record.target(),
+                record.args()
+            );
+            for (key, value) in fields.0 {
+                let value = match value {
+                    json::Value::String(s) if s.is_empty() || s.contains(char::is_whitespace) => {
+                        format!("{s:?}")
+                    }
+                    json::Value::String(s) => s,
+                    other => other.to_string(),
+                };
+                line += &format!(" {key}={value}");
+            }
+            line
+        }
+        LogFormat::Json => {
+            let mut object = json::Map::new();
+            object.insert("ts".into(), ts.into());
+            object.insert("level".into(), record.level().as_str().into());
+            object.insert("target".into(), record.target().into());
+            object.insert("msg".into(), record.args().to_string().into());
+            for (key, value) in fields.0 {
+                object.entry(key).or_insert(value);
+            }
+            json::Value::Object(object).to_string()
+        }
+    }
+}
+
+/// поля записи в порядке их указания; числа и логические значения сохраняют тип
+#[derive(Default)]
+struct Fields(Vec<(String, json::Value)>);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+        let value = if let Some(b) = value.to_bool() {
+            json::Value::from(b)
+        } else if let Some(n) = value.to_i64() {
+            json::Value::from(n)
+        } else if let Some(n) = value.to_u64() {
+            json::Value::from(n)
+        } else if let Some(n) = value.to_f64() {
+            json::Value::from(n)
+        } else {
+            json::Value::from(value.to_string())
+        };
+        self.0.push((key.as_str().to_string(), value));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use log::Level;
+
+    const TS: &str = "2025-01-15T12:00:00.000+03:00";
+
+    #[test]
+    fn test_format_record() {
+        let kvs: [(&str, &dyn log::kv::ToValue); 4] = [
+            ("id", &"7"),
+            ("peer", &"127.0.0.1:5000"),
+            ("user", &"anna smith"),
+            ("bytes", &42),
+        ];
+        let text = format_record(
+            LogFormat::Text,
+            TS,
+            &Record::builder()
+                .args(format_args!("request"))
+                .level(Level::Info)
+                .target("smart_home")
+                .key_values(&kvs)
+                .build(),
+        );
+        assert_eq!(
+            text,
+            format!(
+                "{TS} INFO  smart_home: request id=7 peer=127.0.0.1:5000 user=\"anna smith\" bytes=42"
+            )
+        );
+
+        let line = format_record(
+            LogFormat::Json,
+            TS,
+            &Record::builder()
+                .args(format_args!("request"))
+                .level(Level::Warn)
+                .target("smart_home")
+                .key_values(&kvs)
+                .build(),
+        );
+        let value: json::Value = json::from_str(&line).unwrap();
+        assert_eq!(value["level"], "WARN");
+        assert_eq!(value["msg"], "request");
+        assert_eq!(value["user"], "anna smith");
+        assert_eq!(value["bytes"], 42);
+        assert_eq!(value["ts"], TS);
+    }
+}
@@ -0,0 +1,72 @@
+use super::registry::{header, labels};
+use crate::smart_device::device::{DeviceState, Readings};
+use std::fmt::Write;
+
+/// Снимок устройства для метрик: состояние и показания на момент запроса метрик.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceGauge {
+    pub home: String,
+    pub room: String,
+    /// идентификатор устройства в доме
+    pub device: String,
+    pub state: DeviceState,
+    pub readings: Readings,
+}
+
+/// показание устройства -> метрика, имя которой содержит единицы измерения
+const READING_METRICS: [(&str, &str, &str); 2] = [
+    (
+        "temperature",
+        "smart_home_device_temperature_celsius",
+        "Device temperature.",
+    ),
+    ("power", "smart_home_device_power_watts", "Device power."),
+];
+
+const STATES: [(DeviceState, &str); 3] = [
+    (DeviceState::On, "on"),
+    (DeviceState::Off, "off"),
+    (DeviceState::Broken, "broken"),
+];
+
+/// метрики устройств в текстовом формате Prometheus
+pub(crate) fn render_devices(out: &mut String, devices: &[DeviceGauge]) {
+    header(
+        out,
+        "smart_home_device_state",
+        "Device state, 1 for the current state.",
+        "gauge",
+    );
+    for gauge in devices {
+        for (state, name) in STATES {
+            let _ = writeln!(
+                out,
+                "smart_home_device_state{} {}",
+                labels(&[
+                    ("home", &gauge.home),
+                    ("room", &gauge.room),
+                    ("device", &gauge.device),
+                    ("state", name),
+                ]),
+                u8::from(gauge.state == state)
+            );
+        }
+    }
+
+    for (reading, metric, help) in READING_METRICS {
+        header(out, metric, help, "gauge");
+        for gauge in devices {
+            if let Some(value) = gauge.readings.get(reading) {
+                let _ = writeln!(
+                    out,
+                    "{metric}{} {value}",
+                    labels(&[
+                        ("home", &gauge.home),
+                        ("room", &gauge.room),
+                        ("device", &gauge.device),
+                    ])
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use crate::gateway::limit::{spawn_bounded, LimitedStream};
+use log::{debug, warn};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::AtomicUsize;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// срок на запрос подключившегося сборщика метрик и ответ ему
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+/// наибольший размер запроса со всеми заголовками
+const MAX_REQUEST: usize = 8 * 1024;
+/// не больше одновременно читаемых запросов
+const MAX_PENDING: usize = 16;
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// HTTP-ответчик метрик: отдает `GET /metrics`, на остальное отвечает 404.
+/// Не блокирует: запросы читаются и ответы пишутся в отдельных потоках,
+/// а сервер в своем цикле только готовит текст метрик для запросивших.
+pub struct MetricsListener {
+    listener: TcpListener,
+    /// запросы метрик, ожидающие текста
+    requests: Receiver<Sender<String>>,
+    sender: Sender<Sender<String>>,
+    pending: Arc<AtomicUsize>,
+}
+
+impl MetricsListener {
+    pub fn bind<Addr: ToSocketAddrs>(addr: Addr) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let (sender, requests) = channel();
+        Ok(Self {
+            listener,
+            requests,
+            sender,
+            pending: Arc::default(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// принять новые соединения и ответить на прочитанные запросы метрик;
+    /// `render` вызывается только для запроса метрик
+    pub fn poll(&self, mut render: impl FnMut() -> String) {
+        self.accept();
+        while let Ok(reply) = self.requests.try_recv() {
+            // соединение могло закрыться, пока ждало ответа
+            let _ = reply.send(render());
+        }
+    }
+
+    fn accept(&self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    let sender = self.sender.clone();
+                    let job = move || {
+                        if let Err(e) = respond(stream, sender) {
+                            debug!(error:% = e; "metrics request failed");
+                        }
+                    };
+                    if !spawn_bounded(&self.pending, MAX_PENDING, job) {
+                        warn!("metrics request dropped: too many pending requests");
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    debug!(error:% = e; "metrics connection failed");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// прочитать запрос и ответить на него; текст метрик готовит цикл сервера
+fn respond(stream: TcpStream, requests: Sender<Sender<String>>) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    let mut reader = BufReader::new(LimitedStream::new(stream, READ_TIMEOUT, MAX_REQUEST));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let path = parts.nth(1).and_then(|target| target.split('?').next());
+    let (status, body) = match (request_line.starts_with("GET "), path) {
+        (true, Some("/metrics")) => {
+            let (reply, body) = channel();
+            requests.send(reply).map_err(io::Error::other)?;
+            let body = body.recv_timeout(READ_TIMEOUT).map_err(io::Error::other)?;
+            ("200 OK", body)
+        }
+        _ => ("404 Not Found", "not found\n".to_string()),
+    };
+    let stream = reader.get_mut();
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {CONTENT_TYPE}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+    use std::thread;
+
+    fn get(addr: SocketAddr, path: &str) -> thread::JoinHandle<String> {
+        let path = path.to_string();
+        thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        })
+    }
+
+    fn serve(listener: &MetricsListener, client: thread::JoinHandle<String>) -> String {
+        while !client.is_finished() {
+            listener.poll(|| "smart_home_connections_total 3\n".to_string());
+            thread::sleep(Duration::from_millis(10));
+        }
+        client.join().unwrap()
+    }
+
+    #[test]
+    fn test_metrics_listener() {
+        let listener = MetricsListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let response = serve(&listener, get(addr, "/metrics"));
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(CONTENT_TYPE));
+        assert!(response.ends_with("\r\n\r\nsmart_home_connections_total 3\n"));
+
+        let response = serve(&listener, get(addr, "/"));
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[test]
+    fn test_slow_and_large_requests() {
+        let listener = MetricsListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // молчащий клиент не задерживает ответы остальным
+        let _silent = TcpStream::connect(addr).unwrap();
+        let started = std::time::Instant::now();
+        let response = serve(&listener, get(addr, "/metrics"));
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(started.elapsed() < READ_TIMEOUT);
+
+        // запрос без конца заголовков обрывается после MAX_REQUEST байт
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(stream, "GET /metrics HTTP/1.1\r\n").unwrap();
+            let header = format!("X-Padding: {}\r\n", "a".repeat(1000));
+            let mut response = String::new();
+            for _ in 0..MAX_REQUEST / 1000 + 2 {
+                if stream.write_all(header.as_bytes()).is_err() {
+                    return response;
+                }
+            }
+            let _ = stream.read_to_string(&mut response);
+            response
+        });
+        assert!(serve(&listener, client).is_empty());
+    }
+}
@@ -0,0 +1,259 @@
+use super::device::{render_devices, DeviceGauge};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// верхние границы корзин гистограммы длительности запросов, секунды
+const LATENCY_BUCKETS: [f64; 9] = [0.0005, 0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1.0];
+
+static METRICS: LazyLock<Mutex<Metrics>> = LazyLock::new(Default::default);
+
+/// метрики процесса; сервер один на процесс, как и его журнал
+pub fn global() -> MutexGuard<'static, Metrics> {
+    METRICS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Гистограмма длительностей: счетчики по корзинам, без накопления.
+#[derive(Clone, Debug, Default)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|le| seconds <= *le) {
+            self.buckets[i] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+/// Метрики сервера: запросы JSON-RPC, ошибки и соединения.
+/// Метрики устройств не хранятся, а снимаются с домов при выводе.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// метод -> длительности запросов
+    requests: BTreeMap<String, Histogram>,
+    /// код ошибки JSON-RPC -> число ответов с ней
+    errors: BTreeMap<i64, u64>,
+    connections: u64,
+    active_connections: u64,
+    /// причина -> число отвергнутых при установке соединений
+    handshake_failures: BTreeMap<String, u64>,
+}
+
+impl Metrics {
+    /// учесть выполненный запрос и код ошибки его ответа
+    pub fn observe_request(&mut self, method: &str, elapsed: Duration, error_code: Option<i64>) {
+        self.requests
+            .entry(method.to_string())
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+        if let Some(code) = error_code {
+            *self.errors.entry(code).or_default() += 1;
+        }
+    }
+
+    pub fn connection_opened(&mut self) {
+        self.connections += 1;
+        self.active_connections += 1;
+    }
+
+    pub fn connection_closed(&mut self) {
+        self.active_connections = self.active_connections.saturating_sub(1);
+    }
+
+    /// соединение не установлено: ошибка handshake, аутентификации или TLS
+    pub fn handshake_failed(&mut self, reason: &str) {
+        *self
+            .handshake_failures
+            .entry(reason.to_string())
+            .or_default() += 1;
+    }
+
+    /// все метрики в текстовом формате Prometheus
+    pub fn render(&self, devices: &[DeviceGauge]) -> String {
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "smart_home_rpc_requests_total",
+            "JSON-RPC requests by method.",
+            "counter",
+        );
+        for (method, histogram) in &self.requests {
+            let _ = writeln!(
+                out,
+                "smart_home_rpc_requests_total{} {}",
+                labels(&[("method", method)]),
+                histogram.count
+            );
+        }
+
+        header(
+            &mut out,
+            "smart_home_rpc_request_duration_seconds",
+            "JSON-RPC request latency by method.",
+            "histogram",
+        );
+        for (method, histogram) in &self.requests {
+            let mut cumulative = 0;
+            for (le, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "smart_home_rpc_request_duration_seconds_bucket{} {cumulative}",
+                    labels(&[("method", method), ("le", &le.to_string())])
+                );
+            }
+            let _ = writeln!(
+                out,
+                "smart_home_rpc_request_duration_seconds_bucket{} {}",
+                labels(&[("method", method), ("le", "+Inf")]),
+                histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "smart_home_rpc_request_duration_seconds_sum{} {}",
+                labels(&[("method", method)]),
+                histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "smart_home_rpc_request_duration_seconds_count{} {}",
+                labels(&[("method", method)]),
+                histogram.count
+            );
+        }
+
+        header(
+            &mut out,
+            "smart_home_rpc_errors_total",
+            "JSON-RPC error replies by code.",
+            "counter",
+        );
+        for (code, count) in &self.errors {
+            let _ = writeln!(
+                out,
+                "smart_home_rpc_errors_total{} {count}",
+                labels(&[("code", &code.to_string())])
+            );
+        }
+
+        header(
+            &mut out,
+            "smart_home_connections_total",
+            "Accepted client connections.",
+            "counter",
+        );
+        let _ = writeln!(out, "smart_home_connections_total {}", self.connections);
+        header(
+            &mut out,
+            "smart_home_active_connections",
+            "Client connections being served.",
+            "gauge",
+        );
+        let _ = writeln!(
+            out,
+            "smart_home_active_connections {}",
+            self.active_connections
+        );
+
+        header(
+            &mut out,
+            "smart_home_handshake_failures_total",
+            "Rejected client connections by reason.",
+            "counter",
+        );
+        for (reason, count) in &self.handshake_failures {
+            let _ = writeln!(
+                out,
+                "smart_home_handshake_failures_total{} {count}",
+                labels(&[("reason", reason)])
+            );
+        }
+
+        render_devices(&mut out, devices);
+        out
+    }
+}
+
+/// строки HELP и TYPE семейства метрик
+pub(crate) fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// метки метрики: {name="value",...} с экранированием значений
+pub(crate) fn labels(pairs: &[(&str, &str)]) -> String {
+    let pairs: Vec<String> = pairs
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', r"\\")
+                .replace('"', "\\\"")
+                .replace('\n', r"\n");
+            format!("{name}=\"{value}\"")
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::smart_device::device::{DeviceState, Readings};
+
+    #[test]
+    fn test_render_metrics() {
+        let mut metrics = Metrics::default();
+        metrics.observe_request("getRooms", Duration::from_micros(300), None);
+        metrics.observe_request("getRooms", Duration::from_millis(20), None);
+        metrics.observe_request("delRoom", Duration::from_millis(2), Some(1));
+        metrics.connection_opened();
+        metrics.connection_opened();
+        metrics.connection_closed();
+        metrics.handshake_failed("auth");
+
+        let devices = [DeviceGauge {
+            home: "MyHome".into(),
+            room: "floor-1/\"kids\"".into(),
+            device: "thermometer-1".into(),
+            state: DeviceState::On,
+            readings: Readings::from([("temperature".to_string(), 21.5)]),
+        }];
+        let text = metrics.render(&devices);
+
+        assert!(text.contains("# TYPE smart_home_rpc_requests_total counter\n"));
+        assert!(text.contains("smart_home_rpc_requests_total{method=\"getRooms\"} 2\n"));
+        assert!(text.contains(
+            "smart_home_rpc_request_duration_seconds_bucket{method=\"getRooms\",le=\"0.0005\"} 1\n"
+        ));
+        assert!(text.contains(
+            "smart_home_rpc_request_duration_seconds_bucket{method=\"getRooms\",le=\"0.025\"} 2\n"
+        ));
+        assert!(text.contains(
+            "smart_home_rpc_request_duration_seconds_bucket{method=\"getRooms\",le=\"+Inf\"} 2\n"
+        ));
+        assert!(text.contains("smart_home_rpc_errors_total{code=\"1\"} 1\n"));
+        assert!(text.contains("smart_home_connections_total 2\n"));
+        assert!(text.contains("smart_home_active_connections 1\n"));
+        assert!(text.contains("smart_home_handshake_failures_total{reason=\"auth\"} 1\n"));
+
+        let labels = "home=\"MyHome\",room=\"floor-1/\\\"kids\\\"\",device=\"thermometer-1\"";
+        assert!(text.contains(&format!(
+            "smart_home_device_state{{{labels},state=\"on\"}} 1\n"
+        )));
+        assert!(text.contains(&format!(
+            "smart_home_device_state{{{labels},state=\"broken\"}} 0\n"
+        )));
+        assert!(text.contains(&format!(
+            "smart_home_device_temperature_celsius{{{labels}}} 21.5\n"
+        )));
+        assert!(!text.contains("smart_home_device_power_watts{"));
+    }
+}
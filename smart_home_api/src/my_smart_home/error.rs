@@ -8,11 +8,107 @@ pub enum SmartHomeError {
     #[error("room with same name exist in home: {0}")]
     RoomSameNameExistInHome(String),
 
+    #[error("invalid location: {0:?}")]
+    InvalidLocation(String),
+
+    #[error("room: {0} has nested rooms")]
+    RoomHasNestedRooms(String),
+
+    #[error("room: {room} can't be moved into itself: {to}")]
+    RoomIntoItself { room: String, to: String },
+
     #[error("device: {name:?} not exist in room: {room:?}")]
     NoDeviceInRoom { name: String, room: String },
 
     #[error("device with same name exist in room: {0}")]
     DeviceSameNameExistInRoom(String),
+
+    #[error("device: {name:?} already in room: {room:?}")]
+    DeviceAlreadyInRoom { name: String, room: String },
+
+    #[error("device with same id exist in home: {0}")]
+    DeviceSameIdExistInHome(String),
+
+    #[error("invalid device id: {0:?}")]
+    InvalidDeviceId(String),
+
+    #[error("device: {0:?} can't be renamed")]
+    DeviceRenameUnsupported(String),
+
+    #[error("rule: {0} not exist")]
+    RuleNonExist(String),
+
+    #[error("rule with same id exist: {0}")]
+    RuleSameIdExist(String),
+
+    #[error("invalid rule {id:?}: {reason}")]
+    InvalidRule { id: String, reason: String },
+
+    #[error("schedule: {0} not exist")]
+    ScheduleNonExist(String),
+
+    #[error("schedule with same id exist: {0}")]
+    ScheduleSameIdExist(String),
+
+    #[error("invalid schedule {id:?}: {reason}")]
+    InvalidSchedule { id: String, reason: String },
+
+    #[error("scene: {0} not exist")]
+    SceneNonExist(String),
+
+    #[error("scene with same id exist: {0}")]
+    SceneSameIdExist(String),
+
+    #[error("group: {0} not exist")]
+    GroupNonExist(String),
+
+    #[error("group with same id exist: {0}")]
+    GroupSameIdExist(String),
+
+    #[error("device: {name:?} already in group: {group:?}")]
+    DeviceAlreadyInGroup { name: String, group: String },
+
+    #[error("device: {name:?} not in group: {group:?}")]
+    NoDeviceInGroup { name: String, group: String },
+
+    #[error("alarm: {0} not exist")]
+    AlarmNonExist(String),
+
+    #[error("alarm with same id exist: {0}")]
+    AlarmSameIdExist(String),
+
+    #[error("invalid alarm {id:?}: {reason}")]
+    InvalidAlarm { id: String, reason: String },
+
+    #[error("alarm record: {0} not exist")]
+    AlarmRecordNonExist(u64),
+
+    #[error("alarm record: {0} is not active")]
+    AlarmNotActive(u64),
+
+    #[error("home: {0} not exist")]
+    HomeNonExist(String),
+
+    #[error("home with same name exist: {0}")]
+    HomeSameNameExist(String),
+
+    #[error("invalid home name: {0:?}")]
+    InvalidHomeName(String),
+
+    #[error("home: {0} is the default home and can't be removed")]
+    DefaultHomeRemoval(String),
+
+    #[error("storage error: {0}")]
+    Storage(String),
+
+    #[error("unknown device type: {0}")]
+    UnknownDeviceType(String),
+
+    #[error("device type already registered: {0}")]
+    DeviceTypeAlreadyRegistered(String),
+
+    #[error("invalid parameters for device type {dev_type:?}: {reason}")]
+    InvalidDeviceParams { dev_type: String, reason: String },
 }
 
 pub type SmartHomeResult<T> = Result<T, SmartHomeError>;
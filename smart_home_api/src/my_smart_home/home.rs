@@ -1,13 +1,115 @@
 extern crate stp;
 
-use crate::smart_device::device::SmartDevice;
+use super::error::{SmartHomeError, SmartHomeResult};
+use super::location::{build_tree, is_within, parent_location, LocationNode, LOCATION_SEPARATOR};
+use super::smart_home::SmartHome;
+use super::storage::{load_json, save_json};
+use super::topology::Topology;
+use crate::alarms::engine::AlarmEngine;
+use crate::audit::log::AuditLog;
+use crate::automation::engine::{FiredRule, RuleEngine};
+use crate::clock::{Clock, SystemClock};
+use crate::command::device_command::{execute_command, CommandError, DeviceCommand};
+use crate::energy::meter::EnergyMeter;
+use crate::energy::report::{DeviceEnergy, EnergyPeriod, EnergyReport, RoomEnergy};
+use crate::events::bus::EventBus;
+use crate::events::event::HomeEvent;
+use crate::groups::group::{Group, GroupMember, GroupOutcome, MemberOutcome};
+use crate::groups::store::GroupStore;
+use crate::history::sample::Sample;
+use crate::history::store::HistoryStore;
+use crate::metrics::device::DeviceGauge;
+use crate::notify::notifier::Notifier;
+use crate::scenes::scene::{Scene, SceneEntry, SceneOutcome};
+use crate::scenes::store::SceneStore;
+use crate::scheduler::engine::{DueSchedule, Scheduler};
+use crate::smart_device::device::{DeviceState, Readings, SmartDevice};
+use crate::smart_device::registry::DeviceRegistry;
 
-use std::collections::HashMap;
+use chrono::{NaiveDateTime, TimeDelta};
+use log::warn;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::error::Error;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+/// сколько событий подряд обрабатывается за раз, защита от зацикливания правил
+const MAX_CHAINED_EVENTS: usize = 1000;
+
+/// файл состава дома в каталоге данных дома
+const TOPOLOGY_FILE: &str = "topology.json";
+/// файл расписаний в каталоге данных дома
+const SCHEDULES_FILE: &str = "schedules.json";
+/// файл сцен в каталоге данных дома
+const SCENES_FILE: &str = "scenes.json";
+/// файл групп в каталоге данных дома
+const GROUPS_FILE: &str = "groups.json";
+/// каталог истории показаний в каталоге данных дома
+const HISTORY_DIR: &str = "history";
+/// каталог журнала аудита в каталоге данных дома
+const AUDIT_DIR: &str = "audit";
+/// файл счетчика электроэнергии в каталоге данных дома
+const ENERGY_FILE: &str = "energy.json";
+/// файл тревог в каталоге данных дома
+const ALARMS_FILE: &str = "alarms.json";
+/// получатели оповещений в каталоге данных дома
+const NOTIFICATIONS_FILE: &str = "notifications.json";
+/// файл правил автоматизации в каталоге данных дома
+const RULES_FILE: &str = "rules.json";
+/// все файлы и каталоги в каталоге данных дома
+pub(crate) const DATA_ENTRIES: [&str; 10] = [
+    TOPOLOGY_FILE,
+    SCHEDULES_FILE,
+    SCENES_FILE,
+    GROUPS_FILE,
+    HISTORY_DIR,
+    AUDIT_DIR,
+    ENERGY_FILE,
+    ALARMS_FILE,
+    NOTIFICATIONS_FILE,
+    RULES_FILE,
+];
+/// как часто сохраняются показания счетчика электроэнергии
+const ENERGY_SAVE_MINUTES: i64 = 5;
 
 pub struct Home {
     pub name: String,
-    pub rooms: HashMap<String, HashMap<String, Box<dyn SmartDevice>>>,
+    /// комнаты и их устройства; менять устройства - только через `with_device`,
+    /// иначе изменения не попадут в шину событий
+    pub(crate) rooms: HashMap<String, HashMap<String, Box<dyn SmartDevice>>>,
+    /// реестр типов устройств, которые можно создать по имени типа
+    pub registry: DeviceRegistry,
+    /// шина событий устройств
+    pub events: EventBus,
+    /// правила автоматизации
+    pub rules: RuleEngine,
+    /// расписания команд устройствам
+    pub scheduler: Scheduler,
+    /// сцены: наборы целевых состояний устройств
+    pub scenes: SceneStore,
+    /// группы устройств из разных комнат
+    pub groups: GroupStore,
+    /// история статусов и показаний устройств
+    pub history: HistoryStore,
+    /// счетчик электроэнергии устройств с показанием "power"
+    pub energy: EnergyMeter,
+    /// тревоги по показаниям и неисправностям устройств
+    pub alarms: AlarmEngine,
+    /// оповещения о событиях дома
+    pub notifier: Notifier,
+    /// журнал изменений, сделанных клиентами
+    pub audit: AuditLog,
+    /// источник времени для правил и расписаний
+    pub clock: Box<dyn Clock>,
+    /// каталог, в котором сохраняется состояние дома
+    data_dir: Option<PathBuf>,
+    /// когда счетчик электроэнергии был сохранен в последний раз
+    energy_saved: Option<NaiveDateTime>,
+    /// последние известные статус и показания устройств, по идентификатору
+    observed: HashMap<String, (DeviceState, Readings)>,
+    /// опубликованные, но еще не обработанные правилами события
+    pending: VecDeque<HomeEvent>,
+    dispatching: bool,
 }
 
 impl Home {
@@ -15,23 +117,746 @@ impl Home {
         let home = Home {
             name,
             rooms: HashMap::new(),
+            registry: DeviceRegistry::default(),
+            events: EventBus::default(),
+            rules: RuleEngine::default(),
+            scheduler: Scheduler::default(),
+            scenes: SceneStore::default(),
+            groups: GroupStore::default(),
+            history: HistoryStore::default(),
+            energy: EnergyMeter::default(),
+            alarms: AlarmEngine::default(),
+            notifier: Notifier::default(),
+            audit: AuditLog::default(),
+            clock: Box::new(SystemClock),
+            data_dir: None,
+            energy_saved: None,
+            observed: HashMap::new(),
+            pending: VecDeque::new(),
+            dispatching: false,
         };
 
         Ok(home)
     }
 
+    /// задать каталог данных дома и загрузить из него сохраненные комнаты и устройства,
+    /// расписания, сцены, группы, счетчик электроэнергии, тревоги и получателей оповещений;
+    /// история показаний и журнал аудита ведутся в подкаталогах `history` и `audit`
+    pub fn set_data_dir(&mut self, dir: &Path) -> SmartHomeResult<()> {
+        let topology = dir.join(TOPOLOGY_FILE);
+        if topology.exists() {
+            let topology: Topology = load_json(&topology)?;
+            topology.restore(self)?;
+            self.observed.clear();
+        }
+        let schedules = dir.join(SCHEDULES_FILE);
+        if schedules.exists() {
+            let now = self.clock.now();
+            let mut scheduler = Scheduler::default();
+            scheduler.load_file(&schedules, now)?;
+            self.scheduler = scheduler;
+        }
+        let scenes = dir.join(SCENES_FILE);
+        if scenes.exists() {
+            let mut store = SceneStore::default();
+            store.load_file(&scenes)?;
+            self.scenes = store;
+        }
+        let groups = dir.join(GROUPS_FILE);
+        if groups.exists() {
+            let mut store = GroupStore::default();
+            store.load_file(&groups)?;
+            // группы прежних версий могли ссылаться на устройства по имени
+            store.resolve_members(|m| self.resolve_device(&m.room, &m.device));
+            self.groups = store;
+        }
+        let energy = dir.join(ENERGY_FILE);
+        if energy.exists() {
+            self.energy.load_file(&energy)?;
+        }
+        let alarms = dir.join(ALARMS_FILE);
+        if alarms.exists() {
+            self.alarms.load_file(&alarms)?;
+        }
+        let rules = dir.join(RULES_FILE);
+        if rules.exists() {
+            let mut engine = RuleEngine::default();
+            engine.load_file(&rules)?;
+            self.rules = engine;
+        }
+        let notifications = dir.join(NOTIFICATIONS_FILE);
+        if notifications.exists() {
+            let mut notifier = Notifier::default();
+            notifier.load_file(&notifications)?;
+            self.notifier = notifier;
+        }
+        self.history.set_dir(&dir.join(HISTORY_DIR));
+        self.audit.set_dir(&dir.join(AUDIT_DIR));
+        self.data_dir = Some(dir.to_path_buf());
+        Ok(())
+    }
+
+    /// сохранить состояние дома в каталог данных, если он задан
+    pub fn persist(&self) -> SmartHomeResult<()> {
+        match &self.data_dir {
+            Some(dir) => {
+                save_json(&dir.join(TOPOLOGY_FILE), &Topology::capture(self))?;
+                self.scheduler.save_file(&dir.join(SCHEDULES_FILE))?;
+                self.scenes.save_file(&dir.join(SCENES_FILE))?;
+                self.groups.save_file(&dir.join(GROUPS_FILE))?;
+                self.energy.save_file(&dir.join(ENERGY_FILE))?;
+                self.alarms.save_file(&dir.join(ALARMS_FILE))?;
+                self.rules.save_file(&dir.join(RULES_FILE))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// сохранить состояние из фоновой обработки: ошибку только записать в журнал
+    fn persist_or_warn(&self) {
+        if let Err(e) = self.persist() {
+            warn!(home = self.name.as_str(), error:% = e; "home state save failed");
+        }
+    }
+
+    /// добавить группу; члены группы должны существовать
+    pub fn add_group(&mut self, mut group: Group) -> SmartHomeResult<()> {
+        let mut members = vec![];
+        for member in group.members {
+            let member = self.resolve_member(member)?;
+            if members.contains(&member) {
+                return Err(SmartHomeError::DeviceAlreadyInGroup {
+                    name: member.device,
+                    group: group.id,
+                });
+            }
+            members.push(member);
+        }
+        group.members = members;
+        self.groups.add(group)
+    }
+
+    /// добавить в группу существующее устройство
+    pub fn add_group_member(&mut self, id: &str, member: GroupMember) -> SmartHomeResult<()> {
+        self.groups.get(id)?;
+        let member = self.resolve_member(member)?;
+        self.groups.add_member(id, member)
+    }
+
+    /// исключить устройство из группы; удаленное из дома устройство - по идентификатору
+    pub fn remove_group_member(&mut self, id: &str, member: GroupMember) -> SmartHomeResult<()> {
+        let member = self.resolve_member(member.clone()).unwrap_or(member);
+        self.groups.remove_member(id, &member)
+    }
+
+    /// член группы с идентификатором устройства вместо имени
+    fn resolve_member(&self, member: GroupMember) -> SmartHomeResult<GroupMember> {
+        match self.resolve_device(&member.room, &member.device) {
+            Some(device) => Ok(GroupMember {
+                room: member.room,
+                device,
+            }),
+            None => Err(match self.rooms.contains_key(&member.room) {
+                true => SmartHomeError::NoDeviceInRoom {
+                    name: member.device,
+                    room: member.room,
+                },
+                false => SmartHomeError::RoomNonExist(member.room),
+            }),
+        }
+    }
+
+    /// выполнить команду всеми устройствами группы
+    pub fn group_execute(
+        &mut self,
+        id: &str,
+        command: &str,
+        data: &[String],
+    ) -> SmartHomeResult<GroupOutcome> {
+        let members = self.groups.get(id)?.members.clone();
+        Ok(self.execute_each(id, members, command, data))
+    }
+
+    /// выполнить команду всеми устройствами локации и вложенных в нее комнат
+    pub fn location_execute(
+        &mut self,
+        location: &str,
+        command: &str,
+        data: &[String],
+    ) -> SmartHomeResult<GroupOutcome> {
+        let mut members = vec![];
+        for room in self.rooms_within(location)? {
+            let mut ids: Vec<&String> = self.rooms[&room].keys().collect();
+            ids.sort();
+            members.extend(ids.into_iter().map(|id| GroupMember {
+                room: room.clone(),
+                device: id.clone(),
+            }));
+        }
+        Ok(self.execute_each(location, members, command, data))
+    }
+
+    fn execute_each(
+        &mut self,
+        group: &str,
+        members: Vec<GroupMember>,
+        command: &str,
+        data: &[String],
+    ) -> GroupOutcome {
+        let mut outcome = GroupOutcome {
+            group: group.to_string(),
+            ..Default::default()
+        };
+        for member in members {
+            let cmd = DeviceCommand {
+                room: member.room,
+                device: member.device,
+                command: command.to_string(),
+                data: data.to_vec(),
+            };
+            let (ok, result) = match self.execute_device_command(&cmd) {
+                Ok(res) => (true, res),
+                Err(e) => (false, format!("error: {e}")),
+            };
+            outcome.push(MemberOutcome {
+                room: cmd.room,
+                device: cmd.device,
+                ok,
+                result,
+            });
+        }
+        outcome
+    }
+
+    /// отчет по устройствам группы
+    pub fn group_report(&self, id: &str) -> SmartHomeResult<String> {
+        let group = self.groups.get(id)?;
+
+        let mut report = format!("\n{} {}", group.id, group.name);
+        for member in &group.members {
+            report += "\n";
+            let device = self
+                .resolve_device(&member.room, &member.device)
+                .map(|id| &self.rooms[&member.room][&id]);
+            match device {
+                Some(device) => report += format!("--> {}\n", device.report()).as_str(),
+                None => {
+                    let path = Home::device_path(&member.room, &member.device);
+                    report += format!("--> {path}: device not found\n").as_str()
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// применить сцену ко всем ее устройствам, результат - по каждому устройству
+    pub fn apply_scene(&mut self, id: &str) -> SmartHomeResult<Vec<SceneOutcome>> {
+        let scene = self.scenes.get(id)?.clone();
+
+        let outcomes = scene
+            .entries
+            .iter()
+            .map(|entry| {
+                let (ok, result) = match self.with_device(&entry.room, &entry.device, |dev| {
+                    apply_scene_entry(dev, entry)
+                }) {
+                    Ok(res) => res,
+                    Err(e) => (false, format!("error: {e}")),
+                };
+                SceneOutcome {
+                    room: entry.room.clone(),
+                    device: entry.device.clone(),
+                    ok,
+                    result,
+                }
+            })
+            .collect();
+
+        Ok(outcomes)
+    }
+
+    /// сцена из текущих статусов устройств заданных комнат (всех, если список пуст);
+    /// неисправные устройства в сцену не попадают
+    pub fn snapshot_scene(&self, id: &str, name: &str, rooms: &[String]) -> SmartHomeResult<Scene> {
+        let mut rooms: Vec<String> = match rooms.is_empty() {
+            true => self.rooms.keys().cloned().collect(),
+            false => rooms.to_vec(),
+        };
+        rooms.sort();
+
+        let mut entries = vec![];
+        for room in rooms {
+            let Some(devices) = self.rooms.get(&room) else {
+                return Err(SmartHomeError::RoomNonExist(room));
+            };
+            let mut ids: Vec<&String> = devices.keys().collect();
+            ids.sort();
+            for id in ids {
+                let state = devices[id].device_state();
+                if state == DeviceState::Broken {
+                    continue;
+                }
+                entries.push(SceneEntry {
+                    room: room.clone(),
+                    device: id.clone(),
+                    state: Some(state),
+                    readings: Readings::new(),
+                });
+            }
+        }
+
+        Ok(Scene {
+            id: id.to_string(),
+            name: name.to_string(),
+            entries,
+        })
+    }
+
+    /// путь устройства для отчетов: уровни локации комнаты и идентификатор устройства
     pub fn device_path(r: &str, d: &str) -> String {
-        format!("{}=>{}", r, d)
+        let mut path: Vec<&str> = r.split(LOCATION_SEPARATOR).collect();
+        path.push(d);
+        path.join("=>")
+    }
+
+    /// комнаты в поддереве локации, включая ее саму, по порядку
+    pub fn rooms_within(&self, location: &str) -> SmartHomeResult<Vec<String>> {
+        self.room(location)?;
+        let mut rooms: Vec<String> = self
+            .rooms
+            .keys()
+            .filter(|r| is_within(r, location))
+            .cloned()
+            .collect();
+        rooms.sort();
+        Ok(rooms)
+    }
+
+    /// дерево локаций дома или поддерево заданной локации
+    pub fn locations(&self, root: Option<&str>) -> SmartHomeResult<Vec<LocationNode>> {
+        let mut rooms = BTreeMap::new();
+        for (room, devices) in self.rooms.iter() {
+            let mut ids: Vec<String> = devices.keys().cloned().collect();
+            ids.sort();
+            rooms.insert(room.clone(), ids);
+        }
+        match root {
+            None => Ok(build_tree(&rooms, None)),
+            Some(root) => {
+                self.room(root)?;
+                let mut tree = build_tree(&rooms, parent_location(root));
+                tree.retain(|node| node.path == root);
+                Ok(tree)
+            }
+        }
+    }
+
+    /// отчет о состоянии устройств локации и вложенных в нее комнат
+    pub fn location_report(&self, location: &str) -> SmartHomeResult<String> {
+        let mut report = String::new();
+        for room in self.rooms_within(location)? {
+            report += "\n";
+            report += room.as_str();
+
+            let mut devices: Vec<_> = self.rooms[&room].iter().collect();
+            devices.sort_by(|a, b| a.0.cmp(b.0));
+            for (_id, device) in devices {
+                report += "\n";
+                report += format!("--> {}\n", device.report()).as_str();
+            }
+        }
+        Ok(report)
+    }
+
+    /// состояние и показания всех устройств дома для метрик
+    pub fn device_gauges(&self) -> Vec<DeviceGauge> {
+        self.rooms
+            .iter()
+            .flat_map(|(room, devices)| {
+                devices.iter().map(|(id, device)| DeviceGauge {
+                    home: self.name.clone(),
+                    room: room.clone(),
+                    device: id.clone(),
+                    state: device.device_state(),
+                    readings: device.get_readings(),
+                })
+            })
+            .collect()
+    }
+
+    /// идентификатор устройства в комнате по идентификатору или отображаемому имени
+    pub fn resolve_device(&self, room: &str, device: &str) -> Option<String> {
+        let devices = self.rooms.get(room)?;
+        if devices.contains_key(device) {
+            return Some(device.to_string());
+        }
+        devices
+            .iter()
+            .find(|(_, d)| d.get_name() == device)
+            .map(|(id, _)| id.clone())
+    }
+
+    /// комната, в которой находится устройство с данным идентификатором
+    pub fn device_room(&self, id: &str) -> Option<String> {
+        self.rooms
+            .iter()
+            .find(|(_, devices)| devices.contains_key(id))
+            .map(|(room, _)| room.clone())
+    }
+
+    /// выполнить действие над устройством и опубликовать события о его изменениях
+    pub fn with_device<R, F>(&mut self, room: &str, device: &str, action: F) -> SmartHomeResult<R>
+    where
+        F: FnOnce(&mut dyn SmartDevice) -> R,
+    {
+        let Some(id) = self.resolve_device(room, device) else {
+            return Err(match self.rooms.contains_key(room) {
+                true => SmartHomeError::NoDeviceInRoom {
+                    name: device.to_string(),
+                    room: room.to_string(),
+                },
+                false => SmartHomeError::RoomNonExist(room.to_string()),
+            });
+        };
+
+        self.observe(room, &id);
+        let device = self.rooms.get_mut(room).unwrap().get_mut(&id).unwrap();
+        let result = action(&mut **device);
+        self.observe(room, &id);
+        self.dispatch_pending();
+
+        Ok(result)
+    }
+
+    /// выполнить команду устройству, как запрос deviceExecute
+    pub fn execute_device_command(&mut self, cmd: &DeviceCommand) -> Result<String, CommandError> {
+        self.with_device(&cmd.room, &cmd.device, |dev| {
+            execute_command(dev, &cmd.command, &cmd.data)
+        })?
+    }
+
+    /// сверить все устройства с последним известным состоянием и опубликовать изменения
+    pub fn observe_devices(&mut self) {
+        let devices: Vec<(String, String)> = self
+            .rooms
+            .iter()
+            .flat_map(|(room, devices)| devices.keys().map(|id| (room.clone(), id.clone())))
+            .collect();
+
+        self.observed
+            .retain(|id, _| devices.iter().any(|(_, dev_id)| dev_id == id));
+        for (room, id) in devices {
+            self.observe(&room, &id);
+        }
+    }
+
+    /// разослать событие подписчикам дома
+    pub fn publish(&mut self, event: HomeEvent) {
+        self.events.publish(&event);
+        self.notifier.notify(&event);
+        self.pending.push_back(event);
+    }
+
+    /// периодическая обработка: изменения устройств, расписания, тревоги,
+    /// правила, зависящие от времени, и передача оповещений потокам доставки
+    pub fn tick(&mut self) {
+        self.observe_devices();
+
+        let now = self.clock.now();
+        self.sample_history(now);
+        self.meter_energy(now);
+
+        let due = self.scheduler.due(now);
+        if !due.is_empty() {
+            self.run_due(due);
+            // время следующего запуска изменилось, ошибку сохранения переживем до следующего раза
+            self.persist_or_warn();
+        }
+
+        let mut alarms = mem::take(&mut self.alarms);
+        let changed = alarms.evaluate(self, now);
+        self.alarms = alarms;
+        if !changed.is_empty() {
+            for record in changed {
+                self.publish(HomeEvent::AlarmChanged { record });
+            }
+            self.persist_or_warn();
+        }
+
+        let mut rules = mem::take(&mut self.rules);
+        let fired = rules.on_tick(self, now);
+        self.rules = rules;
+
+        self.run_fired(fired);
+        self.dispatch_pending();
+
+        self.notifier.process(now);
+    }
+
+    /// обработать накопленные события правилами автоматизации
+    fn dispatch_pending(&mut self) {
+        if self.dispatching {
+            return;
+        }
+        self.dispatching = true;
+
+        let mut handled = 0;
+        while let Some(event) = self.pending.pop_front() {
+            handled += 1;
+            if handled > MAX_CHAINED_EVENTS {
+                self.pending.clear();
+                break;
+            }
+
+            let now = self.clock.now();
+            let mut rules = mem::take(&mut self.rules);
+            let fired = rules.on_event(self, &event, now);
+            self.rules = rules;
+
+            self.run_fired(fired);
+        }
+
+        self.dispatching = false;
+    }
+
+    fn run_fired(&mut self, fired: Vec<FiredRule>) {
+        for rule in fired {
+            let outcomes = self.run_actions(&rule.actions);
+            self.publish(HomeEvent::RuleFired {
+                rule: rule.rule,
+                outcomes,
+            });
+        }
+    }
+
+    /// подтвердить тревогу и оповестить об этом подписчиков
+    pub fn acknowledge_alarm(&mut self, seq: u64) -> SmartHomeResult<()> {
+        let record = self.alarms.acknowledge(seq, self.clock.now())?;
+        self.publish(HomeEvent::AlarmChanged { record });
+        self.dispatch_pending();
+        self.persist()
+    }
+
+    /// удалить тревогу, снять ее не снятые записи и оповестить об этом подписчиков
+    pub fn remove_alarm(&mut self, id: &str) -> SmartHomeResult<()> {
+        let cleared = self.alarms.remove(id, self.clock.now())?;
+        for record in cleared {
+            self.publish(HomeEvent::AlarmChanged { record });
+        }
+        self.dispatch_pending();
+        self.persist()
+    }
+
+    /// отчет о потреблении электроэнергии за период, по комнатам и устройствам;
+    /// `tariff` заменяет тариф счетчика
+    pub fn energy_report(&self, period: EnergyPeriod, tariff: Option<f64>) -> EnergyReport {
+        let tariff = tariff.or(self.energy.tariff);
+        let (from, to) = period.range(self.clock.now().date());
+        let cost = |kwh: f64| tariff.map(|price| kwh * price);
+
+        let mut rooms: Vec<&String> = self.rooms.keys().collect();
+        rooms.sort();
+
+        let mut report = EnergyReport {
+            period,
+            from,
+            to,
+            tariff,
+            kwh: 0.0,
+            cost: None,
+            rooms: vec![],
+        };
+        for room in rooms {
+            let mut devices: Vec<DeviceEnergy> = self.rooms[room]
+                .iter()
+                .filter(|(_, device)| device.get_readings().contains_key("power"))
+                .map(|(id, device)| {
+                    let kwh = self.energy.consumption(id, from, to);
+                    DeviceEnergy {
+                        device: id.clone(),
+                        name: device.get_name(),
+                        kwh,
+                        cost: cost(kwh),
+                    }
+                })
+                .collect();
+            if devices.is_empty() {
+                continue;
+            }
+            devices.sort_by(|a, b| a.device.cmp(&b.device));
+
+            let kwh = devices.iter().map(|d| d.kwh).sum();
+            report.kwh += kwh;
+            report.rooms.push(RoomEnergy {
+                room: room.clone(),
+                kwh,
+                cost: cost(kwh),
+                devices,
+            });
+        }
+        report.cost = cost(report.kwh);
+        report
+    }
+
+    /// учесть потребление включенных устройств и периодически сохранять счетчик
+    fn meter_energy(&mut self, now: NaiveDateTime) {
+        for (id, (state, readings)) in self.observed.iter() {
+            let Some(power) = readings.get("power") else {
+                continue;
+            };
+            let power = match state {
+                DeviceState::On => *power,
+                _ => 0.0,
+            };
+            self.energy.integrate(id, power, now);
+        }
+
+        if self.data_dir.is_some()
+            && self
+                .energy_saved
+                .is_none_or(|saved| now - saved >= TimeDelta::minutes(ENERGY_SAVE_MINUTES))
+        {
+            self.energy_saved = Some(now);
+            self.persist_or_warn();
+        }
+    }
+
+    /// записать в историю показания устройств, не менявшиеся дольше интервала записи
+    fn sample_history(&mut self, now: NaiveDateTime) {
+        if !self.history.is_enabled() {
+            return;
+        }
+        for (id, (state, readings)) in self.observed.iter() {
+            let sample = Sample {
+                ts: now,
+                state: *state,
+                readings: readings.clone(),
+            };
+            // ошибка записи истории не должна останавливать дом
+            if let Err(e) = self.history.record(id, sample, false) {
+                warn!(home = self.name.as_str(), device = id, error:% = e; "history write failed");
+            }
+        }
+        if let Err(e) = self.history.prune(now) {
+            warn!(home = self.name.as_str(), error:% = e; "history prune failed");
+        }
+    }
+
+    fn run_due(&mut self, due: Vec<DueSchedule>) {
+        for schedule in due {
+            let outcomes = self.run_actions(&schedule.actions);
+            self.publish(HomeEvent::ScheduleFired {
+                schedule: schedule.schedule,
+                outcomes,
+            });
+        }
+    }
+
+    fn run_actions(&mut self, actions: &[DeviceCommand]) -> Vec<String> {
+        actions
+            .iter()
+            .map(|cmd| match self.execute_device_command(cmd) {
+                Ok(res) => res,
+                Err(e) => format!("error: {e}"),
+            })
+            .collect()
+    }
+
+    fn observe(&mut self, room: &str, id: &str) {
+        let Some(device) = self.rooms.get(room).and_then(|devices| devices.get(id)) else {
+            return;
+        };
+        let state = device.device_state();
+        let readings = device.get_readings();
+
+        let mut events = vec![];
+        if let Some((old_state, old_readings)) = self.observed.get(id) {
+            if *old_state != state {
+                events.push(HomeEvent::StateChanged {
+                    room: room.to_string(),
+                    device: id.to_string(),
+                    from: *old_state,
+                    to: state,
+                });
+                if state == DeviceState::Broken {
+                    events.push(HomeEvent::FaultDetected {
+                        room: room.to_string(),
+                        device: id.to_string(),
+                        fault: device.fault(),
+                    });
+                }
+            }
+            for (reading, value) in readings.iter() {
+                if old_readings.get(reading) != Some(value) {
+                    events.push(HomeEvent::ReadingUpdated {
+                        room: room.to_string(),
+                        device: id.to_string(),
+                        reading: reading.clone(),
+                        value: *value,
+                    });
+                }
+            }
+        }
+
+        // в историю - первое наблюдение и каждое изменение
+        let changed = !events.is_empty() || !self.observed.contains_key(id);
+        if changed && self.history.is_enabled() {
+            let sample = Sample {
+                ts: self.clock.now(),
+                state,
+                readings: readings.clone(),
+            };
+            if let Err(e) = self.history.record(id, sample, true) {
+                warn!(home = self.name.as_str(), device = id, error:% = e; "history write failed");
+            }
+        }
+
+        self.observed.insert(id.to_string(), (state, readings));
+        for event in events {
+            self.publish(event);
+        }
+    }
+}
+
+/// установить устройству целевые статус и параметры сцены
+fn apply_scene_entry(dev: &mut dyn SmartDevice, entry: &SceneEntry) -> (bool, String) {
+    let mut ok = true;
+    let mut results = vec![];
+
+    if let Some(state) = entry.state {
+        if dev.device_state() != state {
+            results.push(dev.set_device_state(state));
+            ok &= dev.device_state() == state;
+        }
+    }
+    for (reading, value) in &entry.readings {
+        match dev.update_reading(reading, *value) {
+            true => results.push(format!("{reading} = {value}")),
+            false => {
+                ok = false;
+                results.push(format!("reading '{reading}' is not supported"));
+            }
+        }
+    }
+
+    match results.is_empty() {
+        true => (ok, "no changes".to_string()),
+        false => (ok, results.join("; ")),
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::Home;
+    use crate::automation::rule::Trigger;
+    use crate::events::event::HomeEvent;
+    use crate::info_provider::json_provider::JsonDeviceInfoProvider;
     use crate::my_smart_home::smart_home::SmartHome;
-    use crate::smart_device::device::VecOfDevice;
+    use crate::smart_device::device::{DeviceState, SmartDevice, VecOfDevice};
     use crate::smart_device::socket::Socket;
+    use serde_json::json;
     use std::collections::HashSet;
+    use std::sync::{Arc, Mutex};
 
     fn setup() -> Home {
         let mut home: Home = Home::new(String::from("MyHome")).unwrap();
@@ -180,4 +1005,283 @@ mod test {
         let _ = home.del_device(&room, "Smart Socket 2");
         assert_eq!(home.get_devices(&room).unwrap().len(), 0);
     }
+
+    #[test]
+    fn test_device_lookup_by_id_or_name() {
+        let mut home = setup();
+        let room: String = "Kitchen".into();
+
+        let _ = home.add_device(&room, Box::new(Socket::new("1")));
+
+        let by_id = home.get_device(&room, "socket-1").unwrap().get_id();
+        let by_name = home.get_device(&room, "Smart Socket 1").unwrap().get_id();
+        assert_eq!(by_id, by_name);
+
+        // после переименования идентификатор не меняется
+        home.rename_device(&room, "socket-1", "Розетка у плиты")
+            .unwrap();
+        assert!(home.get_device(&room, "Smart Socket 1").is_err());
+        assert!(home.get_device(&room, "Розетка у плиты").is_ok());
+        assert!(home.get_devices(&room).unwrap().contains("socket-1"));
+    }
+
+    #[test]
+    fn test_device_id_unique_in_home() {
+        let mut home = setup();
+
+        let _ = home.add_device("Kitchen", Box::new(Socket::new("1")));
+
+        let mut same_id = Socket::new("1");
+        same_id.set_name("Another socket");
+        let res = home.add_device("Dining", Box::new(same_id));
+        assert!(res.is_err());
+        assert!(res.err().unwrap().to_string().contains("same id"));
+
+        let res = home.add_device("Dining", Box::new(Socket::new("bad=>id")));
+        assert!(res.is_err());
+        assert!(res.err().unwrap().to_string().contains("invalid device id"));
+    }
+
+    #[test]
+    fn test_provider_report_with_separator_in_name() {
+        let mut home = setup();
+
+        let mut socket = Socket::new("1");
+        socket.set_name("Socket=>1");
+        let _ = home.add_device("Kitchen", Box::new(socket));
+
+        let provider = JsonDeviceInfoProvider::from_json(json!({
+            "schema": {"Kitchen": ["Socket=>1"], "Kitchen=>Socket": ["1"]}
+        }))
+        .unwrap();
+        let report = home.create_provider_report(&provider);
+
+        assert!(report.contains("Идентификатор: socket-1"));
+        assert!(report.contains("Kitchen=>Socket=>1\x1b[0m не найдено"));
+    }
+
+    #[test]
+    fn test_rename_room() {
+        let mut home = setup();
+
+        let _ = home.add_device("Kitchen", Box::new(Socket::new("1")));
+        assert!(home.rename_room("Kitchen", "Cuisine").is_ok());
+        assert!(home.get_device("Cuisine", "socket-1").is_ok());
+        assert!(home.room("Kitchen").is_err());
+
+        let res = home.rename_room("Cuisine", "Dining");
+        assert!(res.err().unwrap().to_string().contains("same name"));
+
+        let res = home.rename_room("Bathroom", "Spa");
+        assert!(res.err().unwrap().to_string().contains("not exist"));
+    }
+
+    #[test]
+    fn test_nested_rooms() {
+        let mut home = Home::new(String::from("MyHome")).unwrap();
+        for floor in ["floor-1", "floor-2"] {
+            home.add_room(floor.into(), vec![]).unwrap();
+        }
+        let res = home.add_room("attic/bathroom".into(), vec![]);
+        assert!(res.err().unwrap().to_string().contains("not exist"));
+        let res = home.add_room("floor-1/".into(), vec![]);
+        assert!(res.err().unwrap().to_string().contains("invalid location"));
+
+        let bath1: VecOfDevice = vec![Box::new(Socket::new("1"))];
+        home.add_room("floor-1/bathroom".into(), bath1).unwrap();
+        let bath2: VecOfDevice = vec![Box::new(Socket::new("2"))];
+        home.add_room("floor-2/bathroom".into(), bath2).unwrap();
+
+        assert_eq!(
+            home.rooms_within("floor-2").unwrap(),
+            vec!["floor-2", "floor-2/bathroom"]
+        );
+        assert_eq!(
+            Home::device_path("floor-2/bathroom", "socket-2"),
+            "floor-2=>bathroom=>socket-2"
+        );
+        let report = home.location_report("floor-1").unwrap();
+        assert!(report.contains("Smart Socket 1") && !report.contains("Smart Socket 2"));
+
+        let res = home.del_room("floor-1");
+        assert!(res.err().unwrap().to_string().contains("nested rooms"));
+        let res = home.rename_room("floor-1", "floor-1/bathroom/shower");
+        assert!(res.err().unwrap().to_string().contains("into itself"));
+
+        home.rename_room("floor-1", "ground").unwrap();
+        assert!(home.get_device("ground/bathroom", "socket-1").is_ok());
+        assert!(home.room("floor-1/bathroom").is_err());
+    }
+
+    #[test]
+    fn test_rename_room_updates_references() {
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.add_room("floor-1".into(), vec![]).unwrap();
+        let bath: VecOfDevice = vec![Box::new(Socket::new("1"))];
+        home.add_room("floor-1/bathroom".into(), bath).unwrap();
+
+        let action = json!({"room": "floor-1/bathroom", "device": "socket-1", "command": "switch", "data": ["off"]});
+        let scene = json!({"id": "night", "entries": [{"room": "floor-1/bathroom", "device": "socket-1", "state": "off"}]});
+        home.scenes
+            .add(serde_json::from_value(scene).unwrap())
+            .unwrap();
+        let schedule = json!({"id": "off", "when": {"type": "cron", "expr": "0 23 * * *"}, "actions": [action]});
+        let now = home.clock.now();
+        home.scheduler
+            .add(serde_json::from_value(schedule).unwrap(), now)
+            .unwrap();
+        let rule = json!({
+            "id": "broken-off",
+            "trigger": {"type": "state_change", "room": "floor-1/bathroom", "device": "socket-1", "to": "broken"},
+            "actions": [action]
+        });
+        home.rules
+            .add(serde_json::from_value(rule).unwrap())
+            .unwrap();
+        let alarm = json!({"id": "broken", "room": "floor-1/bathroom", "device": "socket-1", "condition": {"type": "broken"}});
+        home.alarms
+            .add(serde_json::from_value(alarm).unwrap())
+            .unwrap();
+
+        home.tick();
+        home.with_device("floor-1/bathroom", "socket-1", |dev| dev.switch("broken"))
+            .unwrap();
+        home.tick();
+        assert_eq!(home.alarms.records(true).len(), 1);
+
+        home.rename_room("floor-1", "ground").unwrap();
+        let room = "ground/bathroom";
+        assert_eq!(home.scenes.get("night").unwrap().entries[0].room, room);
+        assert_eq!(home.scheduler.schedules()[0].actions[0].room, room);
+        assert_eq!(home.rules.rules()[0].actions[0].room, room);
+        assert_eq!(home.alarms.definitions()[0].room, room);
+
+        // тревога по переименованной комнате остается открытой
+        home.tick();
+        let open = home.alarms.records(true);
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].room, room);
+    }
+
+    #[test]
+    fn test_move_device_updates_references() {
+        let mut home = Home::new("MyHome".into()).unwrap();
+        let kitchen: VecOfDevice = vec![Box::new(Socket::new("1")), Box::new(Socket::new("2"))];
+        home.add_room("kitchen".into(), kitchen).unwrap();
+        home.add_room("garage".into(), vec![]).unwrap();
+
+        // на перенесенное устройство ссылаются по имени и по идентификатору
+        let scene = json!({"id": "night", "entries": [
+            {"room": "kitchen", "device": "Smart Socket 1", "state": "off"},
+            {"room": "kitchen", "device": "socket-2", "state": "off"}
+        ]});
+        home.scenes
+            .add(serde_json::from_value(scene).unwrap())
+            .unwrap();
+        let action =
+            json!({"room": "kitchen", "device": "socket-1", "command": "switch", "data": ["off"]});
+        let schedule = json!({"id": "off", "when": {"type": "cron", "expr": "0 23 * * *"}, "actions": [action]});
+        let now = home.clock.now();
+        home.scheduler
+            .add(serde_json::from_value(schedule).unwrap(), now)
+            .unwrap();
+        let rule = json!({
+            "id": "broken-off",
+            "trigger": {"type": "state_change", "room": "kitchen", "device": "socket-1", "to": "broken"},
+            "actions": [action]
+        });
+        home.rules
+            .add(serde_json::from_value(rule).unwrap())
+            .unwrap();
+        let alarm = json!({"id": "broken", "room": "kitchen", "device": "socket-1", "condition": {"type": "broken"}});
+        home.alarms
+            .add(serde_json::from_value(alarm).unwrap())
+            .unwrap();
+
+        home.tick();
+        home.with_device("kitchen", "socket-1", |dev| dev.switch("broken"))
+            .unwrap();
+        home.tick();
+        assert_eq!(home.alarms.records(true).len(), 1);
+
+        home.move_device("kitchen", "socket-1", "garage").unwrap();
+        let entries = &home.scenes.get("night").unwrap().entries;
+        assert_eq!(entries[0].room, "garage");
+        assert_eq!(entries[1].room, "kitchen");
+        assert_eq!(home.scheduler.schedules()[0].actions[0].room, "garage");
+        let rule = &home.rules.rules()[0];
+        assert!(matches!(&rule.trigger, Trigger::StateChange { room, .. } if room == "garage"));
+        assert_eq!(rule.actions[0].room, "garage");
+        assert_eq!(home.alarms.definitions()[0].room, "garage");
+
+        // тревога по перенесенному устройству остается открытой
+        home.tick();
+        let open = home.alarms.records(true);
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].room, "garage");
+    }
+
+    #[test]
+    fn test_rename_and_move_device_conflicts() {
+        let mut home = setup();
+
+        let _ = home.add_device("Kitchen", Box::new(Socket::new("1")));
+        let _ = home.add_device("Kitchen", Box::new(Socket::new("2")));
+        let mut dining_socket = Socket::new("3");
+        dining_socket.set_name("Smart Socket 1");
+        let _ = home.add_device("Dining", Box::new(dining_socket));
+
+        let res = home.rename_device("Kitchen", "socket-2", "Smart Socket 1");
+        assert!(res.err().unwrap().to_string().contains("same name"));
+
+        let res = home.move_device("Kitchen", "socket-1", "Dining");
+        assert!(res.err().unwrap().to_string().contains("same name"));
+
+        let res = home.move_device("Kitchen", "socket-1", "Kitchen");
+        assert!(res.err().unwrap().to_string().contains("already in room"));
+
+        assert!(home.move_device("Kitchen", "socket-2", "Living").is_ok());
+        assert!(home.get_device("Living", "Smart Socket 2").is_ok());
+        assert_eq!(home.get_devices("Kitchen").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_device_events() {
+        let mut home = setup();
+        let _ = home.add_device("Kitchen", Box::new(Socket::new("1")));
+
+        let (_, events) = home.events.subscribe_channel();
+        let counter = Arc::new(Mutex::new(0));
+        let counted = counter.clone();
+        home.events
+            .subscribe(move |_| *counted.lock().unwrap() += 1);
+
+        home.with_device("Kitchen", "Smart Socket 1", |d| d.switch("on"))
+            .unwrap();
+        home.with_device("Kitchen", "socket-1", |d| d.update_reading("power", 100.0))
+            .unwrap();
+        home.with_device("Kitchen", "socket-1", |d| d.switch("broken"))
+            .unwrap();
+
+        let received: Vec<HomeEvent> = events.try_iter().collect();
+        assert_eq!(received.len(), 4);
+        assert_eq!(
+            received[0],
+            HomeEvent::StateChanged {
+                room: "Kitchen".into(),
+                device: "socket-1".into(),
+                from: DeviceState::Off,
+                to: DeviceState::On,
+            }
+        );
+        assert!(matches!(received[1], HomeEvent::ReadingUpdated { value, .. } if value == 100.0));
+        assert!(matches!(received[3], HomeEvent::FaultDetected { .. }));
+        assert_eq!(*counter.lock().unwrap(), 4);
+
+        // без изменений событий нет
+        home.with_device("Kitchen", "socket-1", |d| d.get_current_info())
+            .unwrap();
+        home.observe_devices();
+        assert_eq!(events.try_iter().count(), 0);
+    }
 }
@@ -0,0 +1,455 @@
+use super::error::{SmartHomeError, SmartHomeResult};
+use super::home::{Home, DATA_ENTRIES};
+use super::smart_home_tcp::{deny, log_request, observe_request, pack_reply, SmartHomePublicApi};
+use super::storage::{load_json, save_json, storage_error};
+use crate::access::policy::RequestContext;
+use crate::access::role::HUB_METHODS;
+use crate::command::queue::RPCQueue;
+use crate::events::event::HomeEvent;
+use crate::json_rpc::reply::JsonRpcReplyMsg;
+use crate::json_rpc::request::JsonRpcRequest;
+use crate::json_rpc::utils::unquoted;
+use crate::metrics;
+use crate::smart_device::device::is_valid_device_id;
+use chrono::Local;
+use log::info;
+use serde_json as json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::Instant;
+
+/// список домов в каталоге данных хаба
+const HOMES_FILE: &str = "homes.json";
+
+/// Несколько домов в одном сервере.
+/// Запрос выполняется в доме из поля "home", без него - в основном доме.
+pub struct HomeHub {
+    homes: BTreeMap<String, Home>,
+    /// основной дом, его нельзя удалить
+    default: String,
+    /// каталог данных: данные каждого дома - в подкаталоге с его именем
+    data_dir: Option<PathBuf>,
+    /// куда пересылать события домов, в том числе добавленных позже
+    events: Option<Sender<(String, HomeEvent)>>,
+}
+
+impl HomeHub {
+    /// хаб с одним, основным домом
+    pub fn new(home: Home) -> Self {
+        let default = home.name.clone();
+        Self {
+            homes: BTreeMap::from([(default.clone(), home)]),
+            default,
+            data_dir: None,
+            events: None,
+        }
+    }
+
+    /// имена домов
+    pub fn homes(&self) -> Vec<String> {
+        self.homes.keys().cloned().collect()
+    }
+
+    /// дом по имени, без имени - основной
+    pub fn home(&mut self, name: Option<&str>) -> SmartHomeResult<&mut Home> {
+        let name = name.unwrap_or(&self.default);
+        self.homes
+            .get_mut(name)
+            .ok_or_else(|| SmartHomeError::HomeNonExist(name.to_string()))
+    }
+
+    /// добавить пустой дом
+    pub fn add_home(&mut self, name: &str) -> SmartHomeResult<()> {
+        self.insert_home(name)?;
+        self.persist()
+    }
+
+    /// удалить дом; его сохраненные данные переносятся в архив `<дом>~<время удаления>`
+    /// в каталоге данных, чтобы новый дом с тем же именем начинался с пустого каталога
+    pub fn del_home(&mut self, name: &str) -> SmartHomeResult<()> {
+        if name == self.default {
+            return Err(SmartHomeError::DefaultHomeRemoval(name.to_string()));
+        }
+        if self.homes.remove(name).is_none() {
+            return Err(SmartHomeError::HomeNonExist(name.to_string()));
+        }
+        self.persist()?;
+
+        let Some(dir) = &self.data_dir else {
+            return Ok(());
+        };
+        let data = dir.join(name);
+        if !data.exists() {
+            return Ok(());
+        }
+        // '~' недопустим в имени дома, архив не спутать с домом
+        let archive = dir.join(format!("{name}~{}", Local::now().format("%Y%m%d-%H%M%S")));
+        fs::rename(&data, &archive).map_err(|e| storage_error(&data, e))?;
+        info!(home = name, archive:% = archive.display(); "home data archived");
+        Ok(())
+    }
+
+    /// задать каталог данных хаба и загрузить сохраненные дома;
+    /// данные прежних версий, хранившиеся прямо в каталоге данных, переносятся в каталог основного дома
+    pub fn set_data_dir(&mut self, dir: &Path) -> SmartHomeResult<()> {
+        let homes = dir.join(HOMES_FILE);
+        if homes.exists() {
+            let names: Vec<String> = load_json(&homes)?;
+            for name in names {
+                if !self.homes.contains_key(&name) {
+                    self.insert_home(&name)?;
+                }
+            }
+        } else {
+            self.migrate_legacy_layout(dir)?;
+        }
+        for (name, home) in self.homes.iter_mut() {
+            home.set_data_dir(&dir.join(name))?;
+        }
+        self.data_dir = Some(dir.to_path_buf());
+        self.persist()
+    }
+
+    /// сохранить список домов и данные каждого дома
+    pub fn persist(&self) -> SmartHomeResult<()> {
+        let Some(dir) = &self.data_dir else {
+            return Ok(());
+        };
+        save_json(&dir.join(HOMES_FILE), &self.homes())?;
+        for home in self.homes.values() {
+            home.persist()?;
+        }
+        Ok(())
+    }
+
+    /// перенести данные дома из каталога данных в каталог основного дома;
+    /// уже существующие в каталоге дома файлы не перезаписываются
+    fn migrate_legacy_layout(&self, dir: &Path) -> SmartHomeResult<()> {
+        let target = dir.join(&self.default);
+        for entry in DATA_ENTRIES {
+            let legacy = dir.join(entry);
+            if !legacy.exists() {
+                continue;
+            }
+            let moved = target.join(entry);
+            if moved.exists() {
+                return Err(SmartHomeError::Storage(format!(
+                    "{}: both legacy and per-home data exist",
+                    moved.display()
+                )));
+            }
+            fs::create_dir_all(&target).map_err(|e| storage_error(&target, e))?;
+            fs::rename(&legacy, &moved).map_err(|e| storage_error(&legacy, e))?;
+            info!(home = self.default.as_str(), entry = entry; "legacy home data migrated");
+        }
+        Ok(())
+    }
+
+    fn insert_home(&mut self, name: &str) -> SmartHomeResult<()> {
+        if !is_valid_home_name(name) {
+            return Err(SmartHomeError::InvalidHomeName(name.to_string()));
+        }
+        if self.homes.contains_key(name) {
+            return Err(SmartHomeError::HomeSameNameExist(name.to_string()));
+        }
+        let mut home = Home::new(name.to_string())
+            .map_err(|e| SmartHomeError::Storage(format!("{name}: {e}")))?;
+        if let Some(dir) = &self.data_dir {
+            home.set_data_dir(&dir.join(name))?;
+        }
+        if let Some(tx) = &self.events {
+            home.forward_events(tx.clone());
+        }
+        self.homes.insert(name.to_string(), home);
+        Ok(())
+    }
+
+    fn default_home(&mut self) -> &mut Home {
+        self.homes
+            .get_mut(&self.default)
+            .expect("default home is never removed")
+    }
+
+    /// запрос к дому выполняет сам дом; запросы к хабу и к несуществующему дому
+    /// записываются в журнал аудита основного дома
+    fn handle_request(
+        &mut self,
+        rpc_cmd: JsonRpcRequest,
+        requests: &mut RPCQueue<JsonRpcRequest>,
+        ctx: &RequestContext,
+    ) -> Box<dyn JsonRpcReplyMsg> {
+        if !HUB_METHODS.contains(&rpc_cmd.method.as_str()) {
+            if let Ok(home) = self.home(rpc_cmd.home.as_deref()) {
+                return home.handle_request(rpc_cmd, requests, ctx);
+            }
+        }
+
+        log_request(&rpc_cmd, ctx);
+        let started = Instant::now();
+        let method = rpc_cmd.method.clone();
+        let entry = self.default_home().audit_entry(&rpc_cmd, ctx, None);
+        let reply = match deny(&rpc_cmd, ctx, None) {
+            Some(denied) => denied,
+            None => self.execute_request(rpc_cmd),
+        };
+        observe_request(&method, started, &*reply);
+        if let Some(entry) = entry {
+            self.default_home().record_audit(entry.finish(&*reply));
+        }
+        reply
+    }
+
+    /// выполнить запрос к самому хабу
+    fn execute_request(&mut self, rpc_cmd: JsonRpcRequest) -> Box<dyn JsonRpcReplyMsg> {
+        let mut error_code: i32 = 0;
+        let mut result: Option<json::Value> = None;
+
+        let resp = match rpc_cmd.method.as_str() {
+            "listHomes" => {
+                result = Some(json::json!(self.homes()));
+                String::new()
+            }
+
+            "addHome" | "delHome" => {
+                let name = unquoted(&rpc_cmd.params["name"]);
+                let res = match rpc_cmd.method.as_str() {
+                    "addHome" => self.add_home(&name),
+                    _ => self.del_home(&name),
+                };
+                match res {
+                    Ok(()) => format!("{}: success", rpc_cmd.method),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("{} error: {e}", rpc_cmd.method)
+                    }
+                }
+            }
+
+            // устройства всех домов
+            "getMetrics" => self.metrics(),
+
+            _ => {
+                let home = rpc_cmd.home.clone().unwrap_or_default();
+                error_code = 1;
+                format!("error: {}", SmartHomeError::HomeNonExist(home))
+            }
+        };
+
+        pack_reply(rpc_cmd.id, error_code, resp, result)
+    }
+}
+
+impl SmartHomePublicApi for HomeHub {
+    fn idle(&mut self) {
+        for home in self.homes.values_mut() {
+            home.tick();
+        }
+    }
+
+    fn metrics(&self) -> String {
+        let devices: Vec<_> = self.homes.values().flat_map(Home::device_gauges).collect();
+        metrics::registry::global().render(&devices)
+    }
+
+    fn forward_events(&mut self, tx: Sender<(String, HomeEvent)>) {
+        for home in self.homes.values_mut() {
+            home.forward_events(tx.clone());
+        }
+        self.events = Some(tx);
+    }
+
+    fn execute(&mut self, requests: &mut RPCQueue<JsonRpcRequest>, ctx: &RequestContext) -> String {
+        let mut replies: Vec<Box<dyn JsonRpcReplyMsg>> = vec![];
+
+        while let Some(rpc_cmd) = requests.pop() {
+            replies.push(self.handle_request(rpc_cmd, requests, ctx));
+        }
+
+        json::to_string_pretty(&replies).unwrap()
+    }
+}
+
+/// имя дома - имя его каталога данных: как идентификатор устройства,
+/// но без точки в начале, чтобы не получить "." или ".."
+fn is_valid_home_name(name: &str) -> bool {
+    is_valid_device_id(name) && !name.starts_with('.')
+}
+
+#[cfg(test)]
+mod test {
+    use super::HomeHub;
+    use crate::access::policy::RequestContext;
+    use crate::command::queue::RPCQueue;
+    use crate::json_rpc::request::JsonRpcRequest;
+    use crate::json_rpc::utils::get_validator;
+    use crate::my_smart_home::home::Home;
+    use crate::my_smart_home::smart_home::SmartHome;
+    use crate::my_smart_home::smart_home_tcp::SmartHomePublicApi;
+    use crate::smart_device::socket::Socket;
+    use serde_json::{json, Value};
+    use std::env;
+    use std::path::PathBuf;
+
+    fn call(hub: &mut HomeHub, request: Value) -> Value {
+        let batch = json!([request]);
+        let validator = get_validator(PathBuf::from("public_api.json")).unwrap();
+        assert!(validator.is_valid(&batch), "schema rejected: {batch}");
+
+        let mut requests = RPCQueue::<JsonRpcRequest>::default();
+        requests.push(serde_json::from_value(batch).unwrap());
+        let reply: Value =
+            serde_json::from_str(&hub.execute(&mut requests, &RequestContext::full())).unwrap();
+        reply[0].clone()
+    }
+
+    fn request(home: Option<&str>, method: &str, params: Value) -> Value {
+        let mut request = json!({"id": "1", "jsonrpc": "2.0", "method": method, "params": params});
+        if let Some(home) = home {
+            request["home"] = json!(home);
+        }
+        request
+    }
+
+    #[test]
+    fn test_requests_routed_to_homes() {
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.add_room("kitchen".into(), vec![Box::new(Socket::new("1"))])
+            .unwrap();
+        let mut hub = HomeHub::new(home);
+
+        let reply = call(&mut hub, request(None, "addHome", json!({"name": "dacha"})));
+        assert_eq!(reply["result"]["data"], "addHome: success");
+        let reply = call(&mut hub, request(None, "addHome", json!({"name": "dacha"})));
+        assert_eq!(reply["error"]["code"], 1);
+        for name in ["../etc", "..", ".", ".hidden"] {
+            let reply = call(&mut hub, request(None, "addHome", json!({ "name": name })));
+            assert_eq!(reply["error"]["code"], 1, "{name}");
+        }
+
+        let reply = call(
+            &mut hub,
+            request(Some("dacha"), "addRoom", json!({"name": "veranda"})),
+        );
+        assert_eq!(reply["result"]["data"], "addRoom: success");
+
+        // без поля home - основной дом
+        let reply = call(
+            &mut hub,
+            request(None, "getDevices", json!({"room": "kitchen"})),
+        );
+        assert_eq!(reply["result"]["data"], "socket-1: Smart Socket 1");
+        let reply = call(
+            &mut hub,
+            request(Some("dacha"), "getDevices", json!({"room": "kitchen"})),
+        );
+        assert_eq!(reply["error"]["code"], 1);
+
+        let reply = call(&mut hub, request(Some("flat"), "createReport", json!({})));
+        assert!(reply["error"]["data"]
+            .as_str()
+            .unwrap()
+            .contains("home: flat not exist"));
+
+        let reply = call(&mut hub, request(None, "listHomes", json!({})));
+        assert_eq!(reply["result"]["data"], json!(["MyHome", "dacha"]));
+
+        let reply = call(
+            &mut hub,
+            request(None, "delHome", json!({"name": "MyHome"})),
+        );
+        assert_eq!(reply["error"]["code"], 1);
+        let reply = call(&mut hub, request(None, "delHome", json!({"name": "dacha"})));
+        assert_eq!(reply["result"]["data"], "delHome: success");
+    }
+
+    #[test]
+    fn test_homes_persist() {
+        let dir = env::temp_dir().join(format!("smart_home_hub_{}", std::process::id()));
+        let mut hub = HomeHub::new(Home::new("MyHome".into()).unwrap());
+        hub.set_data_dir(&dir).unwrap();
+
+        call(&mut hub, request(None, "addHome", json!({"name": "dacha"})));
+        call(
+            &mut hub,
+            request(Some("dacha"), "addRoom", json!({"name": "veranda"})),
+        );
+        let params =
+            json!({"room": "veranda", "type": "socket", "params": {"id": "7", "name": "Фонарь"}});
+        call(&mut hub, request(Some("dacha"), "addDevice", params));
+        let cmd =
+            json!({"room": "veranda", "device": "Фонарь", "command": "switch", "data": ["on"]});
+        call(&mut hub, request(Some("dacha"), "deviceExecute", cmd));
+        hub.persist().unwrap();
+
+        let audit = call(&mut hub, request(None, "getAuditLog", json!({"limit": 10})));
+        let methods: Vec<&str> = audit["result"]["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["method"].as_str().unwrap())
+            .collect();
+        assert_eq!(methods, vec!["addHome"]);
+        let audit = call(&mut hub, request(Some("dacha"), "getAuditLog", json!({})));
+        assert_eq!(audit["result"]["data"].as_array().unwrap().len(), 3);
+
+        let mut restored = HomeHub::new(Home::new("MyHome".into()).unwrap());
+        restored.set_data_dir(&dir).unwrap();
+        assert_eq!(restored.homes(), vec!["MyHome", "dacha"]);
+        let dacha = restored.home(Some("dacha")).unwrap();
+        let device = dacha.get_device("veranda", "socket-7").unwrap();
+        assert_eq!(device.get_name(), "Фонарь");
+        assert_eq!(format!("{:?}", device.device_state()), "On");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_legacy_layout_migrated() {
+        let dir = env::temp_dir().join(format!("smart_home_hub_legacy_{}", std::process::id()));
+        // прежде данные основного дома лежали прямо в каталоге данных
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.set_data_dir(&dir).unwrap();
+        home.add_room("kitchen".into(), vec![Box::new(Socket::new("1"))])
+            .unwrap();
+        home.persist().unwrap();
+
+        let mut hub = HomeHub::new(Home::new("MyHome".into()).unwrap());
+        hub.set_data_dir(&dir).unwrap();
+        let home = hub.home(None).unwrap();
+        assert!(home.get_device("kitchen", "socket-1").is_ok());
+        assert!(dir.join("MyHome").join("topology.json").exists());
+        assert!(!dir.join("topology.json").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_deleted_home_archived() {
+        let dir = env::temp_dir().join(format!("smart_home_hub_del_{}", std::process::id()));
+        let mut hub = HomeHub::new(Home::new("MyHome".into()).unwrap());
+        hub.set_data_dir(&dir).unwrap();
+
+        call(&mut hub, request(None, "addHome", json!({"name": "dacha"})));
+        call(
+            &mut hub,
+            request(Some("dacha"), "addRoom", json!({"name": "veranda"})),
+        );
+        hub.persist().unwrap();
+        call(&mut hub, request(None, "delHome", json!({"name": "dacha"})));
+        assert!(!dir.join("dacha").exists());
+        let archived = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .any(|entry| entry.file_name().to_string_lossy().starts_with("dacha~"));
+        assert!(archived);
+
+        // дом с тем же именем создается пустым
+        call(&mut hub, request(None, "addHome", json!({"name": "dacha"})));
+        let reply = call(
+            &mut hub,
+            request(Some("dacha"), "addRoom", json!({"name": "veranda"})),
+        );
+        assert_eq!(reply["result"]["data"], "addRoom: success");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// разделитель уровней в пути локации: "floor-1/bathroom"
+pub const LOCATION_SEPARATOR: char = '/';
+
+/// Узел дерева локаций: этаж, зона или комната.
+/// Устройства могут быть в любом узле, не только в листьях.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct LocationNode {
+    /// имя узла - последний уровень пути
+    pub name: String,
+    /// полный путь узла, он же имя комнаты в доме
+    pub path: String,
+    /// идентификаторы устройств узла
+    pub devices: Vec<String>,
+    pub children: Vec<LocationNode>,
+}
+
+/// путь локации: непустые уровни без пробелов по краям
+pub fn is_valid_location(path: &str) -> bool {
+    path.split(LOCATION_SEPARATOR)
+        .all(|part| !part.is_empty() && part.trim() == part)
+}
+
+/// родительская локация, для верхнего уровня - None
+pub fn parent_location(path: &str) -> Option<&str> {
+    path.rsplit_once(LOCATION_SEPARATOR)
+        .map(|(parent, _)| parent)
+}
+
+/// находится ли комната в поддереве локации, включая саму локацию
+pub fn is_within(room: &str, location: &str) -> bool {
+    match room.strip_prefix(location) {
+        Some(rest) => rest.is_empty() || rest.starts_with(LOCATION_SEPARATOR),
+        None => false,
+    }
+}
+
+/// перенести комнату поддерева из одной локации в другую
+pub fn relocate(room: &str, from: &str, to: &str) -> String {
+    format!("{to}{}", &room[from.len()..])
+}
+
+/// обновить ссылку на комнату после переименования локации `from` в `to`
+pub fn relocate_ref(room: &mut String, from: &str, to: &str) {
+    if is_within(room, from) {
+        *room = relocate(room, from, to);
+    }
+}
+
+/// обновить ссылку на устройство после его переноса из комнаты `from` в `to`;
+/// `names` - идентификатор и имя перенесенного устройства, ссылка может быть любым из них
+pub fn move_device_ref(room: &mut String, device: &str, from: &str, names: &[&str], to: &str) {
+    if room == from && names.contains(&device) {
+        *room = to.to_string();
+    }
+}
+
+/// дерево локаций из путей комнат и их устройств;
+/// пути должны содержать всех своих родителей
+pub fn build_tree(rooms: &BTreeMap<String, Vec<String>>, root: Option<&str>) -> Vec<LocationNode> {
+    rooms
+        .iter()
+        .filter(|(path, _)| parent_location(path) == root)
+        .map(|(path, devices)| LocationNode {
+            name: path.rsplit(LOCATION_SEPARATOR).next().unwrap().to_string(),
+            path: path.clone(),
+            devices: devices.clone(),
+            children: build_tree(rooms, Some(path)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_location_paths() {
+        assert!(is_valid_location("kitchen"));
+        assert!(is_valid_location("floor-2/bathroom"));
+        assert!(!is_valid_location("floor-2/"));
+        assert!(!is_valid_location("/bathroom"));
+        assert!(!is_valid_location("floor-2/ bathroom"));
+
+        assert_eq!(
+            parent_location("house/floor-2/bathroom"),
+            Some("house/floor-2")
+        );
+        assert_eq!(parent_location("kitchen"), None);
+
+        assert!(is_within("floor-2/bathroom", "floor-2"));
+        assert!(is_within("floor-2", "floor-2"));
+        assert!(!is_within("floor-20/bathroom", "floor-2"));
+        assert_eq!(
+            relocate("floor-2/bathroom", "floor-2", "attic"),
+            "attic/bathroom"
+        );
+    }
+
+    #[test]
+    fn test_build_tree() {
+        let rooms = BTreeMap::from([
+            ("floor-1".to_string(), vec![]),
+            ("floor-1/bathroom".to_string(), vec!["socket-1".to_string()]),
+            ("floor-2".to_string(), vec!["thermometer-1".to_string()]),
+            ("floor-2/bathroom".to_string(), vec!["socket-2".to_string()]),
+        ]);
+
+        let tree = build_tree(&rooms, None);
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[1].devices, vec!["thermometer-1"]);
+        assert_eq!(tree[1].children[0].name, "bathroom");
+        assert_eq!(tree[1].children[0].path, "floor-2/bathroom");
+
+        let subtree = build_tree(&rooms, Some("floor-1"));
+        assert_eq!(subtree.len(), 1);
+        assert_eq!(subtree[0].devices, vec!["socket-1"]);
+    }
+}
@@ -1,5 +1,10 @@
 pub mod error;
 pub mod home;
+pub mod hub;
+pub mod location;
+pub mod query;
 pub mod smart_home;
 pub mod smart_home_tcp;
+pub mod storage;
+pub mod topology;
 // pub mod smart_home_udp;
@@ -0,0 +1,128 @@
+use super::location::is_within;
+use crate::automation::rule::CompareOp;
+use crate::smart_device::device::{DeviceState, Readings, SmartDevice};
+use crate::smart_device::fault::Fault;
+use serde::{Deserialize, Serialize};
+
+/// Условие на показание устройства
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReadingFilter {
+    pub reading: String,
+    pub op: CompareOp,
+    pub value: f32,
+}
+
+/// Фильтр устройств дома, все заданные условия должны выполняться
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeviceQuery {
+    /// имя типа: "socket", "kettle", "thermometer" или зарегистрированный тип
+    #[serde(default, rename = "type")]
+    pub device_type: Option<String>,
+    #[serde(default)]
+    pub state: Option<DeviceState>,
+    #[serde(default)]
+    pub room: Option<String>,
+    /// локация: устройства в ней и во вложенных комнатах
+    #[serde(default)]
+    pub location: Option<String>,
+    /// шаблон имени или идентификатора: '*' - любая последовательность, '?' - любой символ
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub readings: Vec<ReadingFilter>,
+}
+
+/// Найденное устройство и его данные
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DeviceMatch {
+    /// путь устройства: комната и идентификатор
+    pub path: String,
+    pub room: String,
+    pub device: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub device_type: String,
+    pub state: DeviceState,
+    pub readings: Readings,
+    pub fault: Option<Fault>,
+}
+
+impl DeviceQuery {
+    /// удовлетворяет ли устройство в комнате фильтру
+    pub fn matches(&self, room: &str, device: &dyn SmartDevice) -> bool {
+        if self.room.as_ref().is_some_and(|r| r != room) {
+            return false;
+        }
+        if self.location.as_ref().is_some_and(|l| !is_within(room, l)) {
+            return false;
+        }
+        if self
+            .device_type
+            .as_ref()
+            .is_some_and(|t| t != device.get_type().type_name())
+        {
+            return false;
+        }
+        if self.state.is_some_and(|s| s != device.device_state()) {
+            return false;
+        }
+        if let Some(pattern) = &self.name {
+            if !glob_match(pattern, &device.get_name()) && !glob_match(pattern, &device.get_id()) {
+                return false;
+            }
+        }
+
+        let readings = device.get_readings();
+        self.readings.iter().all(|filter| {
+            readings
+                .get(&filter.reading)
+                .is_some_and(|value| filter.op.check(*value, filter.value))
+        })
+    }
+}
+
+/// сопоставление строки с шаблоном из '*' и '?'
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // позиция последней '*' в шаблоне и место в тексте, с которого она сопоставлена
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod test {
+    use super::glob_match;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("Smart Socket *", "Smart Socket 4"));
+        assert!(glob_match("*socket*", "big-socket-1"));
+        assert!(glob_match("kettle-?", "kettle-2"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("kettle-?", "kettle-12"));
+        assert!(!glob_match("Smart *", "Thermometer 1"));
+    }
+}
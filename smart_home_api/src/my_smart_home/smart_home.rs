@@ -1,20 +1,28 @@
 use super::error::{SmartHomeError, SmartHomeResult};
 use super::home::Home;
+use super::location::{is_valid_location, is_within, parent_location, relocate};
+use super::query::{DeviceMatch, DeviceQuery};
 use crate::info_provider::provider::{DeviceInfoProvider, IterableProvider};
-use crate::smart_device::device::{SmartDevice, VecOfDevice};
+use crate::smart_device::device::{is_valid_device_id, DeviceState, SmartDevice, VecOfDevice};
+use crate::smart_device::fault::{Fault, FaultedDevice};
+use log::{debug, trace};
 use std::collections::{HashMap, HashSet};
 
 pub trait SmartHome {
     fn room(&self, name: &str) -> SmartHomeResult<()>;
     fn device(&self, room: &str, name: &str) -> SmartHomeResult<&dyn SmartDevice>;
-    fn mut_device(&mut self, room: &str, name: &str) -> SmartHomeResult<&mut dyn SmartDevice>;
     fn get_device(&self, room: &str, name: &str) -> SmartHomeResult<&dyn SmartDevice>;
     fn add_room(&mut self, name: String, devices: VecOfDevice) -> SmartHomeResult<()>;
     fn del_room(&mut self, name: &str) -> SmartHomeResult<()>;
+    fn rename_room(&mut self, name: &str, new_name: &str) -> SmartHomeResult<()>;
     fn get_rooms(&self) -> HashSet<String>;
     fn get_devices(&self, room: &str) -> SmartHomeResult<HashSet<String>>;
     fn add_device(&mut self, room: &str, device: Box<dyn SmartDevice>) -> SmartHomeResult<()>;
     fn del_device(&mut self, room: &str, device: &str) -> SmartHomeResult<()>;
+    fn rename_device(&mut self, room: &str, device: &str, new_name: &str) -> SmartHomeResult<()>;
+    fn move_device(&mut self, room: &str, device: &str, to: &str) -> SmartHomeResult<()>;
+    fn faulted_devices(&self) -> Vec<FaultedDevice>;
+    fn query_devices(&self, query: &DeviceQuery) -> Vec<DeviceMatch>;
     fn create_report(&self) -> String;
     fn create_provider_report(
         &self,
@@ -32,19 +40,8 @@ impl SmartHome for Home {
 
     fn device(&self, room: &str, name: &str) -> SmartHomeResult<&dyn SmartDevice> {
         self.room(room)?;
-        match self.rooms.get(room).unwrap().get(name) {
-            Some(device) => Ok(&**device),
-            None => Err(SmartHomeError::NoDeviceInRoom {
-                name: name.to_string(),
-                room: room.to_string(),
-            }),
-        }
-    }
-
-    fn mut_device(&mut self, room: &str, name: &str) -> SmartHomeResult<&mut dyn SmartDevice> {
-        self.room(room)?;
-        match self.rooms.get_mut(room).unwrap().get_mut(name) {
-            Some(device) => Ok(&mut **device),
+        match self.resolve_device(room, name) {
+            Some(id) => Ok(&*self.rooms[room][&id]),
             None => Err(SmartHomeError::NoDeviceInRoom {
                 name: name.to_string(),
                 room: room.to_string(),
@@ -57,35 +54,77 @@ impl SmartHome for Home {
         Ok(device)
     }
 
+    /// добавить комнату; вложенная комната добавляется в существующую: "floor-1/bathroom"
     fn add_room(&mut self, name: String, devices: VecOfDevice) -> SmartHomeResult<()> {
+        if !is_valid_location(&name) {
+            return Err(SmartHomeError::InvalidLocation(name));
+        }
         if self.room(&name).is_ok() {
             return Err(SmartHomeError::RoomSameNameExistInHome(self.name.clone()));
         }
+        if let Some(parent) = parent_location(&name) {
+            self.room(parent)?;
+        }
 
         self.rooms.insert(name.clone(), HashMap::new());
 
-        if !devices.is_empty() {
-            let mut room_devices: HashMap<String, Box<dyn SmartDevice>> = HashMap::new();
-
-            for d in devices {
-                let dev_name = d.get_name();
-                room_devices.insert(dev_name, d);
+        for d in devices {
+            if let Err(e) = self.add_device(&name, d) {
+                self.rooms.remove(&name);
+                return Err(e);
             }
-
-            self.rooms.insert(name, room_devices);
         }
 
         Ok(())
     }
 
     fn del_room(&mut self, name: &str) -> SmartHomeResult<()> {
-        match self.room(name) {
-            Ok(_) => {
-                self.rooms.remove(name);
-                Ok(())
-            }
-            Err(e) => Err(e),
+        self.room(name)?;
+        if self.rooms.keys().any(|r| r != name && is_within(r, name)) {
+            return Err(SmartHomeError::RoomHasNestedRooms(name.to_string()));
         }
+        self.rooms.remove(name);
+        Ok(())
+    }
+
+    /// переименовать комнату вместе с вложенными, новый путь может быть в другой локации
+    fn rename_room(&mut self, name: &str, new_name: &str) -> SmartHomeResult<()> {
+        self.room(name)?;
+        if name == new_name {
+            return Ok(());
+        }
+        if !is_valid_location(new_name) {
+            return Err(SmartHomeError::InvalidLocation(new_name.to_string()));
+        }
+        if self.room(new_name).is_ok() {
+            return Err(SmartHomeError::RoomSameNameExistInHome(self.name.clone()));
+        }
+        if is_within(new_name, name) {
+            return Err(SmartHomeError::RoomIntoItself {
+                room: name.to_string(),
+                to: new_name.to_string(),
+            });
+        }
+        if let Some(parent) = parent_location(new_name) {
+            self.room(parent)?;
+        }
+        let subtree: Vec<String> = self
+            .rooms
+            .keys()
+            .filter(|r| is_within(r, name))
+            .cloned()
+            .collect();
+        for room in subtree {
+            let devices = self.rooms.remove(&room).unwrap();
+            self.rooms.insert(relocate(&room, name, new_name), devices);
+        }
+        // ссылки на комнаты поддерева в группах, сценах, расписаниях, тревогах и правилах
+        self.groups.relocate_room(name, new_name);
+        self.scenes.relocate_room(name, new_name);
+        self.scheduler.relocate_room(name, new_name);
+        self.alarms.relocate_room(name, new_name);
+        self.rules.relocate_room(name, new_name);
+        Ok(())
     }
 
     fn get_rooms(&self) -> HashSet<String> {
@@ -99,28 +138,121 @@ impl SmartHome for Home {
 
     fn add_device(&mut self, room: &str, device: Box<dyn SmartDevice>) -> SmartHomeResult<()> {
         self.room(room)?;
-        let device_name = device.get_name();
-        match self.device(room, &device_name) {
-            Ok(_) => Err(SmartHomeError::DeviceSameNameExistInRoom(room.to_string())),
-            Err(_) => {
-                self.rooms
-                    .get_mut(room)
-                    .unwrap()
-                    .insert(device_name, device);
-                Ok(())
-            }
+        let device_id = device.get_id();
+        if !is_valid_device_id(&device_id) {
+            return Err(SmartHomeError::InvalidDeviceId(device_id));
+        }
+        if self.device(room, &device.get_name()).is_ok() {
+            return Err(SmartHomeError::DeviceSameNameExistInRoom(room.to_string()));
         }
+        if self.device_room(&device_id).is_some() {
+            return Err(SmartHomeError::DeviceSameIdExistInHome(device_id));
+        }
+        self.rooms.get_mut(room).unwrap().insert(device_id, device);
+        Ok(())
     }
 
     fn del_device(&mut self, room: &str, device: &str) -> SmartHomeResult<()> {
         self.room(room)?;
-        match self.device(room, device) {
-            Ok(_) => {
-                self.rooms.get_mut(room).unwrap().remove(device);
+        match self.resolve_device(room, device) {
+            Some(id) => {
+                self.rooms.get_mut(room).unwrap().remove(&id);
                 Ok(())
             }
-            Err(e) => Err(e),
+            None => Err(SmartHomeError::NoDeviceInRoom {
+                name: device.to_string(),
+                room: room.to_string(),
+            }),
+        }
+    }
+
+    fn rename_device(&mut self, room: &str, device: &str, new_name: &str) -> SmartHomeResult<()> {
+        let id = self.device(room, device)?.get_id();
+        match self.resolve_device(room, new_name) {
+            Some(other) if other != id => {
+                Err(SmartHomeError::DeviceSameNameExistInRoom(room.to_string()))
+            }
+            _ => match self.with_device(room, &id, |dev| {
+                dev.set_name(new_name);
+                dev.get_name() == new_name
+            })? {
+                true => Ok(()),
+                false => Err(SmartHomeError::DeviceRenameUnsupported(id)),
+            },
+        }
+    }
+
+    /// перенести устройство в другую комнату, сохранив его состояние
+    fn move_device(&mut self, room: &str, device: &str, to: &str) -> SmartHomeResult<()> {
+        let (id, name) = {
+            let dev = self.device(room, device)?;
+            (dev.get_id(), dev.get_name())
+        };
+        self.room(to)?;
+        if room == to {
+            return Err(SmartHomeError::DeviceAlreadyInRoom {
+                name,
+                room: to.to_string(),
+            });
+        }
+        if self.resolve_device(to, &name).is_some() || self.resolve_device(to, &id).is_some() {
+            return Err(SmartHomeError::DeviceSameNameExistInRoom(to.to_string()));
+        }
+        let dev = self.rooms.get_mut(room).unwrap().remove(&id).unwrap();
+        self.rooms.get_mut(to).unwrap().insert(id.clone(), dev);
+        self.groups.move_device(room, &id, to);
+        let names = [id.as_str(), name.as_str()];
+        self.scenes.move_device(room, &names, to);
+        self.scheduler.move_device(room, &names, to);
+        self.alarms.move_device(room, &names, to);
+        self.rules.move_device(room, &names, to);
+        Ok(())
+    }
+
+    /// неисправные устройства во всех комнатах дома
+    fn faulted_devices(&self) -> Vec<FaultedDevice> {
+        let mut faulted = vec![];
+        for (room, devices) in self.rooms.iter() {
+            for (id, device) in devices.iter() {
+                let fault = match (device.fault(), device.device_state()) {
+                    (Some(fault), _) => fault,
+                    (None, DeviceState::Broken) => Fault::manual(),
+                    (None, _) => continue,
+                };
+                faulted.push(FaultedDevice {
+                    room: room.clone(),
+                    device: id.clone(),
+                    name: device.get_name(),
+                    fault,
+                });
+            }
         }
+        faulted.sort_by(|a, b| (&a.room, &a.device).cmp(&(&b.room, &b.device)));
+        faulted
+    }
+
+    /// устройства дома, удовлетворяющие фильтру, по комнатам и идентификаторам
+    fn query_devices(&self, query: &DeviceQuery) -> Vec<DeviceMatch> {
+        let mut found = vec![];
+        for (room, devices) in self.rooms.iter() {
+            for (id, device) in devices.iter() {
+                if !query.matches(room, &**device) {
+                    continue;
+                }
+                found.push(DeviceMatch {
+                    path: Home::device_path(room, id),
+                    room: room.clone(),
+                    device: id.clone(),
+                    name: device.get_name(),
+                    device_type: device.get_type().type_name().to_string(),
+                    state: device.device_state(),
+                    readings: device.get_readings(),
+                    fault: device.fault(),
+                });
+            }
+        }
+        found.sort_by(|a, b| (&a.room, &a.device).cmp(&(&b.room, &b.device)));
+        found
     }
 
     /// отчет о состоянии всех устройств в доме
@@ -146,32 +278,34 @@ impl SmartHome for Home {
     ) -> String {
         let mut report: String = String::from("");
         let mut provider_devices = info_provider.as_set().clone();
-        println!("{:?}", provider_devices);
+        debug!(devices:? = provider_devices; "provider report");
 
         for room in self.get_rooms() {
             report += "\n";
             report += room.as_str();
 
-            let room_devices = self.get_devices(room.as_str()).unwrap();
-
-            for device in room_devices.iter() {
-                let dev_id = Self::device_path(&room, device);
-                println!("{}", dev_id);
-                if provider_devices.contains(&dev_id) {
+            for (id, device) in self.rooms[&room].iter() {
+                // провайдер может ссылаться на устройство по идентификатору или по имени
+                let by_id = (room.clone(), id.clone());
+                let by_name = (room.clone(), device.get_name());
+                trace!(device = Self::device_path(&room, id).as_str(); "provider report device");
+                if provider_devices.contains(&by_id) || provider_devices.contains(&by_name) {
                     report += "\n";
                     let part = info_provider
-                        .get_device_info(self, &room, device)
+                        .get_device_info(self, &room, id)
                         .unwrap_or_else(|e| e.to_string());
                     report += format!("--> {}\n", part).as_str();
                 }
-                provider_devices.remove(&dev_id);
+                provider_devices.remove(&by_id);
+                provider_devices.remove(&by_name);
             }
         }
         report += "\n\n";
         report += "==============\n\n";
 
-        for device in provider_devices.iter() {
-            report += format!("\x1b[41m{}\x1b[0m не найдено\n", device).as_str();
+        for (room, device) in provider_devices.iter() {
+            let path = Self::device_path(room, device);
+            report += format!("\x1b[41m{}\x1b[0m не найдено\n", path).as_str();
         }
         report
     }
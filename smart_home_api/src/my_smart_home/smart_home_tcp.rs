@@ -1,184 +1,1888 @@
 #![allow(unused_assignments)]
 
+use crate::access::policy::{AccessPolicy, RequestContext};
+use crate::access::role::{required_role, Role};
+use crate::audit::entry::{AuditEntry, AuditQuery};
+use crate::events::event::HomeEvent;
+use crate::gateway::limit::spawn_bounded;
+use crate::gateway::server::HttpGateway;
+use crate::gateway::ws::WsGateway;
 use crate::info_provider::json_provider::JsonDeviceInfoProvider;
 use crate::json_rpc::error::{
-    api_error, internal_error, invalid_method, invalid_param, invalid_request, parse_error,
-    unhandled_error,
+    access_denied, api_error, internal_error, invalid_method, invalid_param, invalid_request,
+    parse_error, unhandled_error,
 };
 use crate::json_rpc::reply::{reply, reply_error, JsonRpcReplyMsg};
+use crate::metrics;
+use crate::metrics::http::MetricsListener;
+use crate::my_smart_home::error::SmartHomeError;
 use crate::my_smart_home::home::Home;
+use crate::my_smart_home::query::DeviceQuery;
 use crate::my_smart_home::smart_home::SmartHome;
+use chrono::{NaiveDateTime, TimeDelta};
+use jsonschema::Validator;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
 use serde_json as json;
 
 use std::path::PathBuf;
-use stp::server::StpServer;
+use std::sync::atomic::AtomicUsize;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{io, thread};
+use stp::auth::{AuthConfig, Principal};
+use stp::error::ConnectError;
+use stp::server::{Handshake, StpServer};
+#[cfg(feature = "tls")]
+use stp::tls::TlsServerConfig;
 
+use crate::alarms::alarm::AlarmDefinition;
+use crate::automation::rule::Rule;
+use crate::command::device_command::{CommandError, DeviceCommand};
 use crate::command::queue::RPCQueue;
+use crate::energy::report::EnergyPeriod;
+use crate::groups::group::{Group, GroupMember};
 use crate::json_rpc::request::JsonRpcRequest;
 use crate::json_rpc::utils::{get_validator, unquoted};
+use crate::scenes::scene::Scene;
+use crate::scheduler::schedule::Schedule;
 use crate::DEFAULT_TCP_SOCKET;
 
-pub trait SmartHomePublicApi {
-    /// запустить цикл обслуживания клиентов
-    fn serve_public(&mut self) -> anyhow::Result<String>;
-    /// выполнить RPC-запрос клиента
-    fn execute(&mut self, requests: &mut RPCQueue<JsonRpcRequest>) -> String;
+/// пауза между тиками дома, когда нет входящих соединений
+const IDLE_TICK: Duration = Duration::from_millis(100);
+/// сколько STP-соединений одновременно проходят аутентификацию и обмен запросом
+const MAX_PENDING: usize = 16;
+
+/// Настройки сервера: аутентификация клиентов и их права.
+/// По умолчанию сервер закрыт: без учетных данных или `open` к нему не подключиться.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ServerConfig {
+    #[serde(flatten)]
+    pub auth: AuthConfig,
+    #[serde(flatten)]
+    pub access: AccessPolicy,
+    /// адрес HTTP-шлюза: ресурсы REST и JSON-RPC через POST /rpc; без него шлюз выключен
+    #[serde(default)]
+    pub http: Option<String>,
+    /// адрес WebSocket: JSON-RPC и уведомления о событиях; без него WebSocket выключен
+    #[serde(default)]
+    pub ws: Option<String>,
+    /// адрес HTTP для метрик Prometheus; без него метрики доступны только через getMetrics
+    #[serde(default)]
+    pub metrics: Option<String>,
+    /// Unix-сокет для локальных клиентов; без него сервер слушает только TCP
+    #[cfg(unix)]
+    #[serde(default)]
+    pub unix: Option<UnixSocketConfig>,
+    /// сертификат сервера; без него соединения не шифруются
+    #[cfg(feature = "tls")]
+    #[serde(default)]
+    pub tls: Option<TlsServerConfig>,
 }
 
-impl SmartHomePublicApi for Home {
-    fn serve_public(&mut self) -> anyhow::Result<String> {
-        let stp = StpServer::bind(DEFAULT_TCP_SOCKET)?;
-        let mut requests = RPCQueue::<JsonRpcRequest>::default();
+/// Unix-сокет сервера: путь и права на файл сокета в восьмеричной записи.
+#[cfg(unix)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UnixSocketConfig {
+    pub path: PathBuf,
+    /// "600" - только владелец сервера, "660" - еще и его группа
+    #[serde(default = "UnixSocketConfig::default_mode")]
+    pub mode: String,
+}
+
+#[cfg(unix)]
+impl UnixSocketConfig {
+    fn default_mode() -> String {
+        "600".into()
+    }
+
+    /// права на файл сокета, не больше 777
+    pub fn mode(&self) -> anyhow::Result<u32> {
+        match u32::from_str_radix(&self.mode, 8) {
+            Ok(mode) if mode <= 0o777 => Ok(mode),
+            _ => Err(anyhow::anyhow!("invalid unix socket mode: {:?}", self.mode)),
+        }
+    }
+}
+
+pub trait SmartHomePublicApi {
+    /// выполнить RPC-запрос клиента с правами из контекста
+    fn execute(&mut self, requests: &mut RPCQueue<JsonRpcRequest>, ctx: &RequestContext) -> String;
+
+    /// периодическая обработка между запросами клиентов
+    fn idle(&mut self);
+
+    /// метрики сервера и устройств в текстовом формате Prometheus
+    fn metrics(&self) -> String;
+
+    /// пересылать события домов в канал вместе с именем дома
+    fn forward_events(&mut self, tx: Sender<(String, HomeEvent)>);
+
+    /// запустить цикл обслуживания клиентов
+    fn serve_public(&mut self, config: ServerConfig) -> anyhow::Result<String> {
+        let mut stp = StpServer::bind(DEFAULT_TCP_SOCKET)?;
+        stp.set_auth(config.auth.clone());
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &config.tls {
+            stp.set_tls(tls)?;
+        }
         let validator = get_validator(PathBuf::from("./smart_home_api/public_api.json"))?;
+        let metrics_listener = match &config.metrics {
+            Some(addr) => Some(MetricsListener::bind(addr)?),
+            None => None,
+        };
+        let gateway = match &config.http {
+            Some(addr) => Some(HttpGateway::bind(addr)?),
+            None => None,
+        };
+        let mut ws = match &config.ws {
+            Some(addr) => {
+                let (tx, rx) = channel();
+                self.forward_events(tx);
+                Some(WsGateway::bind(addr, rx)?)
+            }
+            None => None,
+        };
+
+        // доступ к Unix-сокету ограничивают права на его файл, учетные данные не нужны
+        #[cfg(unix)]
+        let unix = match &config.unix {
+            Some(unix) => Some(StpServer::bind_unix(&unix.path, unix.mode()?)?),
+            None => None,
+        };
+        #[cfg(not(unix))]
+        let unix = None;
+        let servers: Vec<StpServer> = std::iter::once(stp).chain(unix).collect();
+        for stp in &servers {
+            stp.set_nonblocking(true)?;
+        }
+        let (sender, calls) = channel::<StpCall>();
+        let pending = Arc::new(AtomicUsize::new(0));
 
         loop {
-            let Ok(mut connection) = stp.accept() else {
-                continue;
-            };
-
-            let addr = match connection.peer_addr() {
-                Ok(addr) => addr.to_string(),
-                Err(_) => "unknown".into(),
-            };
-
-            println!("request received from: {:?}", addr);
-
-            connection.process_request(|req| {
-                let binding = json::from_str(&req);
-                let data = match &binding {
-                    Ok(jsondata) => jsondata,
-                    Err(e) => return json::to_string(&parse_error(e.to_string())).unwrap(),
-                };
-                println!("{}", data);
-                match validator.is_valid(data) {
-                    true => {
-                        let batch = json::from_value(data.to_owned()).unwrap();
-                        requests.push(batch);
-                        self.execute(&mut requests) // collect butch of result
-                    }
-
-                    false => {
-                        let mut errors: Vec<String> = Vec::new();
-                        for e in validator.iter_errors(data) {
-                            let er = format!("Error: {}\n\n Location: {}\n\n", e, e.instance_path);
-                            errors.push(er);
-                        }
-                        json::to_string(&invalid_request(errors.join(";"))).unwrap()
+            self.idle();
+            if let Some(listener) = &metrics_listener {
+                listener.poll(|| self.metrics());
+            }
+            if let Some(gateway) = &gateway {
+                gateway.poll(self, &config, &validator);
+            }
+            if let Some(ws) = &mut ws {
+                ws.poll(self, &config, &validator);
+            }
+
+            let mut accepted = false;
+            for stp in &servers {
+                let handshake = match stp.accept_handshake() {
+                    Ok(handshake) => handshake,
+                    Err(ConnectError::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                    Err(e) => {
+                        debug!(error:% = e; "connection failed");
+                        metrics::registry::global().handshake_failed(handshake_failure(&e));
+                        continue;
                     }
+                };
+                accepted = true;
+
+                // проверка пароля и обмен с медленным клиентом не задерживают цикл
+                let sender = sender.clone();
+                let job = move || serve_connection(handshake, sender);
+                if !spawn_bounded(&pending, MAX_PENDING, job) {
+                    warn!("connection dropped: too many pending connections");
                 }
-            })?; // emit only tcp level errors
+            }
+            while let Ok(call) = calls.try_recv() {
+                accepted = true;
+                let ctx = config.access.context(&call.principal, &call.peer);
+                // соединение могло закрыться, пока ждало ответа
+                let _ = call
+                    .reply
+                    .send(execute_batch(self, &validator, &call.request, &ctx));
+            }
+            if !accepted {
+                thread::sleep(IDLE_TICK);
+            }
         }
     }
+}
 
-    fn execute(&mut self, requests: &mut RPCQueue<JsonRpcRequest>) -> String {
+impl SmartHomePublicApi for Home {
+    fn idle(&mut self) {
+        self.tick();
+    }
+
+    fn metrics(&self) -> String {
+        metrics::registry::global().render(&self.device_gauges())
+    }
+
+    fn forward_events(&mut self, tx: Sender<(String, HomeEvent)>) {
+        let name = self.name.clone();
+        self.events.subscribe(move |event| {
+            let _ = tx.send((name.clone(), event.clone()));
+        });
+    }
+
+    fn execute(&mut self, requests: &mut RPCQueue<JsonRpcRequest>, ctx: &RequestContext) -> String {
         let mut replies: Vec<Box<dyn JsonRpcReplyMsg>> = vec![];
 
         while let Some(rpc_cmd) = requests.pop() {
-            let mut error_code: i32 = 0; // 1 - SmartHome Api Errors
+            replies.push(self.handle_request(rpc_cmd, requests, ctx));
+        }
 
-            let resp = match rpc_cmd.method.as_str() {
-                "addRoom" => {
-                    let room_name = unquoted(&rpc_cmd.params["name"]);
-                    match self.add_room(room_name, vec![]) {
-                        Ok(()) => "addRoom: success".to_string(),
-                        Err(e) => {
-                            error_code = 1;
-                            format!("addRoom error: {e}")
+        json::to_string_pretty(&replies).unwrap()
+    }
+}
+
+impl Home {
+    /// проверить права клиента, выполнить запрос и записать его в журнал аудита
+    pub(crate) fn handle_request(
+        &mut self,
+        rpc_cmd: JsonRpcRequest,
+        requests: &mut RPCQueue<JsonRpcRequest>,
+        ctx: &RequestContext,
+    ) -> Box<dyn JsonRpcReplyMsg> {
+        log_request(&rpc_cmd, ctx);
+        let started = Instant::now();
+        let method = rpc_cmd.method.clone();
+        let entry = self.audit_entry(&rpc_cmd, ctx, Some(&self.name));
+        let reply = match deny(&rpc_cmd, ctx, Some(&self.name)) {
+            Some(denied) => denied,
+            None => self.execute_request(rpc_cmd, requests),
+        };
+        observe_request(&method, started, &*reply);
+        if let Some(entry) = entry {
+            self.record_audit(entry.finish(&*reply));
+        }
+        reply
+    }
+
+    /// запись аудита для запросов, требующих роли выше viewer, и для запрещенных запросов;
+    /// `home` - дом запроса, None - запрос к хабу
+    pub(crate) fn audit_entry(
+        &self,
+        rpc_cmd: &JsonRpcRequest,
+        ctx: &RequestContext,
+        home: Option<&str>,
+    ) -> Option<AuditEntry> {
+        if required_role(rpc_cmd) == Role::Viewer && ctx.authorize(rpc_cmd, home).is_ok() {
+            return None;
+        }
+        let device = match (
+            rpc_cmd.params["room"].as_str(),
+            rpc_cmd.params["device"].as_str(),
+        ) {
+            (Some(room), Some(device)) => self.resolve_device(room, device),
+            _ => None,
+        };
+        Some(AuditEntry::new(self.clock.now(), ctx, rpc_cmd, device))
+    }
+
+    pub(crate) fn record_audit(&mut self, entry: AuditEntry) {
+        if let Err(e) = self.audit.append(&entry) {
+            error!(home = self.name.as_str(), error:% = e; "audit log write failed");
+        }
+    }
+
+    /// выполнить один запрос из пакета; `requests` - оставшиеся запросы пакета
+    pub(crate) fn execute_request(
+        &mut self,
+        rpc_cmd: JsonRpcRequest,
+        requests: &mut RPCQueue<JsonRpcRequest>,
+    ) -> Box<dyn JsonRpcReplyMsg> {
+        let mut error_code: i32 = 0; // 1 - SmartHome Api Errors
+        let mut result: Option<json::Value> = None; // структурированный результат вместо строки
+
+        let resp = match rpc_cmd.method.as_str() {
+            "addRoom" => {
+                let room_name = unquoted(&rpc_cmd.params["name"]);
+                match self.add_room(room_name, vec![]) {
+                    Ok(()) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("addRoom error: {e}")
+                    }
+                }
+            }
+            "delRoom" => {
+                let room_name = unquoted(&rpc_cmd.params["name"]);
+                match self.del_room(&room_name) {
+                    Ok(()) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("delRoom error: {e}")
+                    }
+                }
+            }
+
+            "getDevices" => {
+                let room_name = unquoted(&rpc_cmd.params["room"]);
+                match self.get_devices(&room_name) {
+                    Ok(res) => res
+                        .iter()
+                        .map(|id| format!("{id}: {}", self.rooms[&room_name][id].get_name()))
+                        .collect::<Vec<_>>()
+                        .join(";"),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("error: {e}")
+                    }
+                }
+            }
+
+            "getDeviceTypes" => self.registry.types().join(";"),
+
+            "addDevice" => {
+                let room = unquoted(&rpc_cmd.params["room"]);
+                let dev_type = unquoted(&rpc_cmd.params["type"]);
+                let params = rpc_cmd.params.get("params").cloned().unwrap_or_default();
+                let added = self
+                    .registry
+                    .create(&dev_type, &params)
+                    .and_then(|device| self.add_device(&room, device));
+                match added {
+                    Ok(()) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("addDevice error: {e}")
+                    }
+                }
+            }
+
+            "delDevice" => {
+                let room = unquoted(&rpc_cmd.params["room"]);
+                let device = unquoted(&rpc_cmd.params["device"]);
+                match self.del_device(&room, &device) {
+                    Ok(()) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("delDevice error: {e}")
+                    }
+                }
+            }
+
+            "renameRoom" => {
+                let room_name = unquoted(&rpc_cmd.params["name"]);
+                let new_name = unquoted(&rpc_cmd.params["new_name"]);
+                match self.rename_room(&room_name, &new_name) {
+                    Ok(()) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("renameRoom error: {e}")
+                    }
+                }
+            }
+
+            "renameDevice" => {
+                let room = unquoted(&rpc_cmd.params["room"]);
+                let device = unquoted(&rpc_cmd.params["device"]);
+                let new_name = unquoted(&rpc_cmd.params["new_name"]);
+                match self.rename_device(&room, &device, &new_name) {
+                    Ok(()) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("renameDevice error: {e}")
+                    }
+                }
+            }
+
+            "moveDevice" => {
+                let room = unquoted(&rpc_cmd.params["room"]);
+                let device = unquoted(&rpc_cmd.params["device"]);
+                let to = unquoted(&rpc_cmd.params["to"]);
+                match self.move_device(&room, &device, &to) {
+                    Ok(()) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("moveDevice error: {e}")
+                    }
+                }
+            }
+
+            "listRules" => {
+                result = Some(json::json!(self.rules.rules()));
+                String::new()
+            }
+
+            "addRule" => match json::from_value::<Rule>(rpc_cmd.params["rule"].clone()) {
+                Ok(rule) => match self.rules.add(rule) {
+                    Ok(()) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e @ SmartHomeError::InvalidRule { .. }) => {
+                        error_code = -32602;
+                        format!("addRule error: {e}")
+                    }
+                    Err(e) => {
+                        error_code = 1;
+                        format!("addRule error: {e}")
+                    }
+                },
+                Err(e) => {
+                    error_code = -32602;
+                    format!("addRule error: {e}")
+                }
+            },
+
+            "enableRule" | "disableRule" => {
+                let id = unquoted(&rpc_cmd.params["id"]);
+                let enabled = rpc_cmd.method == "enableRule";
+                match self.rules.set_enabled(&id, enabled) {
+                    Ok(()) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("{} error: {e}", rpc_cmd.method)
+                    }
+                }
+            }
+
+            "delRule" => {
+                let id = unquoted(&rpc_cmd.params["id"]);
+                match self.rules.remove(&id) {
+                    Ok(_) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("delRule error: {e}")
+                    }
+                }
+            }
+
+            "listSchedules" => {
+                result = Some(json::json!(self.scheduler.schedules()));
+                String::new()
+            }
+
+            "addSchedule" => {
+                match json::from_value::<Schedule>(rpc_cmd.params["schedule"].clone()) {
+                    Ok(schedule) => {
+                        let now = self.clock.now();
+                        match self.scheduler.add(schedule, now) {
+                            Ok(()) => persisted(self, &rpc_cmd.method, &mut error_code),
+                            Err(e) => {
+                                error_code = 1;
+                                format!("addSchedule error: {e}")
+                            }
                         }
                     }
+                    Err(e) => {
+                        error_code = -32602;
+                        format!("addSchedule error: {e}")
+                    }
+                }
+            }
+
+            "enableSchedule" | "disableSchedule" => {
+                let id = unquoted(&rpc_cmd.params["id"]);
+                let enabled = rpc_cmd.method == "enableSchedule";
+                match self.scheduler.set_enabled(&id, enabled) {
+                    Ok(()) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("{} error: {e}", rpc_cmd.method)
+                    }
+                }
+            }
+
+            "delSchedule" => {
+                let id = unquoted(&rpc_cmd.params["id"]);
+                match self.scheduler.remove(&id) {
+                    Ok(_) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("delSchedule error: {e}")
+                    }
+                }
+            }
+
+            "listScenes" => {
+                result = Some(json::json!(self.scenes.scenes()));
+                String::new()
+            }
+
+            "addScene" => match json::from_value::<Scene>(rpc_cmd.params["scene"].clone()) {
+                Ok(scene) => match self.scenes.add(scene) {
+                    Ok(()) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("addScene error: {e}")
+                    }
+                },
+                Err(e) => {
+                    error_code = -32602;
+                    format!("addScene error: {e}")
+                }
+            },
+
+            "delScene" => {
+                let id = unquoted(&rpc_cmd.params["id"]);
+                match self.scenes.remove(&id) {
+                    Ok(_) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("delScene error: {e}")
+                    }
+                }
+            }
+
+            "applyScene" => {
+                let id = unquoted(&rpc_cmd.params["id"]);
+                match self.apply_scene(&id) {
+                    Ok(outcomes) => {
+                        result = Some(json::json!(outcomes));
+                        String::new()
+                    }
+                    Err(e) => {
+                        error_code = 1;
+                        format!("applyScene error: {e}")
+                    }
                 }
-                "delRoom" => {
-                    let room_name = unquoted(&rpc_cmd.params["name"]);
-                    match self.del_room(&room_name) {
-                        Ok(()) => "delRoom: success".to_string(),
+            }
+
+            "snapshotScene" => {
+                let id = unquoted(&rpc_cmd.params["id"]);
+                let name = rpc_cmd.params["name"].as_str().unwrap_or_default();
+                let rooms: Vec<String> =
+                    json::from_value(rpc_cmd.params["rooms"].clone()).unwrap_or_default();
+                match self
+                    .snapshot_scene(&id, name, &rooms)
+                    .and_then(|scene| self.scenes.add(scene.clone()).map(|_| scene))
+                {
+                    Ok(scene) => match self.persist() {
+                        Ok(()) => {
+                            result = Some(json::json!(scene));
+                            String::new()
+                        }
                         Err(e) => {
                             error_code = 1;
-                            format!("delRoom error: {e}")
+                            format!("snapshotScene error: {e}")
                         }
+                    },
+                    Err(e) => {
+                        error_code = 1;
+                        format!("snapshotScene error: {e}")
+                    }
+                }
+            }
+
+            "listGroups" => {
+                result = Some(json::json!(self.groups.groups()));
+                String::new()
+            }
+
+            "addGroup" => match json::from_value::<Group>(rpc_cmd.params["group"].clone()) {
+                Ok(group) => match self.add_group(group) {
+                    Ok(()) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("addGroup error: {e}")
+                    }
+                },
+                Err(e) => {
+                    error_code = -32602;
+                    format!("addGroup error: {e}")
+                }
+            },
+
+            "delGroup" => {
+                let id = unquoted(&rpc_cmd.params["id"]);
+                match self.groups.remove(&id) {
+                    Ok(_) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("delGroup error: {e}")
                     }
                 }
+            }
 
-                "getDevices" => {
-                    let room_name = unquoted(&rpc_cmd.params["room"]);
-                    match self.get_devices(&room_name) {
-                        Ok(res) => res
-                            .iter()
-                            .map(|x| x.to_string())
-                            .collect::<Vec<_>>()
-                            .join(";"),
+            "addGroupMember" | "delGroupMember" => {
+                let id = unquoted(&rpc_cmd.params["id"]);
+                let member = GroupMember {
+                    room: unquoted(&rpc_cmd.params["room"]),
+                    device: unquoted(&rpc_cmd.params["device"]),
+                };
+                let res = match rpc_cmd.method.as_str() {
+                    "addGroupMember" => self.add_group_member(&id, member),
+                    _ => self.remove_group_member(&id, member),
+                };
+                match res {
+                    Ok(()) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("{} error: {e}", rpc_cmd.method)
+                    }
+                }
+            }
+
+            "groupExecute" => {
+                let id = unquoted(&rpc_cmd.params["id"]);
+                let command = unquoted(&rpc_cmd.params["command"]);
+                let data: Vec<String> =
+                    json::from_value(rpc_cmd.params["data"].clone()).unwrap_or_default();
+                match self.group_execute(&id, &command, &data) {
+                    Ok(outcome) => {
+                        result = Some(json::json!(outcome));
+                        String::new()
+                    }
+                    Err(e) => {
+                        error_code = 1;
+                        format!("groupExecute error: {e}")
+                    }
+                }
+            }
+
+            "groupReport" => {
+                let id = unquoted(&rpc_cmd.params["id"]);
+                match self.group_report(&id) {
+                    Ok(report) => report,
+                    Err(e) => {
+                        error_code = 1;
+                        format!("groupReport error: {e}")
+                    }
+                }
+            }
+
+            "listAlarms" => {
+                result = Some(json::json!(self.alarms.definitions()));
+                String::new()
+            }
+
+            "addAlarm" => {
+                match json::from_value::<AlarmDefinition>(rpc_cmd.params["alarm"].clone()) {
+                    Ok(alarm) => match self.alarms.add(alarm) {
+                        Ok(()) => persisted(self, &rpc_cmd.method, &mut error_code),
+                        Err(e @ SmartHomeError::InvalidAlarm { .. }) => {
+                            error_code = -32602;
+                            format!("addAlarm error: {e}")
+                        }
                         Err(e) => {
                             error_code = 1;
-                            format!("error: {e}")
+                            format!("addAlarm error: {e}")
                         }
+                    },
+                    Err(e) => {
+                        error_code = -32602;
+                        format!("addAlarm error: {e}")
                     }
                 }
-                "createReport" => self.create_report(),
+            }
 
-                "createProviderReport" => {
-                    let schema = rpc_cmd.params.clone()["provider"].clone();
-                    match JsonDeviceInfoProvider::from_json(schema) {
-                        Ok(provider) => self.create_provider_report(&provider),
-                        Err(e) => {
-                            error_code = -32602;
-                            format!("error: {e}")
+            "enableAlarm" | "disableAlarm" => {
+                let id = unquoted(&rpc_cmd.params["id"]);
+                let enabled = rpc_cmd.method == "enableAlarm";
+                match self.alarms.set_enabled(&id, enabled) {
+                    Ok(()) => persisted(self, &rpc_cmd.method, &mut error_code),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("{} error: {e}", rpc_cmd.method)
+                    }
+                }
+            }
+
+            "delAlarm" => {
+                let id = unquoted(&rpc_cmd.params["id"]);
+                match self.remove_alarm(&id) {
+                    Ok(()) => format!("{}: success", rpc_cmd.method),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("delAlarm error: {e}")
+                    }
+                }
+            }
+
+            "getAlarms" => {
+                let open_only = rpc_cmd.params["open"].as_bool().unwrap_or_default();
+                result = Some(json::json!(self.alarms.records(open_only)));
+                String::new()
+            }
+
+            "ackAlarm" => {
+                let seq = rpc_cmd.params["seq"].as_u64().unwrap_or_default();
+                match self.acknowledge_alarm(seq) {
+                    Ok(()) => "ackAlarm: success".to_string(),
+                    Err(e) => {
+                        error_code = 1;
+                        format!("ackAlarm error: {e}")
+                    }
+                }
+            }
+
+            "createReport" => match rpc_cmd.params["location"].as_str() {
+                None => self.create_report(),
+                Some(location) => match self.location_report(location) {
+                    Ok(report) => report,
+                    Err(e) => {
+                        error_code = 1;
+                        format!("createReport error: {e}")
+                    }
+                },
+            },
+
+            "getLocations" => match self.locations(rpc_cmd.params["location"].as_str()) {
+                Ok(tree) => {
+                    result = Some(json::json!(tree));
+                    String::new()
+                }
+                Err(e) => {
+                    error_code = 1;
+                    format!("getLocations error: {e}")
+                }
+            },
+
+            "locationExecute" => {
+                let location = unquoted(&rpc_cmd.params["location"]);
+                let command = unquoted(&rpc_cmd.params["command"]);
+                let data: Vec<String> =
+                    json::from_value(rpc_cmd.params["data"].clone()).unwrap_or_default();
+                match self.location_execute(&location, &command, &data) {
+                    Ok(outcome) => {
+                        result = Some(json::json!(outcome));
+                        String::new()
+                    }
+                    Err(e) => {
+                        error_code = 1;
+                        format!("locationExecute error: {e}")
+                    }
+                }
+            }
+
+            "getAuditLog" => match json::from_value::<AuditQuery>(rpc_cmd.params.clone()) {
+                Ok(query) => match self.audit.query(&query) {
+                    Ok(entries) => {
+                        result = Some(json::json!(entries));
+                        String::new()
+                    }
+                    Err(e) => {
+                        error_code = -32603;
+                        format!("getAuditLog error: {e}")
+                    }
+                },
+                Err(e) => {
+                    error_code = -32602;
+                    format!("getAuditLog error: {e}")
+                }
+            },
+
+            "getMetrics" => self.metrics(),
+
+            "getFaults" => {
+                result = Some(json::json!(self.faulted_devices()));
+                String::new()
+            }
+
+            "queryDevices" => match json::from_value::<DeviceQuery>(rpc_cmd.params.clone()) {
+                Ok(query) => {
+                    result = Some(json::json!(self.query_devices(&query)));
+                    String::new()
+                }
+                Err(e) => {
+                    error_code = -32602;
+                    format!("queryDevices error: {e}")
+                }
+            },
+
+            "getHistory" => {
+                let room = unquoted(&rpc_cmd.params["room"]);
+                let device = unquoted(&rpc_cmd.params["device"]);
+                let range = json::from_value::<NaiveDateTime>(rpc_cmd.params["from"].clone())
+                    .and_then(|from| {
+                        json::from_value::<NaiveDateTime>(rpc_cmd.params["to"].clone())
+                            .map(|to| (from, to))
+                    });
+                let step = match &rpc_cmd.params["step"] {
+                    json::Value::Null => Ok(None),
+                    step => step
+                        .as_i64()
+                        .and_then(TimeDelta::try_seconds)
+                        .map(Some)
+                        .ok_or_else(|| format!("step out of range: {step}")),
+                };
+                let range = range
+                    .map_err(|e| e.to_string())
+                    .and_then(|range| step.map(|step| (range, step)));
+                match (range, self.device(&room, &device)) {
+                    (Err(e), _) => {
+                        error_code = -32602;
+                        format!("getHistory error: {e}")
+                    }
+                    (_, Err(e)) => {
+                        error_code = 1;
+                        format!("getHistory error: {e}")
+                    }
+                    (Ok(((from, to), step)), Ok(dev)) => {
+                        match self.history.query(&dev.get_id(), from, to, step) {
+                            Ok(points) => {
+                                result = Some(json::json!(points));
+                                String::new()
+                            }
+                            Err(e) => {
+                                error_code = 1;
+                                format!("getHistory error: {e}")
+                            }
                         }
                     }
                 }
+            }
 
-                "reset" => {
-                    requests.reset();
-                    "reset: success".to_string()
-                }
-
-                "deviceExecute" => {
-                    let room = rpc_cmd.params["room"].as_str().unwrap();
-                    let device = rpc_cmd.params["device"].as_str().unwrap();
-                    let command = rpc_cmd.params["command"].as_str().unwrap();
-                    let data = rpc_cmd.params.clone()["data"].clone();
-
-                    let res = match self.mut_device(room, device) {
-                        Ok(dev) => match command {
-                            "get_name" => dev.get_name(),
-                            "get_description" => dev.get_description(),
-                            "get_current_info" => dev.get_current_info(),
-                            "report" => dev.report(),
-                            "switch" => match data[0].as_str() {
-                                Some(state) => dev.switch(state),
-                                None => {
-                                    error_code = -32602;
-                                    format!("wrong status provided: {}", data.as_str().unwrap())
-                                }
-                            },
-                            _ => {
-                                error_code = -32601;
-                                "wrong device command".to_string()
+            "getEnergyReport" | "createEnergyReport" => {
+                match json::from_value::<EnergyPeriod>(rpc_cmd.params["period"].clone()) {
+                    Ok(period) => {
+                        let tariff = rpc_cmd.params["tariff"].as_f64();
+                        let report = self.energy_report(period, tariff);
+                        match rpc_cmd.method.as_str() {
+                            "getEnergyReport" => {
+                                result = Some(json::json!(report));
+                                String::new()
                             }
-                        },
-                        Err(e) => {
-                            error_code = 1;
-                            format!("error: {e}")
+                            _ => report.render(),
                         }
-                    };
-                    res
+                    }
+                    Err(e) => {
+                        error_code = -32602;
+                        format!("{} error: {e}", rpc_cmd.method)
+                    }
                 }
-                _ => {
-                    error_code = -32603; // UB :))
-                    "непредвиденный ответ на непредвиденный запрос :)".to_string()
+            }
+
+            "createProviderReport" => {
+                let schema = rpc_cmd.params.clone()["provider"].clone();
+                match JsonDeviceInfoProvider::from_json(schema) {
+                    Ok(provider) => self.create_provider_report(&provider),
+                    Err(e) => {
+                        error_code = -32602;
+                        format!("error: {e}")
+                    }
                 }
-            };
+            }
+
+            "reset" => {
+                requests.reset();
+                "reset: success".to_string()
+            }
+
+            "deviceExecute" => match json::from_value::<DeviceCommand>(rpc_cmd.params.clone()) {
+                Ok(cmd) => match self.execute_device_command(&cmd) {
+                    Ok(res) => res,
+                    Err(e) => {
+                        error_code = command_error_code(&e);
+                        match e {
+                            CommandError::Home(e) => format!("error: {e}"),
+                            e => e.to_string(),
+                        }
+                    }
+                },
+                Err(e) => {
+                    error_code = -32602;
+                    format!("error: {e}")
+                }
+            },
+            _ => {
+                error_code = -32603; // UB :))
+                "непредвиденный ответ на непредвиденный запрос :)".to_string()
+            }
+        };
+
+        pack_reply(rpc_cmd.id, error_code, resp, result)
+    }
+}
+
+/// упаковать ответ в JsonRPC: результат или ошибку по коду ошибки
+pub(crate) fn pack_reply(
+    id: String,
+    error_code: i32,
+    resp: String,
+    result: Option<json::Value>,
+) -> Box<dyn JsonRpcReplyMsg> {
+    match error_code {
+        0 => Box::new(reply(id, result.unwrap_or(resp.into()))),
+        1 => Box::new(reply_error(id, api_error(resp))),
+        -32601 => Box::new(reply_error(id, invalid_method(resp))),
+        -32602 => Box::new(reply_error(id, invalid_param(resp))),
+        -32603 => Box::new(reply_error(id, internal_error(resp))),
+        -32001 => Box::new(reply_error(id, access_denied(resp))),
+        _ => Box::new(reply_error(id, unhandled_error(resp))),
+    }
+}
+
+/// ответ с отказом, если клиенту не разрешен запрос
+pub(crate) fn deny(
+    rpc_cmd: &JsonRpcRequest,
+    ctx: &RequestContext,
+    home: Option<&str>,
+) -> Option<Box<dyn JsonRpcReplyMsg>> {
+    ctx.authorize(rpc_cmd, home).err().map(|reason| {
+        warn!(
+            id = rpc_cmd.id.as_str(),
+            peer = ctx.peer.as_str(),
+            user = ctx.principal.name(),
+            method = rpc_cmd.method.as_str(),
+            reason = reason.as_str();
+            "access denied"
+        );
+        pack_reply(rpc_cmd.id.clone(), -32001, reason, None)
+    })
+}
+
+/// разобрать пакет запросов клиента, проверить его по схеме и выполнить (collect butch of result)
+pub(crate) fn execute_batch<A: SmartHomePublicApi + ?Sized>(
+    api: &mut A,
+    validator: &Validator,
+    text: &str,
+    ctx: &RequestContext,
+) -> String {
+    let data: json::Value = match json::from_str(text) {
+        Ok(jsondata) => jsondata,
+        Err(e) => {
+            warn!(peer = ctx.peer.as_str(), error:% = e; "request is not valid json");
+            return json::to_string(&parse_error(e.to_string())).unwrap();
+        }
+    };
+    // тело запроса может содержать секреты, поэтому только на уровне debug
+    debug!(peer = ctx.peer.as_str(), body:% = data; "request body");
+    if !validator.is_valid(&data) {
+        warn!(peer = ctx.peer.as_str(); "request rejected by schema");
+        let mut errors: Vec<String> = Vec::new();
+        for e in validator.iter_errors(&data) {
+            let er = format!("Error: {}\n\n Location: {}\n\n", e, e.instance_path);
+            errors.push(er);
+        }
+        return json::to_string(&invalid_request(errors.join(";"))).unwrap();
+    }
+    let mut requests = RPCQueue::<JsonRpcRequest>::default();
+    requests.push(json::from_value(data).unwrap());
+    api.execute(&mut requests, ctx)
+}
+
+/// учесть выполненный запрос в метриках: метод, длительность и код ошибки ответа
+pub(crate) fn observe_request(method: &str, started: Instant, reply: &dyn JsonRpcReplyMsg) {
+    let error_code = json::to_value(reply)
+        .ok()
+        .and_then(|reply| reply["error"]["code"].as_i64());
+    metrics::registry::global().observe_request(method, started.elapsed(), error_code);
+}
+
+/// Запрос STP-клиента, прочитанный рабочим потоком; ответ возвращается через `reply`.
+struct StpCall {
+    principal: Principal,
+    peer: String,
+    request: String,
+    reply: Sender<String>,
+}
 
-            // pack to JsonRPC reply
-            let dto: Box<dyn JsonRpcReplyMsg> = match error_code {
-                0 => Box::new(reply(rpc_cmd.id, resp)),
-                1 => Box::new(reply_error(rpc_cmd.id, api_error(resp))),
-                -32601 => Box::new(reply_error(rpc_cmd.id, invalid_method(resp))),
-                -32602 => Box::new(reply_error(rpc_cmd.id, invalid_param(resp))),
-                -32603 => Box::new(reply_error(rpc_cmd.id, internal_error(resp))),
-                _ => Box::new(reply_error(rpc_cmd.id, unhandled_error(resp))),
-            };
-            replies.push(dto);
+/// handshake, аутентификация и обмен запросом в рабочем потоке:
+/// сам запрос выполняет цикл обслуживания, получив его через `calls`
+fn serve_connection(handshake: Handshake, calls: Sender<StpCall>) {
+    let mut connection = match handshake.complete() {
+        Ok(connection) => connection,
+        Err(ConnectError::AuthFailed) => {
+            warn!("connection rejected: authentication failed");
+            metrics::registry::global().handshake_failed("auth");
+            return;
         }
+        Err(e) => {
+            debug!(error:% = e; "connection failed");
+            metrics::registry::global().handshake_failed(handshake_failure(&e));
+            return;
+        }
+    };
 
-        json::to_string_pretty(&replies).unwrap()
+    let principal = connection.principal().clone();
+    let peer = connection.peer().to_string();
+    info!(peer = peer.as_str(), user = principal.name(); "connection accepted");
+
+    metrics::registry::global().connection_opened();
+    let processed = connection.process_request(|request| {
+        let (reply, response) = channel();
+        let call = StpCall {
+            principal,
+            peer: peer.clone(),
+            request,
+            reply,
+        };
+        let _ = calls.send(call);
+        // цикл обслуживания завершился - ответа не будет
+        response.recv().unwrap_or_default()
+    });
+    metrics::registry::global().connection_closed();
+    if let Err(e) = processed {
+        debug!(peer = peer.as_str(), error:% = e; "request failed");
+    }
+}
+
+/// причина отказа в соединении для метрик
+fn handshake_failure(error: &ConnectError) -> &'static str {
+    match error {
+        ConnectError::BadHandshake => "handshake",
+        ConnectError::AuthFailed | ConnectError::AuthRequired => "auth",
+        #[cfg(feature = "tls")]
+        ConnectError::Tls(_) => "tls",
+        ConnectError::Io(_) => "io",
+    }
+}
+
+/// запрос клиента в журнале: без параметров на уровне info, с параметрами на уровне debug
+pub(crate) fn log_request(rpc_cmd: &JsonRpcRequest, ctx: &RequestContext) {
+    info!(
+        id = rpc_cmd.id.as_str(),
+        peer = ctx.peer.as_str(),
+        user = ctx.principal.name(),
+        home = rpc_cmd.home.as_deref().unwrap_or("-"),
+        method = rpc_cmd.method.as_str();
+        "request"
+    );
+    debug!(id = rpc_cmd.id.as_str(), params:% = rpc_cmd.params; "request params");
+}
+
+/// сохранить состояние дома после изменения и сформировать ответ
+fn persisted(home: &Home, method: &str, error_code: &mut i32) -> String {
+    match home.persist() {
+        Ok(()) => format!("{method}: success"),
+        Err(e) => {
+            *error_code = 1;
+            format!("{method} error: {e}")
+        }
+    }
+}
+
+/// код JSON-RPC ошибки для ошибки выполнения команды устройства
+fn command_error_code(error: &CommandError) -> i32 {
+    match error {
+        CommandError::UnknownCommand(_) => -32601,
+        CommandError::InvalidData(_) => -32602,
+        CommandError::Rejected(_) | CommandError::Home(_) => 1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{serve_connection, ServerConfig, SmartHomePublicApi};
+    use crate::access::policy::{AccessPolicy, RequestContext};
+    use crate::access::role::Role;
+    use crate::alarms::alarm::AlarmStatus;
+    use crate::clock::ManualClock;
+    use crate::command::queue::RPCQueue;
+    use crate::events::event::HomeEvent;
+    use crate::groups::group::GroupMember;
+    use crate::json_rpc::request::JsonRpcRequest;
+    use crate::json_rpc::utils::get_validator;
+    use crate::my_smart_home::home::Home;
+    use crate::my_smart_home::smart_home::SmartHome;
+    use crate::smart_device::device::{DeviceState, SmartDevice, VecOfDevice};
+    use crate::smart_device::kettle::Kettle;
+    use crate::smart_device::socket::Socket;
+    use crate::smart_device::thermometer::Thermometer;
+    use chrono::{NaiveDate, TimeDelta};
+    use serde_json::{json, Value};
+    use std::env;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::path::PathBuf;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use stp::auth::{AuthConfig, Principal};
+    use stp::client::StpClient;
+    use stp::server::StpServer;
+
+    /// прогнать batch через валидатор схемы и execute
+    fn call(home: &mut Home, batch: Value) -> Value {
+        call_as(home, batch, &RequestContext::full())
+    }
+
+    fn call_as(home: &mut Home, batch: Value, ctx: &RequestContext) -> Value {
+        let validator = get_validator(PathBuf::from("public_api.json")).unwrap();
+        assert!(validator.is_valid(&batch), "schema rejected: {batch}");
+        execute_as(home, batch, ctx)
+    }
+
+    /// выполнить batch в обход схемы, как если бы схема его пропустила
+    fn call_unchecked(home: &mut Home, batch: Value) -> Value {
+        execute_as(home, batch, &RequestContext::full())
+    }
+
+    fn execute_as(home: &mut Home, batch: Value, ctx: &RequestContext) -> Value {
+        let mut requests = RPCQueue::<JsonRpcRequest>::default();
+        requests.push(serde_json::from_value(batch).unwrap());
+        serde_json::from_str(&home.execute(&mut requests, ctx)).unwrap()
+    }
+
+    fn request(method: &str, params: Value) -> Value {
+        json!([{"id": "1", "jsonrpc": "2.0", "method": method, "params": params}])
+    }
+
+    #[test]
+    fn test_add_and_del_device() {
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.add_room("kitchen".into(), vec![]).unwrap();
+
+        let params = json!({"room": "kitchen", "type": "kettle", "params": {"id": "3"}});
+        let reply = call(&mut home, request("addDevice", params));
+        assert_eq!(reply[0]["result"]["data"], "addDevice: success");
+        assert!(home.get_devices("kitchen").unwrap().contains("kettle-3"));
+
+        let params = json!({"room": "kitchen", "type": "toaster", "params": {}});
+        let reply = call(&mut home, request("addDevice", params));
+        assert_eq!(reply[0]["error"]["code"], 1);
+
+        let params = json!({"room": "kitchen", "device": "Smart Kettle 3"});
+        let reply = call(&mut home, request("delDevice", params));
+        assert_eq!(reply[0]["result"]["data"], "delDevice: success");
+        assert!(home.get_devices("kitchen").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rename_and_move_device() {
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.add_room("kitchen".into(), vec![Box::new(Kettle::new("1"))])
+            .unwrap();
+        home.add_room("storeroom".into(), vec![]).unwrap();
+        home.with_device("kitchen", "kettle-1", |d| d.switch("on"))
+            .unwrap();
+
+        let params = json!({"room": "kitchen", "device": "Smart Kettle 1", "to": "storeroom"});
+        let reply = call(&mut home, request("moveDevice", params));
+        assert_eq!(reply[0]["result"]["data"], "moveDevice: success");
+
+        // состояние устройства сохранилось
+        let kettle = home.get_device("storeroom", "kettle-1").unwrap();
+        assert!(matches!(kettle.device_state(), DeviceState::On));
+
+        let params = json!({"room": "storeroom", "device": "kettle-1", "new_name": "Old kettle"});
+        let reply = call(&mut home, request("renameDevice", params));
+        assert_eq!(reply[0]["result"]["data"], "renameDevice: success");
+
+        let params = json!({"name": "storeroom", "new_name": "pantry"});
+        let reply = call(&mut home, request("renameRoom", params));
+        assert_eq!(reply[0]["result"]["data"], "renameRoom: success");
+        assert!(home.get_device("pantry", "Old kettle").is_ok());
+
+        let params = json!({"name": "pantry", "new_name": "kitchen"});
+        let reply = call(&mut home, request("renameRoom", params));
+        assert_eq!(reply[0]["error"]["code"], 1);
+    }
+
+    #[test]
+    fn test_fault_and_repair() {
+        let mut home = Home::new("MyHome".into()).unwrap();
+        let devices: VecOfDevice = vec![Box::new(Socket::new("1")), Box::new(Socket::new("2"))];
+        home.add_room("bedroom".into(), devices).unwrap();
+
+        let execute = |device: &str, command: &str, data: Value| {
+            request(
+                "deviceExecute",
+                json!({"room": "bedroom", "device": device, "command": command, "data": data}),
+            )
+        };
+
+        let fault = execute(
+            "socket-1",
+            "fault",
+            json!(["E42", "overheat", "recoverable"]),
+        );
+        call(&mut home, fault);
+        let fault = execute("socket-2", "fault", json!(["E99", "burnt", "permanent"]));
+        call(&mut home, fault);
+
+        let reply = call(&mut home, execute("socket-1", "switch", json!(["on"])));
+        assert!(reply[0]["result"]["data"]
+            .as_str()
+            .unwrap()
+            .contains("broken"));
+
+        let reply = call(&mut home, request("getFaults", json!({})));
+        let faults = reply[0]["result"]["data"].as_array().unwrap();
+        assert_eq!(faults.len(), 2);
+        assert_eq!(faults[0]["device"], "socket-1");
+        assert_eq!(faults[0]["fault"]["code"], "E42");
+
+        let reply = call(&mut home, execute("socket-1", "repair", json!([])));
+        assert_eq!(reply[0]["result"]["data"], "fault was cleared");
+        let reply = call(&mut home, execute("socket-2", "reset", json!([])));
+        assert_eq!(reply[0]["error"]["code"], 1);
+
+        let socket = home.get_device("bedroom", "socket-1").unwrap();
+        assert_eq!(socket.device_state(), DeviceState::Off);
+        assert!(socket.fault().is_none());
+        assert_eq!(home.faulted_devices().len(), 1);
+    }
+
+    #[test]
+    fn test_manage_rules() {
+        let dir = env::temp_dir().join(format!("smart_home_rpc_rules_{}", std::process::id()));
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.set_data_dir(&dir).unwrap();
+
+        let rule = json!({
+            "id": "night",
+            "trigger": {"type": "time", "at": "23:00"},
+            "actions": [{"room": "storeroom", "device": "Smart Socket 4",
+                         "command": "switch", "data": ["off"]}]
+        });
+        let reply = call(&mut home, request("addRule", json!({ "rule": rule })));
+        assert_eq!(reply[0]["result"]["data"], "addRule: success");
+
+        let reply = call(
+            &mut home,
+            request("addRule", json!({"rule": {"id": "bad"}})),
+        );
+        assert_eq!(reply[0]["error"]["code"], -32602);
+
+        let endless = json!({
+            "id": "endless",
+            "trigger": {"type": "state_duration", "room": "storeroom", "device": "socket-4",
+                        "state": "on", "minutes": i64::MAX},
+            "actions": []
+        });
+        let reply = call(&mut home, request("addRule", json!({ "rule": endless })));
+        assert_eq!(reply[0]["error"]["code"], -32602);
+        assert_eq!(home.rules.rules().len(), 1);
+
+        let reply = call(&mut home, request("disableRule", json!({"id": "night"})));
+        assert_eq!(reply[0]["result"]["data"], "disableRule: success");
+
+        let reply = call(&mut home, request("listRules", json!({})));
+        assert_eq!(reply[0]["result"]["data"][0]["enabled"], false);
+
+        // правила и их состояние переживают перезапуск
+        let mut restored = Home::new("MyHome".into()).unwrap();
+        restored.set_data_dir(&dir).unwrap();
+        assert_eq!(restored.rules.rules(), home.rules.rules());
+
+        let reply = call(&mut home, request("delRule", json!({"id": "night"})));
+        assert_eq!(reply[0]["result"]["data"], "delRule: success");
+        let reply = call(&mut home, request("enableRule", json!({"id": "night"})));
+        assert_eq!(reply[0]["error"]["code"], 1);
+
+        let mut restored = Home::new("MyHome".into()).unwrap();
+        restored.set_data_dir(&dir).unwrap();
+        assert!(restored.rules.rules().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_manage_schedules() {
+        let mut home = Home::new("MyHome".into()).unwrap();
+
+        let schedule = json!({
+            "id": "socket-off",
+            "when": {"type": "cron", "expr": "0 23 * * *"},
+            "actions": [{"room": "storeroom", "device": "Smart Socket 4",
+                         "command": "switch", "data": ["off"]}]
+        });
+        let reply = call(
+            &mut home,
+            request("addSchedule", json!({ "schedule": schedule })),
+        );
+        assert_eq!(reply[0]["result"]["data"], "addSchedule: success");
+
+        let bad = json!({"id": "bad", "when": {"type": "cron", "expr": "* *"}, "actions": []});
+        let reply = call(
+            &mut home,
+            request("addSchedule", json!({ "schedule": bad })),
+        );
+        assert_eq!(reply[0]["error"]["code"], 1);
+
+        let reply = call(
+            &mut home,
+            request("disableSchedule", json!({"id": "socket-off"})),
+        );
+        assert_eq!(reply[0]["result"]["data"], "disableSchedule: success");
+
+        let reply = call(&mut home, request("listSchedules", json!({})));
+        assert_eq!(reply[0]["result"]["data"][0]["enabled"], false);
+        assert!(reply[0]["result"]["data"][0]["next_run"].is_string());
+
+        let reply = call(
+            &mut home,
+            request("delSchedule", json!({"id": "socket-off"})),
+        );
+        assert_eq!(reply[0]["result"]["data"], "delSchedule: success");
+        let reply = call(
+            &mut home,
+            request("delSchedule", json!({"id": "socket-off"})),
+        );
+        assert_eq!(reply[0]["error"]["code"], 1);
+    }
+
+    #[test]
+    fn test_scenes() {
+        let mut home = Home::new("MyHome".into()).unwrap();
+        let storeroom: VecOfDevice = vec![Box::new(Socket::new("4")), Box::new(Kettle::new("2"))];
+        home.add_room("storeroom".into(), storeroom).unwrap();
+
+        let scene = json!({
+            "id": "night",
+            "name": "Good night",
+            "entries": [
+                {"room": "storeroom", "device": "Smart Socket 4", "state": "on"},
+                {"room": "storeroom", "device": "kettle-2", "readings": {"temperature": 40.0}},
+                {"room": "storeroom", "device": "Smart Socket 9", "state": "off"}
+            ]
+        });
+        let reply = call(&mut home, request("addScene", json!({ "scene": scene })));
+        assert_eq!(reply[0]["result"]["data"], "addScene: success");
+
+        let reply = call(&mut home, request("applyScene", json!({"id": "night"})));
+        let outcomes = &reply[0]["result"]["data"];
+        assert_eq!(outcomes[0]["ok"], true);
+        assert_eq!(outcomes[1]["ok"], true);
+        assert_eq!(outcomes[2]["ok"], false);
+        assert_eq!(
+            home.get_device("storeroom", "socket-4")
+                .unwrap()
+                .device_state(),
+            DeviceState::On
+        );
+        let kettle = home.get_device("storeroom", "kettle-2").unwrap();
+        assert_eq!(kettle.get_readings()["temperature"], 40.0);
+
+        // снимок текущего состояния
+        let reply = call(
+            &mut home,
+            request(
+                "snapshotScene",
+                json!({"id": "now", "rooms": ["storeroom"]}),
+            ),
+        );
+        let entries = &reply[0]["result"]["data"]["entries"];
+        assert_eq!(entries.as_array().unwrap().len(), 2);
+        assert_eq!(entries[1]["device"], "socket-4");
+        assert_eq!(entries[1]["state"], "on");
+
+        let reply = call(&mut home, request("listScenes", json!({})));
+        assert_eq!(reply[0]["result"]["data"].as_array().unwrap().len(), 2);
+
+        let reply = call(&mut home, request("delScene", json!({"id": "night"})));
+        assert_eq!(reply[0]["result"]["data"], "delScene: success");
+        let reply = call(&mut home, request("applyScene", json!({"id": "night"})));
+        assert_eq!(reply[0]["error"]["code"], 1);
+    }
+
+    #[test]
+    fn test_groups() {
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.add_room("kitchen".into(), vec![Box::new(Socket::new("1"))])
+            .unwrap();
+        let storeroom: VecOfDevice = vec![Box::new(Socket::new("4")), Box::new(Kettle::new("2"))];
+        home.add_room("storeroom".into(), storeroom).unwrap();
+
+        let group = json!({"id": "sockets", "name": "все розетки",
+                           "members": [{"room": "kitchen", "device": "socket-1"}]});
+        let reply = call(&mut home, request("addGroup", json!({ "group": group })));
+        assert_eq!(reply[0]["result"]["data"], "addGroup: success");
+
+        let member = json!({"id": "sockets", "room": "storeroom", "device": "Smart Socket 4"});
+        let reply = call(&mut home, request("addGroupMember", member.clone()));
+        assert_eq!(reply[0]["result"]["data"], "addGroupMember: success");
+        let reply = call(&mut home, request("addGroupMember", member));
+        assert_eq!(reply[0]["error"]["code"], 1);
+        // то же устройство по идентификатору
+        let same = json!({"id": "sockets", "room": "storeroom", "device": "socket-4"});
+        let reply = call(&mut home, request("addGroupMember", same));
+        assert_eq!(reply[0]["error"]["code"], 1);
+        let missing = json!({"id": "sockets", "room": "storeroom", "device": "socket-9"});
+        let reply = call(&mut home, request("addGroupMember", missing));
+        assert_eq!(reply[0]["error"]["code"], 1);
+
+        let params = json!({"id": "sockets", "command": "switch", "data": ["on"]});
+        let reply = call(&mut home, request("groupExecute", params));
+        let outcome = &reply[0]["result"]["data"];
+        assert_eq!(outcome["succeeded"], 2);
+        assert_eq!(outcome["failed"], 0);
+        for (room, device) in [("kitchen", "socket-1"), ("storeroom", "socket-4")] {
+            let state = home.get_device(room, device).unwrap().device_state();
+            assert_eq!(state, DeviceState::On);
+        }
+
+        let reply = call(&mut home, request("groupReport", json!({"id": "sockets"})));
+        let report = reply[0]["result"]["data"].as_str().unwrap();
+        assert!(report.contains("Smart Socket 1") && report.contains("Smart Socket 4"));
+        assert!(!report.contains("Smart Kettle 2"));
+
+        // группа следует за устройствами при переименовании и переносе
+        home.add_room("pantry".into(), vec![]).unwrap();
+        home.rename_device("storeroom", "socket-4", "Розетка")
+            .unwrap();
+        home.move_device("storeroom", "socket-4", "pantry").unwrap();
+        home.rename_room("kitchen", "cuisine").unwrap();
+        let members = &home.groups.get("sockets").unwrap().members;
+        assert_eq!(
+            members,
+            &vec![
+                GroupMember {
+                    room: "cuisine".into(),
+                    device: "socket-1".into()
+                },
+                GroupMember {
+                    room: "pantry".into(),
+                    device: "socket-4".into()
+                },
+            ]
+        );
+        let params = json!({"id": "sockets", "command": "switch", "data": ["off"]});
+        let reply = call(&mut home, request("groupExecute", params));
+        assert_eq!(reply[0]["result"]["data"]["succeeded"], 2);
+
+        let reply = call(&mut home, request("delGroup", json!({"id": "sockets"})));
+        assert_eq!(reply[0]["result"]["data"], "delGroup: success");
+        let params = json!({"id": "sockets", "command": "switch", "data": ["off"]});
+        let reply = call(&mut home, request("groupExecute", params));
+        assert_eq!(reply[0]["error"]["code"], 1);
+    }
+
+    #[test]
+    fn test_query_devices() {
+        let mut home = Home::new("MyHome".into()).unwrap();
+        let mut kettle = Kettle::new("1");
+        kettle.update_reading("temperature", 90.0);
+        let kitchen: VecOfDevice = vec![Box::new(Socket::new("1")), Box::new(kettle)];
+        home.add_room("kitchen".into(), kitchen).unwrap();
+        let mut broken = Socket::new("3");
+        broken.set_broken();
+        let bedroom: VecOfDevice = vec![Box::new(broken), Box::new(Kettle::new("2"))];
+        home.add_room("bedroom".into(), bedroom).unwrap();
+
+        let query = |home: &mut Home, params: Value| {
+            let reply = call(home, request("queryDevices", params));
+            reply[0]["result"]["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|d| d["path"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            query(&mut home, json!({"state": "broken"})),
+            vec!["bedroom=>socket-3"]
+        );
+        assert_eq!(
+            query(&mut home, json!({"type": "kettle"})),
+            vec!["bedroom=>kettle-2", "kitchen=>kettle-1"]
+        );
+        assert_eq!(
+            query(
+                &mut home,
+                json!({"room": "kitchen", "name": "Smart Socket *"})
+            ),
+            vec!["kitchen=>socket-1"]
+        );
+        let hot = json!({"readings": [{"reading": "temperature", "op": ">", "value": 50}]});
+        assert_eq!(query(&mut home, hot), vec!["kitchen=>kettle-1"]);
+        assert_eq!(query(&mut home, json!({})).len(), 4);
+
+        let reply = call(
+            &mut home,
+            request("queryDevices", json!({"state": "broken"})),
+        );
+        let device = &reply[0]["result"]["data"][0];
+        assert_eq!(device["type"], "socket");
+        assert_eq!(device["fault"]["code"], "manual");
+    }
+
+    #[test]
+    fn test_get_history() {
+        let clock = ManualClock::new(
+            NaiveDate::from_ymd_opt(2025, 1, 10)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap(),
+        );
+        let dir = env::temp_dir().join(format!("smart_home_rpc_history_{}", std::process::id()));
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.clock = Box::new(clock.clone());
+        home.set_data_dir(&dir).unwrap();
+        home.add_room("living".into(), vec![Box::new(Thermometer::new("1"))])
+            .unwrap();
+
+        for value in [20.0, 22.0, 30.0] {
+            home.tick();
+            home.with_device("living", "Thermometer 1", |d| {
+                d.update_reading("temperature", value)
+            })
+            .unwrap();
+            clock.advance(TimeDelta::minutes(20));
+        }
+        home.tick();
+
+        let params = json!({"room": "living", "device": "Thermometer 1",
+                            "from": "2025-01-10T12:00:00", "to": "2025-01-10T13:00:00"});
+        let reply = call(&mut home, request("getHistory", params.clone()));
+        let points = reply[0]["result"]["data"].as_array().unwrap();
+        // первое наблюдение, три изменения и периодические записи
+        assert!(points.len() >= 4);
+        assert_eq!(points[0]["state"], "on");
+
+        let mut downsampled = params.clone();
+        downsampled["step"] = json!(1800);
+        let reply = call(&mut home, request("getHistory", downsampled));
+        let points = reply[0]["result"]["data"].as_array().unwrap();
+        let ts: Vec<&str> = points.iter().map(|p| p["ts"].as_str().unwrap()).collect();
+        assert_eq!(
+            ts,
+            vec![
+                "2025-01-10T12:00:00",
+                "2025-01-10T12:30:00",
+                "2025-01-10T13:00:00"
+            ]
+        );
+        assert_eq!(points[2]["readings"]["temperature"], 30.0);
+
+        let mut huge = params.clone();
+        huge["step"] = json!(i64::MAX);
+        let validator = get_validator(PathBuf::from("public_api.json")).unwrap();
+        assert!(!validator.is_valid(&request("getHistory", huge.clone())));
+        let reply = call_unchecked(&mut home, request("getHistory", huge));
+        assert_eq!(reply[0]["error"]["code"], -32602);
+
+        let mut bad = params;
+        bad["from"] = json!("вчера");
+        let reply = call(&mut home, request("getHistory", bad));
+        assert_eq!(reply[0]["error"]["code"], -32602);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_energy_report() {
+        let clock = ManualClock::new(
+            NaiveDate::from_ymd_opt(2025, 1, 10)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap(),
+        );
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.clock = Box::new(clock.clone());
+        let kitchen: VecOfDevice = vec![Box::new(Socket::new("1")), Box::new(Kettle::new("1"))];
+        home.add_room("kitchen".into(), kitchen).unwrap();
+        home.add_room("storeroom".into(), vec![Box::new(Socket::new("4"))])
+            .unwrap();
+
+        home.with_device("kitchen", "socket-1", |d| d.update_reading("power", 2000.0))
+            .unwrap();
+        let cmd = json!({"room": "kitchen", "device": "socket-1",
+                         "command": "switch", "data": ["on"]});
+        call(&mut home, request("deviceExecute", cmd));
+
+        // два часа по 2 кВт
+        home.tick();
+        clock.advance(TimeDelta::hours(2));
+        home.tick();
+
+        let params = json!({"period": "month", "tariff": 5.5});
+        let reply = call(&mut home, request("getEnergyReport", params.clone()));
+        let report = &reply[0]["result"]["data"];
+        assert_eq!(report["from"], "2025-01-01");
+        assert_eq!(report["kwh"], 4.0);
+        assert_eq!(report["cost"], 22.0);
+        assert_eq!(report["rooms"][0]["room"], "kitchen");
+        assert_eq!(report["rooms"][0]["devices"].as_array().unwrap().len(), 1);
+        assert_eq!(report["rooms"][1]["kwh"], 0.0);
+
+        let reply = call(&mut home, request("createEnergyReport", params));
+        let text = reply[0]["result"]["data"].as_str().unwrap();
+        assert!(text.contains("Smart Socket 1 (socket-1): 4.000 кВт*ч, 22.00"));
+    }
+
+    #[test]
+    fn test_alarms() {
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.add_room("bedroom".into(), vec![Box::new(Thermometer::new("2"))])
+            .unwrap();
+        let (_, events) = home.events.subscribe_channel();
+
+        let alarm = json!({
+            "id": "hot-bedroom",
+            "room": "bedroom",
+            "device": "Thermometer 2",
+            "condition": {"type": "reading", "reading": "temperature", "op": ">", "value": 28.0}
+        });
+        let mut endless = alarm.clone();
+        endless["id"] = json!("endless");
+        endless["debounce_seconds"] = json!(i64::MAX);
+        let reply = call(&mut home, request("addAlarm", json!({ "alarm": endless })));
+        assert_eq!(reply[0]["error"]["code"], -32602);
+
+        let reply = call(&mut home, request("addAlarm", json!({ "alarm": alarm })));
+        assert_eq!(reply[0]["result"]["data"], "addAlarm: success");
+
+        home.with_device("bedroom", "Thermometer 2", |d| {
+            d.update_reading("temperature", 30.0)
+        })
+        .unwrap();
+        home.tick();
+
+        let reply = call(&mut home, request("getAlarms", json!({"open": true})));
+        let record = &reply[0]["result"]["data"][0];
+        assert_eq!(record["status"], "active");
+        let seq = record["seq"].clone();
+
+        let reply = call(&mut home, request("ackAlarm", json!({ "seq": seq })));
+        assert_eq!(reply[0]["result"]["data"], "ackAlarm: success");
+        let reply = call(&mut home, request("ackAlarm", json!({ "seq": seq })));
+        assert_eq!(reply[0]["error"]["code"], 1);
+
+        let statuses: Vec<String> = events
+            .try_iter()
+            .filter_map(|e| match e {
+                HomeEvent::AlarmChanged { record } => Some(format!("{:?}", record.status)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(statuses, vec!["Active", "Acknowledged"]);
+
+        let reply = call(&mut home, request("delAlarm", json!({"id": "hot-bedroom"})));
+        assert_eq!(reply[0]["result"]["data"], "delAlarm: success");
+        assert!(events.try_iter().any(|e| matches!(
+            e,
+            HomeEvent::AlarmChanged { ref record } if record.status == AlarmStatus::Cleared
+        )));
+        let reply = call(&mut home, request("getAlarms", json!({})));
+        assert_eq!(reply[0]["result"]["data"][0]["status"], "cleared");
+    }
+
+    #[test]
+    fn test_locations() {
+        let mut home = Home::new("MyHome".into()).unwrap();
+        for (room, id) in [("floor-1", None), ("floor-1/bathroom", Some("1"))]
+            .into_iter()
+            .chain([("floor-2", Some("2")), ("floor-2/bathroom", Some("3"))])
+        {
+            let devices: VecOfDevice = id
+                .map(|id| Box::new(Socket::new(id)) as Box<dyn SmartDevice>)
+                .into_iter()
+                .collect();
+            home.add_room(room.into(), devices).unwrap();
+        }
+
+        let reply = call(&mut home, request("getLocations", json!({})));
+        let tree = &reply[0]["result"]["data"];
+        assert_eq!(tree[0]["path"], "floor-1");
+        assert_eq!(tree[0]["children"][0]["devices"], json!(["socket-1"]));
+        assert_eq!(tree[1]["children"][0]["path"], "floor-2/bathroom");
+
+        let reply = call(
+            &mut home,
+            request("getLocations", json!({"location": "floor-2"})),
+        );
+        assert_eq!(
+            reply[0]["result"]["data"][0]["devices"],
+            json!(["socket-2"])
+        );
+        let reply = call(
+            &mut home,
+            request("getLocations", json!({"location": "attic"})),
+        );
+        assert_eq!(reply[0]["error"]["code"], 1);
+
+        let params = json!({"location": "floor-2", "command": "switch", "data": ["on"]});
+        let reply = call(&mut home, request("locationExecute", params));
+        assert_eq!(reply[0]["result"]["data"]["succeeded"], 2);
+        for (room, device, state) in [
+            ("floor-1/bathroom", "socket-1", DeviceState::Off),
+            ("floor-2", "socket-2", DeviceState::On),
+            ("floor-2/bathroom", "socket-3", DeviceState::On),
+        ] {
+            assert_eq!(home.get_device(room, device).unwrap().device_state(), state);
+        }
+
+        let reply = call(
+            &mut home,
+            request("createReport", json!({"location": "floor-1"})),
+        );
+        let report = reply[0]["result"]["data"].as_str().unwrap();
+        assert!(report.contains("floor-1/bathroom") && !report.contains("floor-2"));
+
+        let params = json!({"location": "floor-2", "state": "on"});
+        let reply = call(&mut home, request("queryDevices", params));
+        let paths: Vec<&str> = reply[0]["result"]["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| d["path"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            paths,
+            vec!["floor-2=>socket-2", "floor-2=>bathroom=>socket-3"]
+        );
+    }
+
+    #[test]
+    fn test_access_denied() {
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.add_room("kitchen".into(), vec![Box::new(Socket::new("1"))])
+            .unwrap();
+        home.add_room("kids".into(), vec![Box::new(Socket::new("2"))])
+            .unwrap();
+        let config: ServerConfig = serde_json::from_value(json!({
+            "users": [{"user": "kid", "salt": "00", "hash": "00"}],
+            "grants": [{"name": "kid", "role": "operator", "rooms": ["kids"]}]
+        }))
+        .unwrap();
+        assert_eq!(config.auth.users[0].user, "kid");
+        let policy = config.access;
+        let kid = policy.context(&Principal::User("kid".into()), "10.0.0.7:50000");
+
+        let reply = call_as(
+            &mut home,
+            request("delRoom", json!({"name": "kitchen"})),
+            &kid,
+        );
+        assert_eq!(reply[0]["error"]["code"], -32001);
+        assert_eq!(reply[0]["error"]["message"], "Access denied");
+        assert!(home.room("kitchen").is_ok());
+
+        let switch = |room: &str| {
+            let params = json!({"room": room, "device": "Smart Socket 2", "command": "switch", "data": ["on"]});
+            request("deviceExecute", params)
+        };
+        call_as(&mut home, switch("kids"), &kid);
+        let state = home.get_device("kids", "socket-2").unwrap().device_state();
+        assert_eq!(state, DeviceState::On);
+        let reply = call_as(&mut home, switch("kitchen"), &kid);
+        assert_eq!(reply[0]["error"]["code"], -32001);
+
+        // аноним по умолчанию только смотрит
+        let anonymous = policy.context(&Principal::Anonymous, "127.0.0.1:50000");
+        let reply = call_as(
+            &mut home,
+            request("delRoom", json!({"name": "kids"})),
+            &anonymous,
+        );
+        assert_eq!(reply[0]["error"]["code"], -32001);
+        assert!(home.room("kids").is_ok());
+    }
+
+    #[test]
+    fn test_audit_log() {
+        let dir = env::temp_dir().join(format!("smart_home_audit_rpc_{}", std::process::id()));
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.add_room("kitchen".into(), vec![Box::new(Socket::new("1"))])
+            .unwrap();
+        home.set_data_dir(&dir).unwrap();
+        let config: ServerConfig = serde_json::from_value(json!({
+            "grants": [{"name": "anna", "role": "operator"}, {"name": "kid", "role": "viewer"}]
+        }))
+        .unwrap();
+        let anna = config
+            .access
+            .context(&Principal::User("anna".into()), "10.0.0.5:50000");
+        let kid = config
+            .access
+            .context(&Principal::User("kid".into()), "10.0.0.7:50000");
+
+        let switch = |state: &str| {
+            let params = json!({"room": "kitchen", "device": "Smart Socket 1",
+                                "command": "switch", "data": [state]});
+            request("deviceExecute", params)
+        };
+        call_as(&mut home, switch("on"), &anna);
+        call_as(&mut home, switch("off"), &kid);
+        let report =
+            json!({"room": "kitchen", "device": "socket-1", "command": "report", "data": []});
+        call_as(&mut home, request("deviceExecute", report), &kid);
+        call(&mut home, request("delRoom", json!({"name": "bathroom"})));
+
+        let reply = call(
+            &mut home,
+            request("getAuditLog", json!({"device": "socket-1"})),
+        );
+        let entries = reply[0]["result"]["data"].as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["user"], "anna");
+        assert_eq!(entries[0]["peer"], "10.0.0.5:50000");
+        assert_eq!(entries[0]["params"]["data"], json!(["on"]));
+        assert_eq!(entries[0]["outcome"], "ok");
+        assert_eq!(entries[1]["user"], "kid");
+        assert_eq!(entries[1]["outcome"], "denied");
+
+        let reply = call(
+            &mut home,
+            request("getAuditLog", json!({"method": "delRoom"})),
+        );
+        let entry = &reply[0]["result"]["data"][0];
+        assert_eq!(entry["outcome"], "error");
+        assert!(entry["user"].is_null());
+        assert!(entry["error"].as_str().unwrap().contains("not exist"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_get_metrics() {
+        let mut home = Home::new("MetricsHome".into()).unwrap();
+        home.add_room(
+            "kitchen".into(),
+            vec![Box::new(Socket::new("1")), Box::new(Thermometer::new("1"))],
+        )
+        .unwrap();
+        call(&mut home, request("getDevices", json!({"room": "kitchen"})));
+        call(&mut home, request("getDevices", json!({"room": "attic"})));
+
+        let viewer = AccessPolicy {
+            anonymous: Role::Viewer,
+            ..Default::default()
+        }
+        .context(&Principal::Anonymous, "10.0.0.7:50000");
+        let reply = call_as(&mut home, request("getMetrics", json!({})), &viewer);
+        assert_eq!(reply[0]["error"]["code"], -32001);
+
+        let reply = call(&mut home, request("getMetrics", json!({})));
+        let text = reply[0]["result"]["data"].as_str().unwrap();
+        // метрики общие для процесса, другие тесты тоже выполняют getDevices
+        assert!(text.contains("smart_home_rpc_requests_total{method=\"getDevices\"}"));
+        assert!(text.contains("smart_home_rpc_errors_total{code=\"-32001\"}"));
+        assert!(text.contains(
+            "smart_home_device_state{home=\"MetricsHome\",room=\"kitchen\",device=\"socket-1\",state=\"off\"} 1\n"
+        ));
+        assert!(text.contains(
+            "smart_home_device_temperature_celsius{home=\"MetricsHome\",room=\"kitchen\",device=\"thermometer-1\"}"
+        ));
+        assert!(text.contains(
+            "smart_home_device_power_watts{home=\"MetricsHome\",room=\"kitchen\",device=\"socket-1\"}"
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket_mode() {
+        let config = |mode: &str| super::UnixSocketConfig {
+            path: "/run/smart_home.sock".into(),
+            mode: mode.into(),
+        };
+        assert_eq!(config("660").mode().unwrap(), 0o660);
+        assert!(config("1777").mode().is_err());
+        assert!(config("rw-").mode().is_err());
+    }
+
+    #[test]
+    fn test_serve_connection() {
+        let mut server = StpServer::bind("127.0.0.1:0").unwrap();
+        server.set_auth(AuthConfig {
+            open: true,
+            ..Default::default()
+        });
+        let addr = server.local_addr().unwrap();
+        let (sender, calls) = channel();
+
+        // клиент обрывает соединение посреди запроса: ошибка остается в рабочем потоке
+        let broken = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(b"clnt").unwrap();
+            let mut reply = [0; 8];
+            stream.read_exact(&mut reply).unwrap();
+            stream.write_all(&100u32.to_be_bytes()).unwrap();
+            stream.write_all(b"[{").unwrap();
+        });
+        serve_connection(server.accept_handshake().unwrap(), sender.clone());
+        broken.join().unwrap();
+        assert!(calls.try_recv().is_err());
+
+        // запрос выполняет тот, кто читает `calls`
+        let client = thread::spawn(move || {
+            let mut client = StpClient::connect(addr).unwrap();
+            client.send_request("ping").unwrap()
+        });
+        let handshake = server.accept_handshake().unwrap();
+        thread::spawn(move || serve_connection(handshake, sender));
+        let call = calls.recv().unwrap();
+        assert_eq!(call.request, "ping");
+        assert_eq!(call.principal, Principal::Anonymous);
+        call.reply.send("pong".into()).unwrap();
+        assert_eq!(client.join().unwrap(), "pong");
     }
 }
@@ -0,0 +1,26 @@
+use super::error::{SmartHomeError, SmartHomeResult};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// прочитать json-файл
+pub fn load_json<T: DeserializeOwned>(path: &Path) -> SmartHomeResult<T> {
+    let data = fs::read_to_string(path).map_err(|e| storage_error(path, e))?;
+    serde_json::from_str(&data).map_err(|e| storage_error(path, e))
+}
+
+/// записать json-файл, через временный файл, чтобы не оставить его недописанным
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> SmartHomeResult<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| storage_error(path, e))?;
+    }
+    let data = serde_json::to_string_pretty(value).map_err(|e| storage_error(path, e))?;
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, data).map_err(|e| storage_error(path, e))?;
+    fs::rename(&tmp, path).map_err(|e| storage_error(path, e))
+}
+
+pub(crate) fn storage_error(path: &Path, e: impl ToString) -> SmartHomeError {
+    SmartHomeError::Storage(format!("{}: {}", path.display(), e.to_string()))
+}
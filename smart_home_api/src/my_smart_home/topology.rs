@@ -0,0 +1,89 @@
+use super::error::SmartHomeResult;
+use super::home::Home;
+use super::smart_home::SmartHome;
+use crate::smart_device::device::{DeviceState, Readings};
+use crate::smart_device::fault::Fault;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// Сохраненное устройство: тип из реестра и параметры для его воссоздания
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeviceRecord {
+    #[serde(rename = "type")]
+    pub device_type: String,
+    /// параметр "id" конструктора; идентификатор устройства - "<тип>-<id>"
+    pub id: String,
+    pub name: String,
+    pub state: DeviceState,
+    #[serde(default)]
+    pub readings: Readings,
+    #[serde(default)]
+    pub fault: Option<Fault>,
+}
+
+/// Состав дома: комнаты и устройства в них
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Topology {
+    pub rooms: BTreeMap<String, Vec<DeviceRecord>>,
+}
+
+impl Topology {
+    /// снять состав дома
+    pub fn capture(home: &Home) -> Self {
+        let mut rooms = BTreeMap::new();
+        for (room, devices) in home.rooms.iter() {
+            let mut records: Vec<DeviceRecord> = devices
+                .iter()
+                .map(|(id, device)| {
+                    let device_type = device.get_type().type_name().to_string();
+                    let prefix = format!("{device_type}-");
+                    DeviceRecord {
+                        id: id.strip_prefix(&prefix).unwrap_or(id).to_string(),
+                        device_type,
+                        name: device.get_name(),
+                        state: device.device_state(),
+                        readings: device.get_readings(),
+                        fault: device.fault(),
+                    }
+                })
+                .collect();
+            records.sort_by(|a, b| a.id.cmp(&b.id));
+            rooms.insert(room.clone(), records);
+        }
+        Self { rooms }
+    }
+
+    /// заменить комнаты и устройства дома сохраненными; устройства создаются через реестр типов
+    pub fn restore(&self, home: &mut Home) -> SmartHomeResult<()> {
+        let mut devices = vec![];
+        for (room, records) in self.rooms.iter() {
+            for record in records {
+                let params = json!({"id": record.id, "name": record.name});
+                let mut device = home.registry.create(&record.device_type, &params)?;
+                match &record.fault {
+                    Some(fault) => {
+                        device.set_device_state(DeviceState::Broken);
+                        device.set_fault(Some(fault.clone()));
+                    }
+                    None => {
+                        device.set_device_state(record.state);
+                    }
+                }
+                for (reading, value) in record.readings.iter() {
+                    device.update_reading(reading, *value);
+                }
+                devices.push((room.clone(), device));
+            }
+        }
+
+        home.rooms.clear();
+        for room in self.rooms.keys() {
+            home.add_room(room.clone(), vec![])?;
+        }
+        for (room, device) in devices {
+            home.add_device(&room, device)?;
+        }
+        Ok(())
+    }
+}
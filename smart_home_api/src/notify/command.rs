@@ -0,0 +1,96 @@
+use super::sink::{NotificationSink, NotifyError};
+use crate::events::event::HomeEvent;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// как часто проверять, завершилась ли команда
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Оповещение запуском команды, событие передается в stdin в виде json
+pub struct CommandSink {
+    program: String,
+    args: Vec<String>,
+    /// команда, не завершившаяся за это время, принудительно останавливается
+    timeout: Duration,
+}
+
+impl CommandSink {
+    pub fn new(program: &str, args: &[String], timeout: Duration) -> Self {
+        Self {
+            program: program.to_string(),
+            args: args.to_vec(),
+            timeout,
+        }
+    }
+}
+
+impl NotificationSink for CommandSink {
+    fn name(&self) -> String {
+        format!("command {}", self.program)
+    }
+
+    fn deliver(&mut self, event: &HomeEvent) -> Result<(), NotifyError> {
+        let data = serde_json::to_vec(event).map_err(|e| NotifyError::Rejected(e.to_string()))?;
+
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(&data)?;
+        }
+
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                child.kill()?;
+                child.wait()?;
+                return Err(NotifyError::Rejected(format!(
+                    "{} timed out after {} ms",
+                    self.program,
+                    self.timeout.as_millis()
+                )));
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+        match status.success() {
+            true => Ok(()),
+            false => Err(NotifyError::Rejected(format!(
+                "{} exited with {status}",
+                self.program
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CommandSink;
+    use crate::events::event::HomeEvent;
+    use crate::notify::sink::NotificationSink;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_command_timeout() {
+        let event = HomeEvent::RuleFired {
+            rule: "night".into(),
+            outcomes: vec![],
+        };
+        let args = vec!["-c".to_string(), "cat > /dev/null".to_string()];
+        let mut sink = CommandSink::new("sh", &args, Duration::from_secs(5));
+        assert!(sink.deliver(&event).is_ok());
+
+        let args = vec!["-c".to_string(), "sleep 10".to_string()];
+        let mut sink = CommandSink::new("sh", &args, Duration::from_millis(200));
+        let started = Instant::now();
+        assert!(sink.deliver(&event).is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,80 @@
+use crate::events::event::HomeEvent;
+use serde::{Deserialize, Serialize};
+
+/// Какие события получает получатель оповещений; пустой список - без ограничения
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EventFilter {
+    /// виды событий: "fault_detected", "rule_fired", "alarm_changed", ...
+    #[serde(default)]
+    pub events: Vec<String>,
+    #[serde(default)]
+    pub rooms: Vec<String>,
+    /// идентификаторы устройств
+    #[serde(default)]
+    pub devices: Vec<String>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &HomeEvent) -> bool {
+        if !self.events.is_empty() && !self.events.iter().any(|e| e == event.kind()) {
+            return false;
+        }
+        if self.rooms.is_empty() && self.devices.is_empty() {
+            return true;
+        }
+
+        let device = match event {
+            HomeEvent::AlarmChanged { record } => Some((&*record.room, &*record.device)),
+            event => event.device(),
+        };
+        // события без устройства не проходят фильтр по комнатам и устройствам
+        let Some((room, device)) = device else {
+            return false;
+        };
+        (self.rooms.is_empty() || self.rooms.iter().any(|r| r == room))
+            && (self.devices.is_empty() || self.devices.iter().any(|d| d == device))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::EventFilter;
+    use crate::events::event::HomeEvent;
+    use crate::smart_device::device::DeviceState;
+
+    #[test]
+    fn test_filter() {
+        let fault = HomeEvent::FaultDetected {
+            room: "bedroom".into(),
+            device: "socket-3".into(),
+            fault: None,
+        };
+        let state = HomeEvent::StateChanged {
+            room: "kitchen".into(),
+            device: "socket-1".into(),
+            from: DeviceState::Off,
+            to: DeviceState::On,
+        };
+        let rule = HomeEvent::RuleFired {
+            rule: "night".into(),
+            outcomes: vec![],
+        };
+
+        let all = EventFilter::default();
+        assert!(all.matches(&fault) && all.matches(&state) && all.matches(&rule));
+
+        let faults = EventFilter {
+            events: vec!["fault_detected".into(), "rule_fired".into()],
+            ..Default::default()
+        };
+        assert!(faults.matches(&fault) && faults.matches(&rule));
+        assert!(!faults.matches(&state));
+
+        let kitchen = EventFilter {
+            rooms: vec!["kitchen".into()],
+            ..Default::default()
+        };
+        assert!(kitchen.matches(&state));
+        assert!(!kitchen.matches(&fault) && !kitchen.matches(&rule));
+    }
+}
@@ -0,0 +1,37 @@
+use super::sink::{NotificationSink, NotifyError};
+use crate::events::event::HomeEvent;
+use chrono::Local;
+use serde_json::json;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Оповещения в файл, по json-строке на событие
+pub struct LogFileSink {
+    path: PathBuf,
+}
+
+impl LogFileSink {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl NotificationSink for LogFileSink {
+    fn name(&self) -> String {
+        format!("log_file {}", self.path.display())
+    }
+
+    fn deliver(&mut self, event: &HomeEvent) -> Result<(), NotifyError> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let line = json!({ "ts": Local::now().naive_local(), "event": event });
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{line}")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,7 @@
+pub mod command;
+pub mod filter;
+pub mod log_file;
+pub mod notifier;
+pub mod sink;
+pub mod smtp;
+pub mod webhook;
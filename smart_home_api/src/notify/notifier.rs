@@ -0,0 +1,634 @@
+use super::command::CommandSink;
+use super::filter::EventFilter;
+use super::log_file::LogFileSink;
+use super::sink::{NotificationSink, NotifyError};
+use super::smtp::SmtpSink;
+use super::webhook::WebhookSink;
+use crate::events::event::HomeEvent;
+use crate::my_smart_home::error::{SmartHomeError, SmartHomeResult};
+use crate::my_smart_home::storage::load_json;
+use chrono::{NaiveDateTime, TimeDelta};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// сколько оповещений ждут доставки, лишние отбрасываются, начиная с самых старых
+const MAX_QUEUED: usize = 1000;
+
+/// Повтор неудавшейся доставки: попытки с удваивающейся паузой
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    /// пауза перед первым повтором
+    pub delay_seconds: i64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            delay_seconds: 10,
+        }
+    }
+}
+
+/// Описание получателя оповещений в конфигурации
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkKind {
+    LogFile {
+        path: PathBuf,
+    },
+    Webhook {
+        url: String,
+        #[serde(default = "default_timeout_ms")]
+        timeout_ms: u64,
+    },
+    Command {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default = "default_timeout_ms")]
+        timeout_ms: u64,
+    },
+    Smtp {
+        relay: String,
+        from: String,
+        to: Vec<String>,
+        #[serde(default = "default_subject")]
+        subject: String,
+        #[serde(default = "default_timeout_ms")]
+        timeout_ms: u64,
+    },
+}
+
+fn default_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_subject() -> String {
+    "Smart Home".to_string()
+}
+
+/// Получатель оповещений в конфигурации: вид, фильтр событий и повторы
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SinkConfig {
+    #[serde(flatten)]
+    pub kind: SinkKind,
+    #[serde(default)]
+    pub filter: EventFilter,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+}
+
+impl SinkConfig {
+    /// создать получателя по описанию
+    pub fn build(&self) -> Result<Box<dyn NotificationSink>, NotifyError> {
+        Ok(match &self.kind {
+            SinkKind::LogFile { path } => Box::new(LogFileSink::new(path.clone())),
+            SinkKind::Webhook { url, timeout_ms } => {
+                Box::new(WebhookSink::new(url, Duration::from_millis(*timeout_ms))?)
+            }
+            SinkKind::Command {
+                program,
+                args,
+                timeout_ms,
+            } => Box::new(CommandSink::new(
+                program,
+                args,
+                Duration::from_millis(*timeout_ms),
+            )),
+            SinkKind::Smtp {
+                relay,
+                from,
+                to,
+                subject,
+                timeout_ms,
+            } => Box::new(SmtpSink::new(
+                relay,
+                from,
+                to,
+                subject,
+                Duration::from_millis(*timeout_ms),
+            )),
+        })
+    }
+}
+
+/// Получатель в рассылке; доставка идет в отдельном потоке получателя,
+/// чтобы медленный или недоступный получатель не задерживал дом
+struct SinkEntry {
+    name: String,
+    filter: EventFilter,
+    retry: RetryPolicy,
+    jobs: Sender<Vec<Queued>>,
+    /// оповещения переданы потоку доставки и еще не вернулись
+    busy: bool,
+}
+
+/// Оповещение, ожидающее доставки
+struct Queued {
+    sink: usize,
+    event: HomeEvent,
+    attempts: u32,
+    next_try: Option<NaiveDateTime>,
+    /// когда передано потоку доставки, от этого момента отсчитывается пауза до повтора
+    sent_at: NaiveDateTime,
+}
+
+/// Итог доставки одного оповещения
+enum Outcome {
+    Delivered,
+    Failed(NotifyError),
+    /// не отправлялось: предыдущее оповещение этому получателю не доставлено
+    Skipped,
+}
+
+/// Ответ потока доставки на переданные ему оповещения
+struct Report {
+    sink: usize,
+    results: Vec<(Queued, Outcome)>,
+}
+
+/// Рассылка событий дома получателям оповещений.
+/// События ставятся в очередь, на тике дома подошедшие оповещения передаются потокам доставки,
+/// а их результаты забираются на следующих тиках.
+pub struct Notifier {
+    sinks: Vec<SinkEntry>,
+    queue: VecDeque<Queued>,
+    /// оповещений у потоков доставки
+    in_flight: usize,
+    reports: Receiver<Report>,
+    reporter: Sender<Report>,
+    delivered: u64,
+    failed: u64,
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        let (reporter, reports) = channel();
+        Self {
+            sinks: vec![],
+            queue: VecDeque::new(),
+            in_flight: 0,
+            reports,
+            reporter,
+            delivered: 0,
+            failed: 0,
+        }
+    }
+}
+
+/// доставлять оповещения по очереди; после неудачи остальные не отправлять до повтора
+fn deliver_jobs(
+    index: usize,
+    mut sink: Box<dyn NotificationSink>,
+    jobs: Receiver<Vec<Queued>>,
+    reporter: Sender<Report>,
+) {
+    for batch in jobs {
+        let mut failed = false;
+        let results = batch
+            .into_iter()
+            .map(|queued| {
+                if failed {
+                    return (queued, Outcome::Skipped);
+                }
+                match sink.deliver(&queued.event) {
+                    Ok(()) => (queued, Outcome::Delivered),
+                    Err(e) => {
+                        failed = true;
+                        (queued, Outcome::Failed(e))
+                    }
+                }
+            })
+            .collect();
+        if reporter
+            .send(Report {
+                sink: index,
+                results,
+            })
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+impl Notifier {
+    /// добавить получателя оповещений
+    pub fn add_sink(
+        &mut self,
+        sink: Box<dyn NotificationSink>,
+        filter: EventFilter,
+        retry: RetryPolicy,
+    ) {
+        let index = self.sinks.len();
+        let name = sink.name();
+        let (jobs, receiver) = channel();
+        let reporter = self.reporter.clone();
+        thread::spawn(move || deliver_jobs(index, sink, receiver, reporter));
+
+        self.sinks.push(SinkEntry {
+            name,
+            filter,
+            retry,
+            jobs,
+            busy: false,
+        });
+    }
+
+    /// добавить получателей из json-файла конфигурации
+    pub fn load_file(&mut self, path: &Path) -> SmartHomeResult<usize> {
+        let configs: Vec<SinkConfig> = load_json(path)?;
+        for config in configs.iter() {
+            let sink = config
+                .build()
+                .map_err(|e| SmartHomeError::Storage(format!("{}: {e}", path.display())))?;
+            self.add_sink(sink, config.filter.clone(), config.retry.clone());
+        }
+        Ok(configs.len())
+    }
+
+    pub fn sinks(&self) -> Vec<String> {
+        self.sinks.iter().map(|s| s.name.clone()).collect()
+    }
+
+    /// поставить событие в очередь всем получателям, которым оно подходит
+    pub fn notify(&mut self, event: &HomeEvent) {
+        for (index, entry) in self.sinks.iter().enumerate() {
+            if !entry.filter.matches(event) {
+                continue;
+            }
+            if self.queued() >= MAX_QUEUED {
+                self.failed += 1;
+                // если все ожидающие уже у потоков доставки, отбрасывается новое
+                if self.queue.pop_front().is_none() {
+                    continue;
+                }
+            }
+            self.queue.push_back(Queued {
+                sink: index,
+                event: event.clone(),
+                attempts: 0,
+                next_try: None,
+                sent_at: NaiveDateTime::default(),
+            });
+        }
+    }
+
+    /// учесть результаты доставки и передать потокам доставки оповещения, время которых пришло;
+    /// не ждет завершения доставки
+    pub fn process(&mut self, now: NaiveDateTime) {
+        while let Ok(report) = self.reports.try_recv() {
+            self.settle(report);
+        }
+
+        let mut batches: Vec<Vec<Queued>> = self.sinks.iter().map(|_| vec![]).collect();
+        let mut waiting = VecDeque::new();
+        while let Some(mut queued) = self.queue.pop_front() {
+            let due = queued.next_try.is_none_or(|next| next <= now);
+            // получателю, занятому предыдущей доставкой, передадим позже
+            if !due || self.sinks[queued.sink].busy {
+                waiting.push_back(queued);
+                continue;
+            }
+            queued.sent_at = now;
+            batches[queued.sink].push(queued);
+        }
+        self.queue = waiting;
+
+        for (entry, batch) in self.sinks.iter_mut().zip(batches) {
+            if batch.is_empty() {
+                continue;
+            }
+            self.in_flight += batch.len();
+            entry.busy = true;
+            if let Err(e) = entry.jobs.send(batch) {
+                // поток доставки завершился аварийно
+                warn!(sink = entry.name.as_str(); "notification sink is gone");
+                self.in_flight -= e.0.len();
+                self.failed += e.0.len() as u64;
+                entry.busy = false;
+            }
+        }
+    }
+
+    /// дождаться результатов всех начатых доставок, но не дольше `timeout`;
+    /// возвращает false, если какие-то доставки еще идут
+    pub fn wait_idle(&mut self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.in_flight > 0 {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.reports.recv_timeout(left) {
+                Ok(report) => self.settle(report),
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => {
+                    return false
+                }
+            }
+        }
+        true
+    }
+
+    /// учесть результаты доставки; неудачные - повторить позже
+    fn settle(&mut self, report: Report) {
+        let entry = &mut self.sinks[report.sink];
+        entry.busy = false;
+        self.in_flight -= report.results.len();
+
+        // неотправленные возвращаются в начало очереди в прежнем порядке
+        let mut returned = VecDeque::new();
+        for (mut queued, outcome) in report.results {
+            match outcome {
+                Outcome::Delivered => self.delivered += 1,
+                Outcome::Skipped => returned.push_back(queued),
+                Outcome::Failed(e) => {
+                    queued.attempts += 1;
+                    if queued.attempts >= entry.retry.max_attempts {
+                        warn!(sink = entry.name.as_str(), error:% = e; "notification dropped");
+                        self.failed += 1;
+                        continue;
+                    }
+                    let delay = entry.retry.delay_seconds << (queued.attempts - 1).min(16);
+                    let next_try = TimeDelta::try_seconds(delay)
+                        .and_then(|delay| queued.sent_at.checked_add_signed(delay));
+                    match next_try {
+                        Some(next_try) => {
+                            queued.next_try = Some(next_try);
+                            returned.push_back(queued);
+                        }
+                        None => self.failed += 1,
+                    }
+                }
+            }
+        }
+        returned.append(&mut self.queue);
+        self.queue = returned;
+    }
+
+    /// оповещений в очереди, включая переданные потокам доставки
+    pub fn queued(&self) -> usize {
+        self.queue.len() + self.in_flight
+    }
+
+    /// доставлено оповещений
+    pub fn delivered(&self) -> u64 {
+        self.delivered
+    }
+
+    /// оповещений, которые так и не удалось доставить
+    pub fn failed(&self) -> u64 {
+        self.failed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Notifier, RetryPolicy, SinkConfig};
+    use crate::events::event::HomeEvent;
+    use crate::my_smart_home::home::Home;
+    use crate::my_smart_home::smart_home::SmartHome;
+    use crate::notify::filter::EventFilter;
+    use crate::notify::sink::{NotificationSink, NotifyError};
+    use crate::smart_device::socket::Socket;
+    use chrono::{NaiveDate, TimeDelta};
+    use serde_json::json;
+    use std::env;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread::{self, JoinHandle};
+    use std::time::{Duration, Instant};
+
+    /// получатель, который отказывает заданное число раз
+    struct Flaky {
+        failures: u32,
+        delivered: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl NotificationSink for Flaky {
+        fn name(&self) -> String {
+            "flaky".into()
+        }
+
+        fn deliver(&mut self, event: &HomeEvent) -> Result<(), NotifyError> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(NotifyError::Rejected("busy".into()));
+            }
+            self.delivered
+                .lock()
+                .unwrap()
+                .push(event.kind().to_string());
+            Ok(())
+        }
+    }
+
+    fn rule_fired() -> HomeEvent {
+        HomeEvent::RuleFired {
+            rule: "night".into(),
+            outcomes: vec![],
+        }
+    }
+
+    #[test]
+    fn test_retry_with_backoff() {
+        let now = NaiveDate::from_ymd_opt(2025, 1, 10)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let delivered = Arc::new(Mutex::new(vec![]));
+        let mut notifier = Notifier::default();
+        let sink = Flaky {
+            failures: 2,
+            delivered: delivered.clone(),
+        };
+        let retry = RetryPolicy {
+            max_attempts: 3,
+            delay_seconds: 10,
+        };
+        notifier.add_sink(Box::new(sink), EventFilter::default(), retry.clone());
+
+        let process = |notifier: &mut Notifier, sec| {
+            notifier.process(now + TimeDelta::seconds(sec));
+            assert!(notifier.wait_idle(Duration::from_secs(5)));
+        };
+
+        notifier.notify(&rule_fired());
+        process(&mut notifier, 0);
+        assert_eq!(notifier.queued(), 1);
+
+        // повтор через 10 секунд, затем через 20
+        process(&mut notifier, 5);
+        assert_eq!(notifier.queued(), 1);
+        process(&mut notifier, 10);
+        process(&mut notifier, 25);
+        assert_eq!(notifier.queued(), 1);
+        process(&mut notifier, 30);
+        assert_eq!(notifier.queued(), 0);
+        assert_eq!(notifier.delivered(), 1);
+        assert_eq!(*delivered.lock().unwrap(), vec!["rule_fired"]);
+
+        // отказывает чаще, чем разрешено попыток
+        let sink = Flaky {
+            failures: 5,
+            delivered: delivered.clone(),
+        };
+        let mut notifier = Notifier::default();
+        notifier.add_sink(Box::new(sink), EventFilter::default(), retry);
+        notifier.notify(&rule_fired());
+        for sec in [0, 10, 30] {
+            process(&mut notifier, sec);
+        }
+        assert_eq!(notifier.queued(), 0);
+        assert_eq!(notifier.failed(), 1);
+    }
+
+    /// получатель, который долго не отвечает
+    struct Stuck;
+
+    impl NotificationSink for Stuck {
+        fn name(&self) -> String {
+            "stuck".into()
+        }
+
+        fn deliver(&mut self, _event: &HomeEvent) -> Result<(), NotifyError> {
+            thread::sleep(Duration::from_millis(500));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_slow_sink_does_not_block() {
+        let now = NaiveDate::from_ymd_opt(2025, 1, 10)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let mut notifier = Notifier::default();
+        notifier.add_sink(
+            Box::new(Stuck),
+            EventFilter::default(),
+            RetryPolicy::default(),
+        );
+        for _ in 0..5 {
+            notifier.notify(&rule_fired());
+        }
+
+        let started = Instant::now();
+        notifier.process(now);
+        notifier.process(now);
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert_eq!(notifier.queued(), 5);
+        assert_eq!(notifier.delivered(), 0);
+
+        assert!(!notifier.wait_idle(Duration::from_millis(100)));
+        assert!(notifier.wait_idle(Duration::from_secs(30)));
+        assert_eq!(notifier.delivered(), 5);
+        assert_eq!(notifier.queued(), 0);
+    }
+
+    /// сервер, который принимает одно соединение, читает первую строку и отказывает
+    fn refusing_server(greeting: bool, reply: &'static str) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            if greeting {
+                // SMTP: сервер говорит первым
+                write!(reader.get_mut(), "{reply}\r\n").unwrap();
+            } else {
+                reader.read_line(&mut line).unwrap();
+                write!(reader.get_mut(), "{reply}\r\n\r\n").unwrap();
+            }
+            line
+        });
+        (addr, server)
+    }
+
+    #[test]
+    fn test_sinks_from_config() {
+        let dir = env::temp_dir().join(format!("smart_home_notify_{}", std::process::id()));
+        let log = dir.join("events.log");
+        let out = dir.join("command.json");
+        let (http, webhook) = refusing_server(false, "HTTP/1.1 503 Service Unavailable");
+        let (relay, smtp) = refusing_server(true, "554 no service");
+        let url = format!("http://{http}/hook");
+
+        let configs: Vec<SinkConfig> = serde_json::from_value(json!([
+            {"type": "log_file", "path": log, "filter": {"events": ["rule_fired"]}},
+            {"type": "command", "program": "sh", "args": ["-c", format!("cat > {}", out.display())]},
+            {"type": "webhook", "url": url, "retry": {"max_attempts": 1, "delay_seconds": 1}},
+            {"type": "smtp", "relay": relay, "from": "home@home.local", "to": ["me@home.local"]}
+        ]))
+        .unwrap();
+        let path = dir.join("notifications.json");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, serde_json::to_string(&configs).unwrap()).unwrap();
+
+        let mut notifier = Notifier::default();
+        assert_eq!(notifier.load_file(&path).unwrap(), 4);
+        assert_eq!(notifier.sinks()[2], format!("webhook {url}"));
+
+        let fault = HomeEvent::FaultDetected {
+            room: "bedroom".into(),
+            device: "socket-3".into(),
+            fault: None,
+        };
+        notifier.notify(&rule_fired());
+        notifier.notify(&fault);
+        // в журнал только rule_fired, остальным - оба события
+        assert_eq!(notifier.queued(), 7);
+
+        let now = NaiveDate::from_ymd_opt(2025, 1, 10)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        notifier.process(now);
+        assert!(notifier.wait_idle(Duration::from_secs(10)));
+
+        let logged = std::fs::read_to_string(&log).unwrap();
+        assert_eq!(logged.lines().count(), 1);
+        assert!(logged.contains("\"rule_fired\""));
+        let piped = std::fs::read_to_string(&out).unwrap();
+        assert!(piped.contains("\"fault_detected\""));
+        // после отказа остальные оповещения ждут повтора
+        assert_eq!(webhook.join().unwrap(), "POST /hook HTTP/1.1\r\n");
+        smtp.join().unwrap();
+        assert_eq!(notifier.delivered(), 3);
+        assert_eq!(notifier.failed(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_home_delivers_events_on_tick() {
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.add_room("bedroom".into(), vec![Box::new(Socket::new("3"))])
+            .unwrap();
+        let delivered = Arc::new(Mutex::new(vec![]));
+        let sink = Flaky {
+            failures: 0,
+            delivered: delivered.clone(),
+        };
+        let faults = EventFilter {
+            events: vec!["fault_detected".into()],
+            ..Default::default()
+        };
+        home.notifier
+            .add_sink(Box::new(sink), faults, RetryPolicy::default());
+
+        home.tick();
+        home.with_device("bedroom", "socket-3", |dev| dev.switch("broken"))
+            .unwrap();
+        assert!(delivered.lock().unwrap().is_empty());
+
+        home.tick();
+        assert!(home.notifier.wait_idle(Duration::from_secs(5)));
+        assert_eq!(*delivered.lock().unwrap(), vec!["fault_detected"]);
+    }
+}
@@ -0,0 +1,66 @@
+use crate::events::event::HomeEvent;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, thiserror::Error)]
+pub enum NotifyError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("delivery rejected: {0}")]
+    Rejected(String),
+}
+
+/// Получатель оповещений о событиях дома
+pub trait NotificationSink: Send {
+    /// имя получателя для журналов и ошибок
+    fn name(&self) -> String;
+
+    /// доставить событие; при ошибке доставка будет повторена
+    fn deliver(&mut self, event: &HomeEvent) -> Result<(), NotifyError>;
+}
+
+/// Соединение со сроком на всю попытку доставки: таймаут каждого чтения и записи
+/// сокращается до оставшегося времени, так что медленный собеседник не затянет попытку
+pub struct TimedStream {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl TimedStream {
+    pub fn new(stream: TcpStream, timeout: Duration) -> Self {
+        Self {
+            stream,
+            deadline: Instant::now() + timeout,
+        }
+    }
+
+    fn remaining(&self) -> io::Result<Duration> {
+        match self.deadline.saturating_duration_since(Instant::now()) {
+            Duration::ZERO => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "delivery timed out",
+            )),
+            left => Ok(left),
+        }
+    }
+}
+
+impl Read for TimedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.set_read_timeout(Some(self.remaining()?))?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for TimedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.set_write_timeout(Some(self.remaining()?))?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
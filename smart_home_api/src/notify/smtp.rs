@@ -0,0 +1,184 @@
+use super::sink::{NotificationSink, NotifyError, TimedStream};
+use crate::events::event::HomeEvent;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Оповещение письмом через SMTP-релей без авторизации, например локальный
+pub struct SmtpSink {
+    relay: String,
+    from: String,
+    to: Vec<String>,
+    subject: String,
+    /// время на весь диалог с релеем
+    timeout: Duration,
+}
+
+impl SmtpSink {
+    /// `relay` - адрес "host:port"
+    pub fn new(relay: &str, from: &str, to: &[String], subject: &str, timeout: Duration) -> Self {
+        Self {
+            relay: relay.to_string(),
+            from: from.to_string(),
+            to: to.to_vec(),
+            subject: subject.to_string(),
+            timeout,
+        }
+    }
+}
+
+/// SMTP-сессия с релеем
+struct Session {
+    reader: BufReader<TimedStream>,
+}
+
+impl Session {
+    /// прочитать ответ сервера (в том числе многострочный) и проверить код
+    fn expect(&mut self, class: char) -> Result<(), NotifyError> {
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(NotifyError::Rejected("connection closed".into()));
+            }
+            if !line.starts_with(class) {
+                return Err(NotifyError::Rejected(line.trim().to_string()));
+            }
+            // "250-..." - продолжение ответа, "250 ..." - последняя строка
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+
+    fn command(&mut self, command: &str, class: char) -> Result<(), NotifyError> {
+        write!(self.reader.get_mut(), "{command}\r\n")?;
+        self.expect(class)
+    }
+}
+
+impl NotificationSink for SmtpSink {
+    fn name(&self) -> String {
+        format!("smtp {}", self.relay)
+    }
+
+    fn deliver(&mut self, event: &HomeEvent) -> Result<(), NotifyError> {
+        let body = serde_json::to_string_pretty(event)
+            .map_err(|e| NotifyError::Rejected(e.to_string()))?;
+
+        let Some(addr) = self.relay.to_socket_addrs()?.next() else {
+            return Err(NotifyError::Rejected(format!(
+                "can't resolve {}",
+                self.relay
+            )));
+        };
+        let stream = TcpStream::connect_timeout(&addr, self.timeout)?;
+        let mut session = Session {
+            reader: BufReader::new(TimedStream::new(stream, self.timeout)),
+        };
+
+        session.expect('2')?;
+        session.command("HELO smart-home", '2')?;
+        session.command(&format!("MAIL FROM:<{}>", self.from), '2')?;
+        for to in &self.to {
+            session.command(&format!("RCPT TO:<{to}>"), '2')?;
+        }
+        session.command("DATA", '3')?;
+
+        let mut message = format!(
+            "From: <{}>\r\nTo: {}\r\nSubject: {}: {}\r\nContent-Type: application/json\r\n\r\n",
+            self.from,
+            self.to
+                .iter()
+                .map(|to| format!("<{to}>"))
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.subject,
+            event.kind()
+        );
+        for line in body.lines() {
+            // строки, начинающиеся с точки, удваиваются (RFC 5321, 4.5.2)
+            if line.starts_with('.') {
+                message.push('.');
+            }
+            message += line;
+            message += "\r\n";
+        }
+        message += ".";
+        session.command(&message, '2')?;
+        session.command("QUIT", '2')
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SmtpSink;
+    use crate::events::event::HomeEvent;
+    use crate::notify::sink::NotificationSink;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_smtp_dialog() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let relay = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut lines = vec![];
+            write!(reader.get_mut(), "220 relay ready\r\n").unwrap();
+            let mut in_data = false;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                let line = line.trim_end().to_string();
+                let reply = match line.as_str() {
+                    "." if in_data => {
+                        in_data = false;
+                        "250 queued"
+                    }
+                    _ if in_data => "",
+                    "DATA" => {
+                        in_data = true;
+                        "354 go ahead"
+                    }
+                    "QUIT" => "221 bye",
+                    l if l.starts_with("HELO") => "250-relay\r\n250 ok",
+                    _ => "250 ok",
+                };
+                lines.push(line);
+                if !reply.is_empty() {
+                    write!(reader.get_mut(), "{reply}\r\n").unwrap();
+                }
+                if reply.starts_with("221") {
+                    break;
+                }
+            }
+            lines
+        });
+
+        let to = vec!["owner@home.local".to_string()];
+        let mut sink = SmtpSink::new(
+            &relay,
+            "home@home.local",
+            &to,
+            "MyHome",
+            Duration::from_secs(5),
+        );
+        let event = HomeEvent::FaultDetected {
+            room: "bedroom".into(),
+            device: "socket-3".into(),
+            fault: None,
+        };
+        sink.deliver(&event).unwrap();
+
+        let lines = server.join().unwrap();
+        assert_eq!(lines[1], "MAIL FROM:<home@home.local>");
+        assert_eq!(lines[2], "RCPT TO:<owner@home.local>");
+        assert!(lines.contains(&"Subject: MyHome: fault_detected".to_string()));
+        assert_eq!(lines.last().unwrap(), "QUIT");
+    }
+}
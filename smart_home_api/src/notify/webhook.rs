@@ -0,0 +1,146 @@
+use super::sink::{NotificationSink, NotifyError, TimedStream};
+use crate::events::event::HomeEvent;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// самая длинная строка статуса в ответе
+const MAX_STATUS_LINE: u64 = 1024;
+
+/// Оповещение POST-запросом с json-событием на http-адрес, например локальный
+pub struct WebhookSink {
+    host: String,
+    path: String,
+    /// время на всю попытку: соединение, запрос и строку статуса ответа
+    timeout: Duration,
+}
+
+impl WebhookSink {
+    /// `url` вида "http://host:port/path"; https не поддерживается
+    pub fn new(url: &str, timeout: Duration) -> Result<Self, NotifyError> {
+        let Some(rest) = url.strip_prefix("http://") else {
+            return Err(NotifyError::Rejected(format!("unsupported url: {url}")));
+        };
+        let (host, path) = match rest.find('/') {
+            Some(pos) => (&rest[..pos], &rest[pos..]),
+            None => (rest, "/"),
+        };
+        let host = match host.contains(':') {
+            true => host.to_string(),
+            false => format!("{host}:80"),
+        };
+        Ok(Self {
+            host,
+            path: path.to_string(),
+            timeout,
+        })
+    }
+}
+
+impl NotificationSink for WebhookSink {
+    fn name(&self) -> String {
+        format!("webhook http://{}{}", self.host, self.path)
+    }
+
+    fn deliver(&mut self, event: &HomeEvent) -> Result<(), NotifyError> {
+        let body =
+            serde_json::to_string(event).map_err(|e| NotifyError::Rejected(e.to_string()))?;
+
+        let Some(addr) = self.host.to_socket_addrs()?.next() else {
+            return Err(NotifyError::Rejected(format!(
+                "can't resolve {}",
+                self.host
+            )));
+        };
+        let mut stream = TimedStream::new(
+            TcpStream::connect_timeout(&addr, self.timeout)?,
+            self.timeout,
+        );
+
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.host,
+            body.len(),
+            body
+        )?;
+        stream.flush()?;
+
+        let mut status = String::new();
+        BufReader::new(stream.take(MAX_STATUS_LINE)).read_line(&mut status)?;
+        // "HTTP/1.1 200 OK"
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(NotifyError::Rejected(format!(
+                "unexpected response: {}",
+                status.trim()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::WebhookSink;
+    use crate::events::event::HomeEvent;
+    use crate::notify::sink::NotificationSink;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_webhook_posts_event() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let mut replies = ["HTTP/1.1 204 No Content", "HTTP/1.1 500 Oops"].into_iter();
+            let mut bodies = vec![];
+            for stream in listener.incoming().take(2) {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut head = String::new();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.strip_prefix("Content-Length: ") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                    head += &line;
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push((head, String::from_utf8(body).unwrap()));
+
+                let reply = replies.next().unwrap();
+                write!(reader.get_mut(), "{reply}\r\nContent-Length: 0\r\n\r\n").unwrap();
+            }
+            bodies
+        });
+
+        let url = format!("http://127.0.0.1:{port}/hooks/home");
+        let mut sink = WebhookSink::new(&url, Duration::from_secs(5)).unwrap();
+        let event = HomeEvent::RuleFired {
+            rule: "night".into(),
+            outcomes: vec![],
+        };
+        assert!(sink.deliver(&event).is_ok());
+        assert!(sink.deliver(&event).is_err());
+
+        let bodies = server.join().unwrap();
+        assert!(bodies[0].0.starts_with("POST /hooks/home HTTP/1.1"));
+        assert!(bodies[0].1.contains("\"event\":\"rule_fired\""));
+    }
+
+    #[test]
+    fn test_webhook_url() {
+        assert!(WebhookSink::new("https://example.com", Duration::from_secs(1)).is_err());
+        let sink = WebhookSink::new("http://localhost", Duration::from_secs(1)).unwrap();
+        assert_eq!(sink.name(), "webhook http://localhost:80/");
+    }
+}
@@ -0,0 +1,35 @@
+use crate::smart_device::device::{DeviceState, Readings};
+use serde::{Deserialize, Serialize};
+
+/// Целевое состояние одного устройства в сцене
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SceneEntry {
+    pub room: String,
+    /// идентификатор или имя устройства
+    pub device: String,
+    /// статус, который нужно установить, None - не менять
+    #[serde(default)]
+    pub state: Option<DeviceState>,
+    /// параметры устройства, которые нужно установить
+    #[serde(default)]
+    pub readings: Readings,
+}
+
+/// Сцена: именованный набор целевых состояний устройств в разных комнатах
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    pub entries: Vec<SceneEntry>,
+}
+
+/// Результат применения сцены к одному устройству
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SceneOutcome {
+    pub room: String,
+    pub device: String,
+    /// все целевые значения установлены
+    pub ok: bool,
+    pub result: String,
+}
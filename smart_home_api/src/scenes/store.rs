@@ -0,0 +1,67 @@
+use super::scene::Scene;
+use crate::my_smart_home::error::{SmartHomeError, SmartHomeResult};
+use crate::my_smart_home::location::{move_device_ref, relocate_ref};
+use crate::my_smart_home::storage::{load_json, save_json};
+use std::path::Path;
+
+/// Хранилище сцен дома
+#[derive(Default)]
+pub struct SceneStore {
+    scenes: Vec<Scene>,
+}
+
+impl SceneStore {
+    pub fn scenes(&self) -> &[Scene] {
+        &self.scenes
+    }
+
+    pub fn get(&self, id: &str) -> SmartHomeResult<&Scene> {
+        self.scenes
+            .iter()
+            .find(|s| s.id == id)
+            .ok_or_else(|| SmartHomeError::SceneNonExist(id.to_string()))
+    }
+
+    pub fn add(&mut self, scene: Scene) -> SmartHomeResult<()> {
+        if self.scenes.iter().any(|s| s.id == scene.id) {
+            return Err(SmartHomeError::SceneSameIdExist(scene.id));
+        }
+        self.scenes.push(scene);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: &str) -> SmartHomeResult<Scene> {
+        match self.scenes.iter().position(|s| s.id == id) {
+            Some(pos) => Ok(self.scenes.remove(pos)),
+            None => Err(SmartHomeError::SceneNonExist(id.to_string())),
+        }
+    }
+
+    /// комната переименована вместе с вложенными
+    pub fn relocate_room(&mut self, from: &str, to: &str) {
+        for entry in self.scenes.iter_mut().flat_map(|s| s.entries.iter_mut()) {
+            relocate_ref(&mut entry.room, from, to);
+        }
+    }
+
+    /// устройство с идентификатором или именем из `names` перенесено из `room` в `to`
+    pub fn move_device(&mut self, room: &str, names: &[&str], to: &str) {
+        for entry in self.scenes.iter_mut().flat_map(|s| s.entries.iter_mut()) {
+            move_device_ref(&mut entry.room, &entry.device, room, names, to);
+        }
+    }
+
+    /// загрузить сцены из json-файла
+    pub fn load_file(&mut self, path: &Path) -> SmartHomeResult<usize> {
+        let scenes: Vec<Scene> = load_json(path)?;
+        let count = scenes.len();
+        for scene in scenes {
+            self.add(scene)?;
+        }
+        Ok(count)
+    }
+
+    pub fn save_file(&self, path: &Path) -> SmartHomeResult<()> {
+        save_json(path, &self.scenes)
+    }
+}
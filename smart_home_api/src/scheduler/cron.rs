@@ -0,0 +1,169 @@
+use chrono::{DateTime, Datelike, NaiveDateTime, TimeDelta, Timelike};
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+/// Cron-выражение из пяти полей: минута, час, день месяца, месяц, день недели.
+/// Поддерживаются `*`, числа, диапазоны `a-b`, шаг `*/n` и `a-b/n`, списки через запятую.
+/// День недели: 0-7, воскресенье - 0 или 7.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CronExpr {
+    minutes: BTreeSet<u32>,
+    hours: BTreeSet<u32>,
+    days: BTreeSet<u32>,
+    months: BTreeSet<u32>,
+    weekdays: BTreeSet<u32>,
+    /// день месяца и день недели заданы оба: достаточно совпадения любого
+    days_or_weekdays: bool,
+}
+
+/// сколько дней вперед искать ближайший запуск (покрывает 29 февраля)
+const SEARCH_DAYS: i64 = 366 * 8;
+
+impl CronExpr {
+    /// ближайший момент строго после `after`, с точностью до минуты
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        let mut day = start.date();
+        let last_day = day + TimeDelta::days(SEARCH_DAYS);
+
+        while day <= last_day {
+            if self.matches_day(day) {
+                for hour in self.hours.iter() {
+                    for minute in self.minutes.iter() {
+                        let moment = day.and_hms_opt(*hour, *minute, 0)?;
+                        if moment >= start {
+                            return Some(moment);
+                        }
+                    }
+                }
+            }
+            day = day.succ_opt()?;
+        }
+        None
+    }
+
+    /// есть ли хотя бы один момент запуска, например "0 0 30 2 *" не срабатывает никогда
+    pub fn can_fire(&self) -> bool {
+        // окно поиска в восемь лет содержит любую допустимую дату, включая 29 февраля
+        self.next_after(DateTime::UNIX_EPOCH.naive_utc()).is_some()
+    }
+
+    fn matches_day(&self, day: chrono::NaiveDate) -> bool {
+        if !self.months.contains(&day.month()) {
+            return false;
+        }
+        let by_day = self.days.contains(&day.day());
+        let by_weekday = self
+            .weekdays
+            .contains(&day.weekday().num_days_from_sunday());
+        match self.days_or_weekdays {
+            true => by_day || by_weekday,
+            false => by_day && by_weekday,
+        }
+    }
+}
+
+impl FromStr for CronExpr {
+    type Err = String;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!("cron expression must have 5 fields: {expr:?}"));
+        }
+
+        let mut weekdays = parse_field(fields[4], 0, 7)?;
+        if weekdays.remove(&7) {
+            weekdays.insert(0);
+        }
+
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays,
+            days_or_weekdays: fields[2] != "*" && fields[4] != "*",
+        })
+    }
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<BTreeSet<u32>, String> {
+    let mut values = BTreeSet::new();
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, parse_num(step)?),
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (parse_num(from)?, parse_num(to)?),
+                None => {
+                    let value = parse_num(range)?;
+                    (value, value)
+                }
+            },
+        };
+        if from < min || to > max || from > to || step == 0 {
+            return Err(format!("cron field out of range {min}-{max}: {part:?}"));
+        }
+        values.extend((from..=to).step_by(step as usize));
+    }
+    Ok(values)
+}
+
+fn parse_num(value: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("wrong cron value: {value:?}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::CronExpr;
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_daily() {
+        let cron: CronExpr = "0 23 * * *".parse().unwrap();
+        assert_eq!(cron.next_after(at(10, 22, 15)), Some(at(10, 23, 0)));
+        assert_eq!(cron.next_after(at(10, 23, 0)), Some(at(11, 23, 0)));
+    }
+
+    #[test]
+    fn test_steps_lists_and_weekdays() {
+        let cron: CronExpr = "*/15 8-9 * * *".parse().unwrap();
+        assert_eq!(cron.next_after(at(10, 8, 50)), Some(at(10, 9, 0)));
+        assert_eq!(cron.next_after(at(10, 9, 45)), Some(at(11, 8, 0)));
+
+        // 2025-01-10 - пятница, ближайший понедельник или среда - 13 января
+        let cron: CronExpr = "30 7 * * 1,3".parse().unwrap();
+        assert_eq!(cron.next_after(at(10, 12, 0)), Some(at(13, 7, 30)));
+
+        // воскресенье можно задать как 7
+        let cron: CronExpr = "0 12 * * 7".parse().unwrap();
+        assert_eq!(cron.next_after(at(10, 12, 0)), Some(at(12, 12, 0)));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!("0 23 * *".parse::<CronExpr>().is_err());
+        assert!("60 * * * *".parse::<CronExpr>().is_err());
+        assert!("*/0 * * * *".parse::<CronExpr>().is_err());
+        assert!("a * * * *".parse::<CronExpr>().is_err());
+
+        let never: CronExpr = "0 0 30 2 *".parse().unwrap();
+        assert!(!never.can_fire());
+        let leap: CronExpr = "0 0 29 2 *".parse().unwrap();
+        assert!(leap.can_fire());
+    }
+}
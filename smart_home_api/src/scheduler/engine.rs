@@ -0,0 +1,278 @@
+use super::schedule::Schedule;
+use crate::command::device_command::DeviceCommand;
+use crate::my_smart_home::error::{SmartHomeError, SmartHomeResult};
+use crate::my_smart_home::storage::{load_json, save_json};
+use chrono::NaiveDateTime;
+use std::path::Path;
+
+/// Расписание, время которого наступило
+#[derive(Debug, PartialEq)]
+pub struct DueSchedule {
+    pub schedule: String,
+    pub actions: Vec<DeviceCommand>,
+}
+
+/// Планировщик команд устройствам по времени
+#[derive(Default)]
+pub struct Scheduler {
+    schedules: Vec<Schedule>,
+}
+
+impl Scheduler {
+    pub fn schedules(&self) -> &[Schedule] {
+        &self.schedules
+    }
+
+    /// добавить расписание, время первого запуска отсчитывается от `now`
+    pub fn add(&mut self, mut schedule: Schedule, now: NaiveDateTime) -> SmartHomeResult<()> {
+        if self.schedules.iter().any(|s| s.id == schedule.id) {
+            return Err(SmartHomeError::ScheduleSameIdExist(schedule.id));
+        }
+        schedule
+            .when
+            .validate()
+            .map_err(|reason| SmartHomeError::InvalidSchedule {
+                id: schedule.id.clone(),
+                reason,
+            })?;
+        if schedule.next_run.is_none() && schedule.last_run.is_none() {
+            schedule.next_run = schedule.when.first_run(now);
+            if schedule.next_run.is_none() {
+                return Err(SmartHomeError::InvalidSchedule {
+                    id: schedule.id,
+                    reason: "schedule never runs".into(),
+                });
+            }
+        }
+        self.schedules.push(schedule);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: &str) -> SmartHomeResult<Schedule> {
+        match self.schedules.iter().position(|s| s.id == id) {
+            Some(pos) => Ok(self.schedules.remove(pos)),
+            None => Err(SmartHomeError::ScheduleNonExist(id.to_string())),
+        }
+    }
+
+    pub fn set_enabled(&mut self, id: &str, enabled: bool) -> SmartHomeResult<()> {
+        match self.schedules.iter_mut().find(|s| s.id == id) {
+            Some(schedule) => {
+                schedule.enabled = enabled;
+                Ok(())
+            }
+            None => Err(SmartHomeError::ScheduleNonExist(id.to_string())),
+        }
+    }
+
+    /// комната переименована вместе с вложенными
+    pub fn relocate_room(&mut self, from: &str, to: &str) {
+        for action in self.schedules.iter_mut().flat_map(|s| s.actions.iter_mut()) {
+            action.relocate_room(from, to);
+        }
+    }
+
+    /// устройство с идентификатором или именем из `names` перенесено из `room` в `to`
+    pub fn move_device(&mut self, room: &str, names: &[&str], to: &str) {
+        for action in self.schedules.iter_mut().flat_map(|s| s.actions.iter_mut()) {
+            action.move_device(room, names, to);
+        }
+    }
+
+    /// расписания, время которых наступило к `now`; время следующего запуска сдвигается
+    pub fn due(&mut self, now: NaiveDateTime) -> Vec<DueSchedule> {
+        let mut due = vec![];
+
+        for schedule in self.schedules.iter_mut().filter(|s| s.enabled) {
+            let Some(planned) = schedule.next_run else {
+                continue;
+            };
+            if planned > now {
+                continue;
+            }
+            schedule.last_run = Some(now);
+            schedule.next_run = schedule.when.next_run(planned, now);
+            due.push(DueSchedule {
+                schedule: schedule.id.clone(),
+                actions: schedule.actions.clone(),
+            });
+        }
+
+        due
+    }
+
+    /// загрузить расписания из json-файла
+    pub fn load_file(&mut self, path: &Path, now: NaiveDateTime) -> SmartHomeResult<usize> {
+        let schedules: Vec<Schedule> = load_json(path)?;
+        let count = schedules.len();
+        for schedule in schedules {
+            self.add(schedule, now)?;
+        }
+        Ok(count)
+    }
+
+    pub fn save_file(&self, path: &Path) -> SmartHomeResult<()> {
+        save_json(path, &self.schedules)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::clock::{Clock, ManualClock};
+    use crate::events::event::HomeEvent;
+    use crate::my_smart_home::home::Home;
+    use crate::my_smart_home::smart_home::SmartHome;
+    use crate::scheduler::schedule::Schedule;
+    use crate::smart_device::device::{DeviceState, VecOfDevice};
+    use crate::smart_device::kettle::Kettle;
+    use crate::smart_device::socket::Socket;
+    use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+    use serde_json::json;
+    use std::env;
+
+    fn at(day: u32, hour: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, min, 0)
+            .unwrap()
+    }
+
+    fn setup() -> (Home, ManualClock) {
+        let clock = ManualClock::new(at(10, 22, 0));
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.clock = Box::new(clock.clone());
+
+        let storeroom: VecOfDevice = vec![Box::new(Socket::new("4")), Box::new(Kettle::new("2"))];
+        home.add_room("storeroom".into(), storeroom).unwrap();
+        (home, clock)
+    }
+
+    fn schedule(value: serde_json::Value) -> Schedule {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn socket_state(home: &Home) -> DeviceState {
+        home.get_device("storeroom", "socket-4")
+            .unwrap()
+            .device_state()
+    }
+
+    fn switch_socket(home: &mut Home, state: &str) {
+        home.with_device("storeroom", "socket-4", |dev| dev.switch(state))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_daily_cron_schedule() {
+        let (mut home, clock) = setup();
+        let (_, events) = home.events.subscribe_channel();
+        let now = clock.now();
+        home.scheduler
+            .add(
+                schedule(json!({
+                    "id": "socket-off",
+                    "name": "выключать розетку в кладовке в 23:00",
+                    "when": {"type": "cron", "expr": "0 23 * * *"},
+                    "actions": [{"room": "storeroom", "device": "Smart Socket 4",
+                                 "command": "switch", "data": ["off"]}]
+                })),
+                now,
+            )
+            .unwrap();
+        assert_eq!(home.scheduler.schedules()[0].next_run, Some(at(10, 23, 0)));
+
+        switch_socket(&mut home, "on");
+        clock.advance(TimeDelta::minutes(59));
+        home.tick();
+        assert_eq!(socket_state(&home), DeviceState::On);
+
+        clock.advance(TimeDelta::minutes(1));
+        home.tick();
+        assert_eq!(socket_state(&home), DeviceState::Off);
+        assert!(events.try_iter().any(|e| matches!(
+            e,
+            HomeEvent::ScheduleFired { ref schedule, .. } if schedule == "socket-off"
+        )));
+        assert_eq!(home.scheduler.schedules()[0].next_run, Some(at(11, 23, 0)));
+
+        // на следующий день снова
+        switch_socket(&mut home, "on");
+        clock.set(at(11, 23, 0));
+        home.tick();
+        assert_eq!(socket_state(&home), DeviceState::Off);
+    }
+
+    #[test]
+    fn test_once_and_interval_schedules() {
+        let (home, clock) = setup();
+        let mut scheduler = home.scheduler;
+        let now = clock.now();
+        let once = json!({"id": "once", "when": {"type": "at", "at": "2025-01-10T22:30:00"},
+                          "actions": []});
+        scheduler.add(schedule(once), now).unwrap();
+        let every = json!({"id": "every", "when": {"type": "every", "seconds": 600},
+                           "actions": []});
+        scheduler.add(schedule(every), now).unwrap();
+
+        assert!(scheduler.due(at(10, 22, 5)).is_empty());
+        assert_eq!(scheduler.due(at(10, 22, 10)).len(), 1);
+
+        // пропущенные запуски не накапливаются
+        assert_eq!(scheduler.due(at(10, 23, 5)).len(), 2);
+        assert_eq!(scheduler.schedules()[1].next_run, Some(at(10, 23, 10)));
+
+        // однократное расписание исчерпано
+        assert_eq!(scheduler.schedules()[0].next_run, None);
+        assert_eq!(scheduler.due(at(10, 23, 10)).len(), 1);
+
+        scheduler.set_enabled("every", false).unwrap();
+        assert!(scheduler.due(at(11, 0, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_schedules() {
+        let (mut home, clock) = setup();
+        let now = clock.now();
+        let bad_cron = json!({"id": "a", "when": {"type": "cron", "expr": "0 25 * * *"},
+                              "actions": []});
+        assert!(home.scheduler.add(schedule(bad_cron), now).is_err());
+        let bad_every = json!({"id": "b", "when": {"type": "every", "seconds": 0},
+                               "actions": []});
+        assert!(home.scheduler.add(schedule(bad_every), now).is_err());
+        let huge_every = json!({"id": "b", "when": {"type": "every", "seconds": i64::MAX},
+                                "actions": []});
+        assert!(home.scheduler.add(schedule(huge_every), now).is_err());
+        let never = json!({"id": "b", "when": {"type": "cron", "expr": "0 12 31 4 *"},
+                           "actions": []});
+        let res = home.scheduler.add(schedule(never), now);
+        assert!(res.err().unwrap().to_string().contains("never fires"));
+        assert!(home.scheduler.schedules().is_empty());
+
+        let ok = json!({"id": "c", "when": {"type": "every", "seconds": 60}, "actions": []});
+        home.scheduler.add(schedule(ok.clone()), now).unwrap();
+        assert!(home.scheduler.add(schedule(ok), now).is_err());
+        assert!(home.scheduler.remove("x").is_err());
+    }
+
+    #[test]
+    fn test_schedules_persist_with_home() {
+        let dir = env::temp_dir().join(format!("smart_home_data_{}", std::process::id()));
+        let (mut home, clock) = setup();
+        home.set_data_dir(&dir).unwrap();
+        let now = clock.now();
+        let daily = json!({"id": "daily", "when": {"type": "cron", "expr": "0 23 * * *"},
+                           "actions": []});
+        home.scheduler.add(schedule(daily), now).unwrap();
+        home.persist().unwrap();
+
+        // после запуска сохраняется и время следующего запуска
+        clock.set(at(10, 23, 0));
+        home.tick();
+
+        let (mut other, _) = setup();
+        other.set_data_dir(&dir).unwrap();
+        assert_eq!(other.scheduler.schedules(), home.scheduler.schedules());
+        assert_eq!(other.scheduler.schedules()[0].next_run, Some(at(11, 23, 0)));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,3 @@
+pub mod cron;
+pub mod engine;
+pub mod schedule;
@@ -0,0 +1,96 @@
+use super::cron::CronExpr;
+use crate::command::device_command::DeviceCommand;
+use chrono::{NaiveDateTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+
+/// самый длинный интервал расписания: десять лет
+const MAX_INTERVAL_SECONDS: i64 = 10 * 366 * 24 * 60 * 60;
+
+/// Когда выполняется расписание
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScheduleSpec {
+    /// однократно в заданный момент
+    At { at: NaiveDateTime },
+
+    /// с фиксированным интервалом, начиная с `start` (по умолчанию - с момента добавления)
+    Every {
+        seconds: i64,
+        #[serde(default)]
+        start: Option<NaiveDateTime>,
+    },
+
+    /// по cron-выражению из пяти полей, например "0 23 * * *"
+    Cron { expr: String },
+}
+
+impl ScheduleSpec {
+    /// проверить параметры расписания
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            ScheduleSpec::At { .. } => Ok(()),
+            ScheduleSpec::Every { seconds, .. } if *seconds <= 0 => {
+                Err("interval must be positive".into())
+            }
+            ScheduleSpec::Every { seconds, .. } if *seconds > MAX_INTERVAL_SECONDS => Err(format!(
+                "interval must not exceed {MAX_INTERVAL_SECONDS} seconds"
+            )),
+            ScheduleSpec::Every { .. } => Ok(()),
+            ScheduleSpec::Cron { expr } => match expr.parse::<CronExpr>()?.can_fire() {
+                true => Ok(()),
+                false => Err(format!("cron expression never fires: {expr:?}")),
+            },
+        }
+    }
+
+    /// первый запуск для расписания, добавленного в момент `now`
+    pub fn first_run(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        match self {
+            ScheduleSpec::At { at } => Some(*at),
+            ScheduleSpec::Every { start, seconds } => match start {
+                Some(start) => Some(*start),
+                None => now.checked_add_signed(TimeDelta::try_seconds(*seconds)?),
+            },
+            ScheduleSpec::Cron { expr } => expr.parse::<CronExpr>().ok()?.next_after(now),
+        }
+    }
+
+    /// следующий запуск после запуска, запланированного на `previous`;
+    /// пропущенные за время простоя запуски не накапливаются
+    pub fn next_run(&self, previous: NaiveDateTime, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        match self {
+            ScheduleSpec::At { .. } => None,
+            ScheduleSpec::Every { seconds, .. } => {
+                let mut next = previous.checked_add_signed(TimeDelta::try_seconds(*seconds)?)?;
+                if next <= now {
+                    let missed = (now - next).num_seconds() / seconds + 1;
+                    let skipped = TimeDelta::try_seconds(missed.checked_mul(*seconds)?)?;
+                    next = next.checked_add_signed(skipped)?;
+                }
+                Some(next)
+            }
+            ScheduleSpec::Cron { expr } => expr.parse::<CronExpr>().ok()?.next_after(now),
+        }
+    }
+}
+
+/// Расписание: когда и какие команды устройствам выполнить
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    pub when: ScheduleSpec,
+    pub actions: Vec<DeviceCommand>,
+    /// следующий запуск, None - расписание исчерпано
+    #[serde(default)]
+    pub next_run: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub last_run: Option<NaiveDateTime>,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
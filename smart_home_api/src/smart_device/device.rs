@@ -1,5 +1,7 @@
-use std::time::Duration;
-use std::{fmt, thread};
+use super::fault::Fault;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 
 pub const NO_INFO_PROVIDED: &str = "No information provided";
 
@@ -9,10 +11,25 @@ pub enum DeviceType {
     Socket,
     Kettle,
     Thermometer,
+    /// тип устройства, зарегистрированный сторонним крейтом
+    Custom(String),
+}
+
+impl DeviceType {
+    /// имя типа, как в реестре типов устройств
+    pub fn type_name(&self) -> &str {
+        match self {
+            DeviceType::Socket => "socket",
+            DeviceType::Kettle => "kettle",
+            DeviceType::Thermometer => "thermometer",
+            DeviceType::Custom(name) => name,
+        }
+    }
 }
 
 /// Статус устройства
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DeviceState {
     On,
     #[default]
@@ -20,6 +37,9 @@ pub enum DeviceState {
     Broken,
 }
 
+/// Показания устройства: имя показания -> значение
+pub type Readings = BTreeMap<String, f32>;
+
 impl fmt::Display for DeviceState {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -34,11 +54,20 @@ pub trait SmartDevice {
     /// тип устройства
     fn get_type(&self) -> DeviceType;
 
+    /// стабильный идентификатор устройства, уникальный в рамках дома;
+    /// по умолчанию выводится из имени, поэтому такое устройство нельзя переименовать
+    fn get_id(&self) -> String {
+        device_id_from_name(&self.get_name(), self.get_type().type_name())
+    }
+
     /// имя устройства
     fn get_name(&self) -> String {
         format!("{} {}", NO_INFO_PROVIDED, "about name").to_string()
     }
 
+    /// изменить отображаемое имя устройства; по умолчанию имя не меняется
+    fn set_name(&mut self, _name: &str) {}
+
     /// описание устройства
     fn get_description(&self) -> String {
         NO_INFO_PROVIDED.to_string()
@@ -57,20 +86,65 @@ pub trait SmartDevice {
         NO_INFO_PROVIDED.to_string()
     }
 
-    fn send_notify(&self, interval: u64) {
-        thread::sleep(Duration::from_secs(interval));
+    /// текущая неисправность устройства; по умолчанию устройство не хранит неисправностей,
+    /// и сломанное устройство считается неисправным вручную
+    fn fault(&self) -> Option<Fault> {
+        None
+    }
+
+    /// записать или очистить неисправность, статус устройства не меняется;
+    /// по умолчанию неисправность не сохраняется
+    fn set_fault(&mut self, _fault: Option<Fault>) {}
+
+    /// зарегистрировать неисправность и перевести устройство в статус Broken
+    fn report_fault(&mut self, fault: Fault) -> String {
+        self.set_device_state(DeviceState::Broken);
+        self.set_fault(Some(fault));
+        "fault was registered".into()
+    }
+
+    /// сбросить устранимую неисправность, устройство переходит в статус Off
+    fn repair(&mut self) -> Result<String, String> {
+        match self.fault() {
+            Some(fault) if !fault.recoverable => Err(format!(
+                "fault {} is permanent, device must be replaced",
+                fault.code
+            )),
+            None if self.device_state() != DeviceState::Broken => Err("device has no fault".into()),
+            _ => {
+                self.set_fault(None);
+                self.set_device_state(DeviceState::Off);
+                Ok("fault was cleared".into())
+            }
+        }
+    }
+
+    /// текущие показания устройства в числовом виде
+    fn get_readings(&self) -> Readings {
+        Readings::new()
+    }
+
+    /// обновить показание устройства, false - если показание не поддерживается
+    fn update_reading(&mut self, _reading: &str, _value: f32) -> bool {
+        false
     }
 
     fn report(&self) -> String {
         format!(
             "Устройство: {}
+    Идентификатор: {}
     Описание: {}
     Состояние: {}
-    Текущие параметры: {}",
+    Текущие параметры: {}{}",
             self.get_name(),
+            self.get_id(),
             self.get_description(),
             self.device_state(),
-            self.get_current_info()
+            self.get_current_info(),
+            match self.fault() {
+                Some(fault) => format!("\n    Неисправность: {fault}"),
+                None => String::new(),
+            }
         )
     }
 
@@ -78,10 +152,33 @@ pub trait SmartDevice {
         match to {
             "on" => self.set_device_state(DeviceState::On),
             "off" => self.set_device_state(DeviceState::Off),
-            "broken" => self.set_device_state(DeviceState::Broken),
+            "broken" => self.report_fault(Fault::manual()),
             _ => NO_INFO_PROVIDED.to_string(),
         }
     }
 }
 
 pub type VecOfDevice = Vec<Box<dyn SmartDevice>>;
+
+/// идентификатор устройства: непустой slug из латиницы, цифр, '-', '_' и '.'
+pub fn is_valid_device_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// идентификатор из имени: латиница и цифры в нижнем регистре, остальное - через '-';
+/// для имени без таких символов - имя типа
+pub fn device_id_from_name(name: &str, type_name: &str) -> String {
+    let slug = name
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    match slug.is_empty() {
+        true => type_name.to_string(),
+        false => slug,
+    }
+}
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Неисправность устройства
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fault {
+    /// код неисправности
+    pub code: String,
+    /// описание неисправности
+    pub description: String,
+    /// unix-время регистрации, с
+    pub timestamp: u64,
+    /// может ли неисправность быть сброшена командой repair
+    pub recoverable: bool,
+}
+
+impl Fault {
+    pub fn new(code: &str, description: &str, recoverable: bool) -> Self {
+        Self {
+            code: code.to_string(),
+            description: description.to_string(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            recoverable,
+        }
+    }
+
+    /// устройство переведено в статус Broken без указания причины
+    pub fn manual() -> Self {
+        Self::new("manual", "device was switched to broken state", true)
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.recoverable {
+            true => "устранимая",
+            false => "неустранимая",
+        };
+        write!(f, "[{}] {} ({})", self.code, self.description, kind)
+    }
+}
+
+/// Неисправное устройство в доме
+#[derive(Clone, Debug, Serialize)]
+pub struct FaultedDevice {
+    pub room: String,
+    pub device: String,
+    pub name: String,
+    pub fault: Fault,
+}
@@ -1,22 +1,27 @@
-use super::device::{DeviceState, DeviceType, SmartDevice};
+use super::device::{DeviceState, DeviceType, Readings, SmartDevice};
+use super::fault::Fault;
 
 #[derive(Clone)]
 pub struct Kettle {
     dev_type: DeviceType,
+    id: String,
     name: String,
     temperature: f32,
     water_volume: f32,
     state: DeviceState,
+    fault: Option<Fault>,
 }
 
 impl Kettle {
     pub fn new(id: &str) -> Self {
         Self {
             dev_type: DeviceType::Kettle,
+            id: format!("kettle-{id}"),
             name: ("Smart Kettle ".to_owned() + id).to_string(),
             temperature: 0.0,
             water_volume: 1.1,
             state: DeviceState::Off,
+            fault: None,
         }
     }
 
@@ -42,21 +47,32 @@ impl SmartDevice for Kettle {
         self.dev_type.clone()
     }
 
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
     fn get_name(&self) -> String {
         self.name.clone()
     }
 
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
     fn device_state(&self) -> DeviceState {
         self.state
     }
 
     fn set_device_state(&mut self, device_state: DeviceState) -> String {
-        match self.state {
-            DeviceState::Off | DeviceState::On => {
+        match self.fault {
+            None => {
+                if device_state == DeviceState::Broken {
+                    self.fault = Some(Fault::manual());
+                }
                 self.state = device_state;
                 "state was set".into()
             }
-            DeviceState::Broken => "Can't change state, device is broken!".into(),
+            Some(_) => "Can't change state, device is broken!".into(),
         }
     }
 
@@ -65,4 +81,28 @@ impl SmartDevice for Kettle {
         let wv = self.get_current_water_volume();
         format!("В чайнике: {wv:.2} л воды, Текущая температура: {t:.2} °C")
     }
+
+    fn fault(&self) -> Option<Fault> {
+        self.fault.clone()
+    }
+
+    fn set_fault(&mut self, fault: Option<Fault>) {
+        self.fault = fault;
+    }
+
+    fn get_readings(&self) -> Readings {
+        Readings::from([
+            ("temperature".to_string(), self.temperature),
+            ("water_volume".to_string(), self.water_volume),
+        ])
+    }
+
+    fn update_reading(&mut self, reading: &str, value: f32) -> bool {
+        match reading {
+            "temperature" => self.temperature = value,
+            "water_volume" => self.water_volume = value,
+            _ => return false,
+        }
+        true
+    }
 }
@@ -1,4 +1,6 @@
 pub mod device;
+pub mod fault;
 pub mod kettle;
+pub mod registry;
 pub mod socket;
 pub mod thermometer;
@@ -0,0 +1,187 @@
+use super::device::SmartDevice;
+use super::kettle::Kettle;
+use super::socket::Socket;
+use super::thermometer::Thermometer;
+use crate::my_smart_home::error::{SmartHomeError, SmartHomeResult};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Конструктор устройства по json-параметрам
+pub type DeviceConstructor = Box<dyn Fn(&Value) -> SmartHomeResult<Box<dyn SmartDevice>>>;
+
+/// Реестр типов устройств: имя типа -> конструктор.
+/// Встроенные типы ("socket", "kettle", "thermometer") зарегистрированы в `default()`,
+/// сторонние крейты добавляют свои реализации `SmartDevice` через `register`.
+pub struct DeviceRegistry {
+    constructors: BTreeMap<String, DeviceConstructor>,
+}
+
+impl DeviceRegistry {
+    /// реестр без зарегистрированных типов
+    pub fn empty() -> Self {
+        Self {
+            constructors: BTreeMap::new(),
+        }
+    }
+
+    /// зарегистрировать тип устройства
+    pub fn register<F>(&mut self, type_name: &str, constructor: F) -> SmartHomeResult<()>
+    where
+        F: Fn(&Value) -> SmartHomeResult<Box<dyn SmartDevice>> + 'static,
+    {
+        if self.constructors.contains_key(type_name) {
+            return Err(SmartHomeError::DeviceTypeAlreadyRegistered(
+                type_name.to_string(),
+            ));
+        }
+        self.constructors
+            .insert(type_name.to_string(), Box::new(constructor));
+        Ok(())
+    }
+
+    /// создать устройство заданного типа,
+    /// необязательный параметр "name" задает отображаемое имя
+    pub fn create(&self, type_name: &str, params: &Value) -> SmartHomeResult<Box<dyn SmartDevice>> {
+        match self.constructors.get(type_name) {
+            Some(constructor) => {
+                let mut device = constructor(params)?;
+                if let Some(name) = params.get("name").and_then(Value::as_str) {
+                    device.set_name(name);
+                }
+                Ok(device)
+            }
+            None => Err(SmartHomeError::UnknownDeviceType(type_name.to_string())),
+        }
+    }
+
+    /// имена зарегистрированных типов
+    pub fn types(&self) -> Vec<String> {
+        self.constructors.keys().cloned().collect()
+    }
+}
+
+impl Default for DeviceRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry
+            .register("socket", |p| {
+                Ok(Box::new(Socket::new(&param_str(p, "socket", "id")?)))
+            })
+            .unwrap();
+        registry
+            .register("kettle", |p| {
+                Ok(Box::new(Kettle::new(&param_str(p, "kettle", "id")?)))
+            })
+            .unwrap();
+        registry
+            .register("thermometer", |p| {
+                Ok(Box::new(Thermometer::new(&param_str(
+                    p,
+                    "thermometer",
+                    "id",
+                )?)))
+            })
+            .unwrap();
+        registry
+    }
+}
+
+/// обязательный строковый параметр конструктора
+pub fn param_str(params: &Value, dev_type: &str, key: &str) -> SmartHomeResult<String> {
+    match params.get(key).and_then(Value::as_str) {
+        Some(value) if !value.is_empty() => Ok(value.to_string()),
+        _ => Err(SmartHomeError::InvalidDeviceParams {
+            dev_type: dev_type.to_string(),
+            reason: format!("string parameter '{key}' required"),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DeviceRegistry;
+    use crate::my_smart_home::home::Home;
+    use crate::my_smart_home::smart_home::SmartHome;
+    use crate::smart_device::device::{DeviceState, DeviceType, SmartDevice};
+    use crate::smart_device::socket::Socket;
+    use serde_json::json;
+
+    #[test]
+    fn test_builtin_types_registered() {
+        let registry = DeviceRegistry::default();
+        assert_eq!(registry.types(), vec!["kettle", "socket", "thermometer"]);
+
+        let device = registry.create("kettle", &json!({"id": "7"})).unwrap();
+        assert_eq!(device.get_type(), DeviceType::Kettle);
+        assert_eq!(device.get_id(), "kettle-7");
+        assert_eq!(device.get_name(), "Smart Kettle 7");
+
+        let params = json!({"id": "8", "name": "Чайник на даче"});
+        let device = registry.create("kettle", &params).unwrap();
+        assert_eq!(device.get_id(), "kettle-8");
+        assert_eq!(device.get_name(), "Чайник на даче");
+    }
+
+    #[test]
+    fn test_create_errors() {
+        let registry = DeviceRegistry::default();
+
+        let res = registry.create("toaster", &json!({"id": "1"}));
+        assert!(res
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("unknown device type"));
+
+        let res = registry.create("socket", &json!({}));
+        assert!(res.err().unwrap().to_string().contains("'id'"));
+    }
+
+    #[test]
+    fn test_register_custom_type() {
+        let mut registry = DeviceRegistry::default();
+        registry
+            .register("boiler", |_| Ok(Box::new(Socket::new("boiler"))))
+            .unwrap();
+        assert!(registry.create("boiler", &json!({})).is_ok());
+
+        // повторная регистрация запрещена
+        let res = registry.register("boiler", |_| Ok(Box::new(Socket::new("2"))));
+        assert!(res.is_err());
+    }
+
+    /// устройство стороннего крейта: только обязательные методы
+    struct Boiler;
+
+    impl SmartDevice for Boiler {
+        fn get_type(&self) -> DeviceType {
+            DeviceType::Custom("boiler".into())
+        }
+
+        fn get_name(&self) -> String {
+            "Boiler №1 (bath)".into()
+        }
+
+        fn set_device_state(&mut self, _device_state: DeviceState) -> String {
+            "state was set".into()
+        }
+    }
+
+    #[test]
+    fn test_device_with_default_id() {
+        let mut registry = DeviceRegistry::default();
+        registry
+            .register("boiler", |_| Ok(Box::new(Boiler)))
+            .unwrap();
+        let device = registry.create("boiler", &json!({})).unwrap();
+        assert_eq!(device.get_id(), "boiler-1-bath");
+
+        let mut home = Home::new("MyHome".into()).unwrap();
+        home.add_room("bath".into(), vec![device]).unwrap();
+        assert!(home.get_device("bath", "boiler-1-bath").is_ok());
+
+        // имя, из которого выведен идентификатор, не меняется
+        let res = home.rename_device("bath", "boiler-1-bath", "Котел");
+        assert!(res.err().unwrap().to_string().contains("can't be renamed"));
+    }
+}
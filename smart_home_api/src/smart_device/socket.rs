@@ -1,22 +1,27 @@
-use super::device::{DeviceState, DeviceType, SmartDevice};
+use super::device::{DeviceState, DeviceType, Readings, SmartDevice};
+use super::fault::Fault;
 
 #[derive(Clone, Default)]
 pub struct Socket {
     dev_type: DeviceType,
+    id: String,
     name: String,
     description: String,
     state: DeviceState,
     power: f32,
+    fault: Option<Fault>,
 }
 
 impl Socket {
     pub fn new(id: &str) -> Self {
         Self {
             dev_type: DeviceType::Socket,
+            id: format!("socket-{id}"),
             name: ("Smart Socket ".to_owned() + id).to_string(),
             description: "Very Powerful Smart Device".to_string(),
             state: DeviceState::Off,
             power: 15.2,
+            fault: None,
         }
     }
 
@@ -29,7 +34,7 @@ impl Socket {
     }
 
     pub fn set_broken(&mut self) {
-        self.state = DeviceState::Broken;
+        self.report_fault(Fault::manual());
     }
 
     fn get_description(&self) -> String {
@@ -46,10 +51,18 @@ impl SmartDevice for Socket {
         self.dev_type.clone()
     }
 
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
     fn get_name(&self) -> String {
         self.name.clone()
     }
 
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
     fn get_description(&self) -> String {
         self.get_description()
     }
@@ -59,12 +72,15 @@ impl SmartDevice for Socket {
     }
 
     fn set_device_state(&mut self, device_state: DeviceState) -> String {
-        match self.state {
-            DeviceState::Off | DeviceState::On => {
+        match self.fault {
+            None => {
+                if device_state == DeviceState::Broken {
+                    self.fault = Some(Fault::manual());
+                }
                 self.state = device_state;
                 "state was set".into()
             }
-            DeviceState::Broken => "Can't change state, device is broken!".into(),
+            Some(_) => "Can't change state, device is broken!".into(),
         }
     }
 
@@ -72,4 +88,24 @@ impl SmartDevice for Socket {
         let pw = self.get_current_power();
         format!("Текущая мощность: {pw:.2} Вт")
     }
+
+    fn fault(&self) -> Option<Fault> {
+        self.fault.clone()
+    }
+
+    fn set_fault(&mut self, fault: Option<Fault>) {
+        self.fault = fault;
+    }
+
+    fn get_readings(&self) -> Readings {
+        Readings::from([("power".to_string(), self.power)])
+    }
+
+    fn update_reading(&mut self, reading: &str, value: f32) -> bool {
+        match reading {
+            "power" => self.power = value,
+            _ => return false,
+        }
+        true
+    }
 }
@@ -1,20 +1,25 @@
-use super::device::{DeviceState, DeviceType, SmartDevice};
+use super::device::{DeviceState, DeviceType, Readings, SmartDevice};
+use super::fault::Fault;
 
 #[derive(Clone)]
 pub struct Thermometer {
     dev_type: DeviceType,
+    id: String,
     name: String,
     state: DeviceState,
     temperature: f32,
+    fault: Option<Fault>,
 }
 
 impl Thermometer {
     pub fn new(id: &str) -> Self {
         Self {
             dev_type: DeviceType::Thermometer,
+            id: format!("thermometer-{id}"),
             name: ("Thermometer ".to_owned() + id).to_string(),
             state: DeviceState::On,
             temperature: f32::default(),
+            fault: None,
         }
     }
 
@@ -28,10 +33,18 @@ impl SmartDevice for Thermometer {
         self.dev_type.clone()
     }
 
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
     fn get_name(&self) -> String {
         self.name.clone()
     }
 
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
     fn device_state(&self) -> DeviceState {
         self.state
     }
@@ -41,13 +54,36 @@ impl SmartDevice for Thermometer {
         format!("Текущая температура: {t:.2} °C")
     }
 
+    fn fault(&self) -> Option<Fault> {
+        self.fault.clone()
+    }
+
+    fn set_fault(&mut self, fault: Option<Fault>) {
+        self.fault = fault;
+    }
+
+    fn get_readings(&self) -> Readings {
+        Readings::from([("temperature".to_string(), self.temperature)])
+    }
+
+    fn update_reading(&mut self, reading: &str, value: f32) -> bool {
+        match reading {
+            "temperature" => self.temperature = value,
+            _ => return false,
+        }
+        true
+    }
+
     fn set_device_state(&mut self, device_state: DeviceState) -> String {
-        match self.state {
-            DeviceState::Off | DeviceState::On => {
+        match self.fault {
+            None => {
+                if device_state == DeviceState::Broken {
+                    self.fault = Some(Fault::manual());
+                }
                 self.state = device_state;
                 "ok, state was set".into()
             }
-            DeviceState::Broken => "failed, can't change state, device is broken!".into(),
+            Some(_) => "failed, can't change state, device is broken!".into(),
         }
     }
 }
@@ -3,9 +3,14 @@
 use serde_json::{json, Value};
 use smart_home_api::home_client::RpcOverStpClient;
 use smart_home_api::DEFAULT_TCP_SOCKET;
+use std::env;
 use std::error::Error;
 use std::io::stdin;
 use std::str::FromStr;
+use stp::auth::Credentials;
+use stp::error::ConnectError;
+#[cfg(feature = "tls")]
+use stp::tls::TlsClientConfig;
 
 // at server side was init, and serving that SmartHome structure:
 //
@@ -23,6 +28,47 @@ use std::str::FromStr;
 //         "Smart Socket 4", state: Off
 //         "Smart Kettle 2", state: Off
 
+/// учетные данные из окружения: SMART_HOME_TOKEN или SMART_HOME_USER и SMART_HOME_PASSWORD
+fn credentials() -> Option<Credentials> {
+    if let Ok(token) = env::var("SMART_HOME_TOKEN") {
+        return Some(Credentials::Token(token));
+    }
+    match (env::var("SMART_HOME_USER"), env::var("SMART_HOME_PASSWORD")) {
+        (Ok(user), Ok(password)) => Some(Credentials::Password { user, password }),
+        _ => None,
+    }
+}
+
+/// настройки TLS из окружения: SMART_HOME_TLS_CA, SMART_HOME_TLS_NAME (по умолчанию localhost),
+/// для mutual TLS - SMART_HOME_TLS_CERT и SMART_HOME_TLS_KEY
+#[cfg(feature = "tls")]
+fn tls_config() -> Option<TlsClientConfig> {
+    let ca = env::var("SMART_HOME_TLS_CA").ok()?;
+    Some(TlsClientConfig {
+        ca: ca.into(),
+        server_name: env::var("SMART_HOME_TLS_NAME").unwrap_or_else(|_| "localhost".into()),
+        cert: env::var("SMART_HOME_TLS_CERT").ok().map(Into::into),
+        key: env::var("SMART_HOME_TLS_KEY").ok().map(Into::into),
+    })
+}
+
+fn connect() -> Result<RpcOverStpClient, ConnectError> {
+    let credentials = credentials();
+    // SMART_HOME_SOCKET - путь к Unix-сокету локального сервера
+    #[cfg(unix)]
+    if let Ok(path) = env::var("SMART_HOME_SOCKET") {
+        return RpcOverStpClient::with_unix(path, credentials.as_ref());
+    }
+    #[cfg(feature = "tls")]
+    if let Some(tls) = tls_config() {
+        return RpcOverStpClient::with_tls(DEFAULT_TCP_SOCKET, &tls, credentials.as_ref());
+    }
+    match credentials {
+        Some(credentials) => RpcOverStpClient::with_credentials(DEFAULT_TCP_SOCKET, &credentials),
+        None => RpcOverStpClient::new(DEFAULT_TCP_SOCKET),
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let batch = include!("../commands_json");
 
@@ -45,6 +91,11 @@ fn main() -> Result<(), Box<dyn Error>> {
     println!("9 - createProviderReport: schema = 'living': ['Socket 2','Thermo 1']");
     println!("10- deviceExecute: kitchen room, Thermometer 1, get_current_info");
     println!("11- deviceExecute: storeroom, Smart Socket 4, switch off");
+    println!("12- addDevice: storeroom, socket with id 5");
+    println!("13- delDevice: storeroom, Smart Socket 5");
+    println!("14- createEnergyReport: month, tariff 5.5");
+    println!("15- listHomes");
+    println!("16- getMetrics");
     println!("------------------");
     println!();
 
@@ -60,7 +111,7 @@ fn main() -> Result<(), Box<dyn Error>> {
             .map(|x| batch[x].clone())
             .collect();
 
-        let mut client = RpcOverStpClient::new(DEFAULT_TCP_SOCKET)?;
+        let mut client = connect()?;
 
         let req = Value::Array(v); // send as batch,  even if single request.
 
@@ -4,12 +4,17 @@ use smart_device::device::{SmartDevice, VecOfDevice};
 use smart_device::kettle::Kettle as SmartKettle;
 use smart_device::socket::Socket as SmartSocket;
 use smart_device::thermometer::Thermometer as SmartThermometer;
+use smart_home_api::logging::Logger;
 use smart_home_api::{my_smart_home, smart_device};
 use std::error::Error;
+use std::path::Path;
+use std::{env, fs};
+use stp::auth::SecretHash;
 
 use my_smart_home::home::Home;
+use my_smart_home::hub::HomeHub;
 use my_smart_home::smart_home::SmartHome;
-use my_smart_home::smart_home_tcp::SmartHomePublicApi;
+use my_smart_home::smart_home_tcp::{ServerConfig, SmartHomePublicApi};
 
 // test SmartHome structure
 //
@@ -73,10 +78,53 @@ fn init_home() -> Result<Home, Box<dyn Error>> {
     Ok(home)
 }
 
+/// настройки доступа: токены и пользователи с хешами секретов, их роли
+const AUTH_FILE: &str = "./smart_home_tcp_server/auth.json";
+
+/// без файла настроек или с испорченным файлом сервер не запускается;
+/// работать без аутентификации можно, только явно указав "open": true
+fn load_config() -> Result<ServerConfig, Box<dyn Error>> {
+    match fs::read_to_string(AUTH_FILE) {
+        Ok(data) => Ok(serde_json::from_str(&data)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(format!(
+            "{AUTH_FILE} not found: list tokens and users, or set \"open\": true \
+             to accept clients without authentication"
+        )
+        .into()),
+        Err(e) => Err(e.into()),
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
+    // smart_home_server hash <token> - хеш токена для auth.json,
+    // smart_home_server hash-password <password> - хеш пароля
+    let args: Vec<String> = env::args().collect();
+    if let [_, cmd, secret] = args.as_slice() {
+        let hash = match cmd.as_str() {
+            "hash" => Some(SecretHash::new(secret)),
+            "hash-password" => Some(SecretHash::password(secret)),
+            _ => None,
+        };
+        if let Some(hash) = hash {
+            println!("{}", serde_json::to_string_pretty(&hash)?);
+            return Ok(());
+        }
+    }
+
+    // SMART_HOME_LOG=debug - уровень журнала, SMART_HOME_LOG_FORMAT=json - вывод в JSON
+    Logger::from_env().init()?;
+
+    let config = load_config()?;
     let mut home: Home = init_home()?;
+    // начальные правила дома по умолчанию; сохраненные в его каталоге данных их заменят
+    home.rules
+        .load_file(Path::new("./smart_home_tcp_server/rules.json"))?;
+
+    // данные каждого дома - в подкаталоге с его именем
+    let mut hub = HomeHub::new(home);
+    hub.set_data_dir(Path::new("./smart_home_tcp_server/data"))?;
 
-    home.serve_public()?;
+    hub.serve_public(config)?;
 
     Ok(())
 }
@@ -0,0 +1,264 @@
+use pbkdf2::pbkdf2_hmac;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+/// число итераций PBKDF2-HMAC-SHA256 для новых паролей
+pub const PASSWORD_ROUNDS: u32 = 600_000;
+
+/// Учетные данные клиента для аутентификации после handshake.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Credentials {
+    /// заранее выданный токен
+    Token(String),
+    /// имя пользователя и пароль
+    Password { user: String, password: String },
+}
+
+impl Credentials {
+    /// строка, которую клиент отправляет серверу
+    pub(crate) fn encode(&self) -> String {
+        match self {
+            Credentials::Token(token) => format!("token:{token}"),
+            Credentials::Password { user, password } => format!("password:{user}:{password}"),
+        }
+    }
+
+    /// разбор строки клиента; пароль может содержать ':'
+    pub(crate) fn decode(data: &str) -> Option<Self> {
+        match data.split_once(':')? {
+            ("token", token) => Some(Credentials::Token(token.to_string())),
+            ("password", rest) => {
+                let (user, password) = rest.split_once(':')?;
+                Some(Credentials::Password {
+                    user: user.to_string(),
+                    password: password.to_string(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Кто подключился к серверу.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Principal {
+    /// сервер без аутентификации
+    Anonymous,
+    /// клиент с токеном, по имени токена в конфигурации
+    Token(String),
+    User(String),
+    /// клиент Unix-сокета; доступ к нему ограничен правами на файл сокета
+    Local,
+}
+
+impl Principal {
+    pub fn name(&self) -> &str {
+        match self {
+            Principal::Anonymous => "anonymous",
+            Principal::Local => "local",
+            Principal::Token(name) | Principal::User(name) => name,
+        }
+    }
+}
+
+/// Хеш секрета с солью, оба в hex.
+/// Без `rounds` - sha256(salt || secret): достаточно для случайных токенов;
+/// с `rounds` - PBKDF2-HMAC-SHA256 с этим числом итераций, для паролей.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SecretHash {
+    pub salt: String,
+    pub hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rounds: Option<u32>,
+}
+
+impl SecretHash {
+    /// хеш токена со случайной солью
+    pub fn new(secret: &str) -> Self {
+        let salt = random_salt();
+        let hash = salted_hash(&salt, secret);
+        Self {
+            salt,
+            hash,
+            rounds: None,
+        }
+    }
+
+    /// хеш пароля со случайной солью, `PASSWORD_ROUNDS` итераций
+    pub fn password(secret: &str) -> Self {
+        Self::password_with_rounds(secret, PASSWORD_ROUNDS)
+    }
+
+    /// хеш пароля с заданным числом итераций
+    pub fn password_with_rounds(secret: &str, rounds: u32) -> Self {
+        let salt = random_salt();
+        let hash = derived_hash(&salt, secret, rounds);
+        Self {
+            salt,
+            hash,
+            rounds: Some(rounds),
+        }
+    }
+
+    /// совпадает ли секрет с хешем; сравнение за постоянное время
+    pub fn verify(&self, secret: &str) -> bool {
+        let hash = match self.rounds {
+            Some(rounds) => derived_hash(&self.salt, secret, rounds),
+            None => salted_hash(&self.salt, secret),
+        };
+        hash.len() == self.hash.len()
+            && hash
+                .bytes()
+                .zip(self.hash.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+/// Именованный токен.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TokenEntry {
+    pub name: String,
+    #[serde(flatten)]
+    pub secret: SecretHash,
+}
+
+/// Пользователь с паролем.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UserEntry {
+    pub user: String,
+    #[serde(flatten)]
+    pub secret: SecretHash,
+}
+
+/// Настройки аутентификации сервера.
+/// Без токенов и пользователей к серверу не подключиться, пока он явно не открыт.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AuthConfig {
+    /// принимать клиентов без учетных данных
+    #[serde(default)]
+    pub open: bool,
+    #[serde(default)]
+    pub tokens: Vec<TokenEntry>,
+    #[serde(default)]
+    pub users: Vec<UserEntry>,
+}
+
+impl AuthConfig {
+    /// аутентификация не требуется: сервер открыт явно
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// проверить учетные данные клиента
+    pub fn authenticate(&self, credentials: &Credentials) -> Option<Principal> {
+        match credentials {
+            Credentials::Token(token) => self
+                .tokens
+                .iter()
+                .find(|entry| entry.secret.verify(token))
+                .map(|entry| Principal::Token(entry.name.clone())),
+            Credentials::Password { user, password } => self
+                .users
+                .iter()
+                .find(|entry| &entry.user == user && entry.secret.verify(password))
+                .map(|entry| Principal::User(entry.user.clone())),
+        }
+    }
+}
+
+fn random_salt() -> String {
+    let mut salt = [0u8; 16];
+    getrandom::fill(&mut salt).expect("system random generator");
+    to_hex(&salt)
+}
+
+fn derived_hash(salt: &str, secret: &str, rounds: u32) -> String {
+    let mut hash = [0u8; 32];
+    pbkdf2_hmac::<Sha256>(secret.as_bytes(), salt.as_bytes(), rounds, &mut hash);
+    to_hex(&hash)
+}
+
+fn salted_hash(salt: &str, secret: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(secret.as_bytes());
+    to_hex(&hasher.finalize())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, b| {
+        let _ = write!(hex, "{b:02x}");
+        hex
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AuthConfig {
+        AuthConfig {
+            open: false,
+            tokens: vec![TokenEntry {
+                name: "ci".into(),
+                secret: SecretHash::new("s3cr3t"),
+            }],
+            users: vec![UserEntry {
+                user: "anna".into(),
+                secret: SecretHash::password_with_rounds("pass:word", 1000),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_secret_hash() {
+        let a = SecretHash::new("secret");
+        let b = SecretHash::new("secret");
+        assert_ne!(a.salt, b.salt);
+        assert_ne!(a.hash, b.hash);
+        assert!(a.verify("secret") && b.verify("secret"));
+        assert!(!a.verify("Secret"));
+
+        let password = SecretHash::password_with_rounds("secret", 1000);
+        assert_eq!(password.rounds, Some(1000));
+        assert_ne!(password.hash, salted_hash(&password.salt, "secret"));
+        assert!(password.verify("secret"));
+        assert!(!password.verify("Secret"));
+
+        // RFC 7914, 11: PBKDF2-HMAC-SHA256("passwd", "salt", 1)
+        assert!(derived_hash("salt", "passwd", 1).starts_with("55ac046e56e3089f"));
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let config = config();
+        assert!(!config.is_open());
+        assert!(!AuthConfig::default().is_open());
+
+        let token = Credentials::Token("s3cr3t".into());
+        assert_eq!(
+            config.authenticate(&token),
+            Some(Principal::Token("ci".into()))
+        );
+
+        let password = Credentials::Password {
+            user: "anna".into(),
+            password: "pass:word".into(),
+        };
+        let decoded = Credentials::decode(&password.encode()).unwrap();
+        assert_eq!(decoded, password);
+        assert_eq!(
+            config.authenticate(&decoded),
+            Some(Principal::User("anna".into()))
+        );
+
+        let wrong_user = Credentials::Password {
+            user: "boris".into(),
+            password: "pass:word".into(),
+        };
+        assert_eq!(config.authenticate(&wrong_user), None);
+        assert_eq!(Credentials::decode("cookie:123"), None);
+    }
+}
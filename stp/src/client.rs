@@ -1,10 +1,19 @@
+use crate::auth::Credentials;
 use crate::error::{ConnectError, RequestError};
+use crate::stream::StpStream;
 use std::io::{Read, Write};
 use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::{os::unix::net::UnixStream, path::Path};
+
+#[cfg(feature = "tls")]
+use crate::{error::TlsError, tls::TlsClientConfig};
+#[cfg(feature = "tls")]
+use rustls::{ClientConnection, StreamOwned};
 
 /// Клиент STP.
 pub struct StpClient {
-    stream: TcpStream,
+    stream: StpStream,
 }
 
 impl StpClient {
@@ -13,14 +22,62 @@ impl StpClient {
     where
         Addrs: ToSocketAddrs,
     {
+        Self::connect_with(addrs, None)
+    }
+
+    /// Подключение с учетными данными, если сервер требует аутентификацию.
+    pub fn connect_with<Addrs>(
+        addrs: Addrs,
+        credentials: Option<&Credentials>,
+    ) -> Result<Self, ConnectError>
+    where
+        Addrs: ToSocketAddrs,
+    {
+        Self::connect_stream(TcpStream::connect(addrs)?, credentials)
+    }
+
+    /// Подключение к локальному серверу через Unix-сокет.
+    #[cfg(unix)]
+    pub fn connect_unix<P: AsRef<Path>>(
+        path: P,
+        credentials: Option<&Credentials>,
+    ) -> Result<Self, ConnectError> {
+        Self::connect_stream(UnixStream::connect(path)?, credentials)
+    }
+
+    /// Handshake и аутентификация по уже установленному потоку.
+    pub fn connect_stream<S>(
+        stream: S,
+        credentials: Option<&Credentials>,
+    ) -> Result<Self, ConnectError>
+    where
+        S: Read + Write + Send + 'static,
+    {
+        let mut client = Self::try_handshake(Box::new(stream))?;
+        client.try_auth(credentials)?;
+        Ok(client)
+    }
+
+    /// Подключение по TLS; дальше протокол тот же, что и без TLS.
+    #[cfg(feature = "tls")]
+    pub fn connect_tls<Addrs>(
+        addrs: Addrs,
+        tls: &TlsClientConfig,
+        credentials: Option<&Credentials>,
+    ) -> Result<Self, ConnectError>
+    where
+        Addrs: ToSocketAddrs,
+    {
+        let (config, name) = tls.build()?;
+        let conn = ClientConnection::new(config, name).map_err(TlsError::from)?;
         let stream = TcpStream::connect(addrs)?;
-        Self::try_handshake(stream)
+        Self::connect_stream(StreamOwned::new(conn, stream), credentials)
     }
 
     /// Проводим handshake, чтобы убедиться, что сервер поддерживает STP:
     /// 1) отправляем байты "clnt",
     /// 1) ожидаем байты "serv" в ответ.
-    fn try_handshake(mut stream: TcpStream) -> Result<Self, ConnectError> {
+    fn try_handshake(mut stream: StpStream) -> Result<Self, ConnectError> {
         stream.write_all(b"clnt")?;
         let mut buf = [0; 4];
         stream.read_exact(&mut buf)?;
@@ -30,6 +87,27 @@ impl StpClient {
         Ok(Self { stream })
     }
 
+    /// Аутентификация после handshake:
+    /// 1) ожидаем байты "open" - сервер открыт, или "auth" - нужны учетные данные,
+    /// 2) для "auth" отправляем учетные данные и ожидаем "okay" в ответ.
+    fn try_auth(&mut self, credentials: Option<&Credentials>) -> Result<(), ConnectError> {
+        let mut buf = [0; 4];
+        self.stream.read_exact(&mut buf)?;
+        match &buf {
+            b"open" => return Ok(()),
+            b"auth" => (),
+            _ => return Err(ConnectError::BadHandshake),
+        }
+
+        let credentials = credentials.ok_or(ConnectError::AuthRequired)?;
+        crate::send_string(credentials.encode(), &mut self.stream)?;
+        self.stream.read_exact(&mut buf)?;
+        match &buf {
+            b"okay" => Ok(()),
+            _ => Err(ConnectError::AuthFailed),
+        }
+    }
+
     /// Отправка запроса на сервер и получение ответа.
     pub fn send_request<R: AsRef<str>>(&mut self, req: R) -> Result<String, RequestError> {
         crate::send_string(req, &mut self.stream)?;
@@ -7,10 +7,37 @@ pub enum ConnectError {
     #[error("bad handshake")]
     BadHandshake,
 
+    #[error("authentication failed")]
+    AuthFailed,
+
+    #[error("server requires authentication")]
+    AuthRequired,
+
+    #[cfg(feature = "tls")]
+    #[error(transparent)]
+    Tls(#[from] TlsError),
+
     #[error(transparent)]
     Io(#[from] io::Error),
 }
 
+/// Ошибка настройки или установки TLS.
+#[cfg(feature = "tls")]
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError {
+    #[error("bad pem file: {0}")]
+    Pem(String),
+
+    #[error("invalid server name: {0:?}")]
+    InvalidServerName(String),
+
+    #[error(transparent)]
+    Rustls(#[from] rustls::Error),
+
+    #[error(transparent)]
+    Verifier(#[from] rustls::server::VerifierBuilderError),
+}
+
 /// Ошибка отправки сообщения.
 #[derive(Debug, thiserror::Error)]
 pub enum SendError {
@@ -24,6 +51,9 @@ pub enum RecvError {
     #[error("bad encoding")]
     BadEncoding,
 
+    #[error("message too long: {0} bytes")]
+    TooLong(u32),
+
     #[error(transparent)]
     Io(#[from] io::Error),
 }
@@ -37,3 +67,11 @@ pub enum RequestError {
     #[error(transparent)]
     Recv(#[from] RecvError),
 }
+
+impl From<SendError> for ConnectError {
+    fn from(e: SendError) -> Self {
+        match e {
+            SendError::Io(e) => ConnectError::Io(e),
+        }
+    }
+}
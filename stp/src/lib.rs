@@ -1,9 +1,13 @@
 use crate::error::{RecvError, SendError};
 use std::io::{Read, Write};
 
+pub mod auth;
 pub mod client;
 pub mod error;
 pub mod server;
+mod stream;
+#[cfg(feature = "tls")]
+pub mod tls;
 
 /// Отправляет четыре байта `data.len()`, а потом сами данные.
 fn send_string<Data: AsRef<str>, Writer: Write>(
@@ -19,10 +23,18 @@ fn send_string<Data: AsRef<str>, Writer: Write>(
 }
 
 /// Читает четыре байта длины, а потом сами данные.
-fn recv_string<Reader: Read>(mut reader: Reader) -> Result<String, RecvError> {
+fn recv_string<Reader: Read>(reader: Reader) -> Result<String, RecvError> {
+    recv_bounded(reader, u32::MAX)
+}
+
+/// Как `recv_string`, но данные длиннее `max_len` байт не читает.
+fn recv_bounded<Reader: Read>(mut reader: Reader, max_len: u32) -> Result<String, RecvError> {
     let mut buf = [0; 4];
     reader.read_exact(&mut buf)?;
     let len = u32::from_be_bytes(buf);
+    if len > max_len {
+        return Err(RecvError::TooLong(len));
+    }
 
     let mut buf = vec![0; len as _];
     reader.read_exact(&mut buf)?;
@@ -31,7 +43,8 @@ fn recv_string<Reader: Read>(mut reader: Reader) -> Result<String, RecvError> {
 
 #[cfg(test)]
 mod tests {
-    use super::{recv_string, send_string};
+    use super::{recv_bounded, recv_string, send_string};
+    use crate::error::RecvError;
 
     // Обратите внимание: generic реализация позволяет использовать в тестах
     // память, вместо реального сетевого обмена.
@@ -69,5 +82,8 @@ mod tests {
 
         let received = recv_string(&buf[..]).unwrap();
         assert_eq!(data, received);
+
+        let res = recv_bounded(&buf[..], 4);
+        assert!(matches!(res, Err(RecvError::TooLong(5))));
     }
 }
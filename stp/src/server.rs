@@ -1,11 +1,49 @@
-use crate::error::{ConnectError, RequestError};
+use crate::auth::{AuthConfig, Credentials, Principal};
+use crate::error::{ConnectError, RecvError, RequestError};
+use crate::stream::{Deadline, StpStream, Timeouts, Timer};
 use std::io;
 use std::io::{Read, Write};
-use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(unix)]
+use std::{
+    fs,
+    os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "tls")]
+use crate::{error::TlsError, tls::TlsServerConfig};
+#[cfg(feature = "tls")]
+use rustls::{ServerConnection, StreamOwned};
+
+/// Сколько времени у клиента на handshake и аутентификацию по умолчанию.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Сколько времени у клиента на отправку запроса и прием ответа по умолчанию.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Самые длинные учетные данные клиента.
+const MAX_CREDENTIALS_LEN: u32 = 4096;
+/// Самый длинный запрос клиента.
+const MAX_REQUEST_LEN: u32 = 1 << 20;
+
+/// Откуда сервер принимает соединения.
+enum Listener {
+    Tcp(TcpListener),
+    /// Unix-сокет и путь к его файлу
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
 
 /// STP сервер.
 pub struct StpServer {
-    tcp: TcpListener,
+    listener: Listener,
+    auth: Arc<AuthConfig>,
+    handshake_timeout: Duration,
+    request_timeout: Duration,
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<rustls::ServerConfig>>,
 }
 
 impl StpServer {
@@ -15,50 +53,581 @@ impl StpServer {
         Addrs: ToSocketAddrs,
     {
         let tcp = TcpListener::bind(addrs)?;
-        Ok(Self { tcp })
+        Ok(Self::with_listener(Listener::Tcp(tcp)))
+    }
+
+    /// Закрепляем сервер на Unix-сокете для локальных клиентов.
+    /// Доступ к серверу ограничивают права `mode` (не больше 0o777) на файл сокета:
+    /// подключиться может только тот, кто может писать в этот файл.
+    /// Сокет создается в закрытом каталоге и попадает на `path` уже с этими правами;
+    /// сокет другого работающего сервера не заменяется.
+    #[cfg(unix)]
+    pub fn bind_unix<P: AsRef<Path>>(path: P, mode: u32) -> io::Result<Self> {
+        let path = path.as_ref();
+        if mode > 0o777 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid unix socket mode: {mode:o}"),
+            ));
+        }
+        match fs::symlink_metadata(path) {
+            // сокет, оставшийся от прошлого запуска, заменит новый
+            Ok(meta) if meta.file_type().is_socket() => match UnixStream::connect(path) {
+                Ok(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AddrInUse,
+                        format!("{} is used by a running server", path.display()),
+                    ))
+                }
+                Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => (),
+                Err(e) => return Err(e),
+            },
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e),
+        }
+
+        // до смены прав сокет доступен всем, кому позволяет umask, - но не в этом каталоге
+        let name = path.file_name().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "unix socket path has no file name",
+            )
+        })?;
+        let private = path.with_file_name(format!(
+            ".{}.{}",
+            name.to_string_lossy(),
+            std::process::id()
+        ));
+        fs::DirBuilder::new().mode(0o700).create(&private)?;
+        let staged = private.join("socket");
+        let bound = UnixListener::bind(&staged).and_then(|unix| {
+            fs::set_permissions(&staged, fs::Permissions::from_mode(mode))?;
+            fs::rename(&staged, path)?;
+            Ok(unix)
+        });
+        let _ = fs::remove_file(&staged);
+        fs::remove_dir(&private)?;
+        Ok(Self::with_listener(Listener::Unix(
+            bound?,
+            path.to_path_buf(),
+        )))
+    }
+
+    fn with_listener(listener: Listener) -> Self {
+        Self {
+            listener,
+            auth: Arc::default(),
+            handshake_timeout: HANDSHAKE_TIMEOUT,
+            request_timeout: REQUEST_TIMEOUT,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Принимать только TLS-соединения.
+    #[cfg(feature = "tls")]
+    pub fn set_tls(&mut self, config: &TlsServerConfig) -> Result<(), TlsError> {
+        self.tls = Some(config.build()?);
+        Ok(())
+    }
+
+    /// Настройки аутентификации клиентов; по умолчанию TCP-клиентам без учетных данных отказ.
+    pub fn set_auth(&mut self, auth: AuthConfig) {
+        self.auth = Arc::new(auth);
+    }
+
+    /// Сколько времени у клиента на handshake и аутентификацию.
+    pub fn set_handshake_timeout(&mut self, timeout: Duration) {
+        self.handshake_timeout = timeout;
+    }
+
+    /// Сколько времени у клиента на отправку запроса и на прием ответа.
+    pub fn set_request_timeout(&mut self, timeout: Duration) {
+        self.request_timeout = timeout;
+    }
+
+    /// TCP-адрес, на котором слушает сервер.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        match &self.listener {
+            Listener::Tcp(tcp) => tcp.local_addr(),
+            #[cfg(unix)]
+            Listener::Unix(..) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "server listens on unix socket",
+            )),
+        }
+    }
+
+    /// Неблокирующий режим: accept возвращает WouldBlock, если входящих соединений нет.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        match &self.listener {
+            Listener::Tcp(tcp) => tcp.set_nonblocking(nonblocking),
+            #[cfg(unix)]
+            Listener::Unix(unix, _) => unix.set_nonblocking(nonblocking),
+        }
     }
 
     /// Принимаем входящее соединение и производим handshake.
+    /// Принятое соединение всегда обслуживаем в блокирующем режиме;
+    /// на handshake и аутентификацию у клиента не больше заданного времени.
     pub fn accept(&self) -> Result<StpConnection, ConnectError> {
-        let (stream, _) = self.tcp.accept()?;
-        Self::try_handshake(stream)
+        self.accept_handshake()?.complete()
+    }
+
+    /// Принимаем входящее соединение, но handshake оставляем вызывающей стороне:
+    /// проверка пароля долгая, ее можно провести в отдельном потоке.
+    /// Срок на handshake отсчитывается уже с этого момента.
+    pub fn accept_handshake(&self) -> Result<Handshake, ConnectError> {
+        match &self.listener {
+            Listener::Tcp(tcp) => {
+                let (stream, addr) = tcp.accept()?;
+                stream.set_nonblocking(false)?;
+                self.handshake_within(stream, addr.to_string())
+            }
+            #[cfg(unix)]
+            Listener::Unix(unix, path) => {
+                let (stream, _) = unix.accept()?;
+                stream.set_nonblocking(false)?;
+                self.handshake_within(stream, format!("unix:{}", path.display()))
+            }
+        }
+    }
+
+    /// handshake со сроком; тот же таймер потом ограничивает обмен запросом и ответом
+    fn handshake_within<S>(&self, stream: S, peer: String) -> Result<Handshake, ConnectError>
+    where
+        S: Read + Write + Timeouts + Send + 'static,
+    {
+        let timer = Timer::default();
+        timer.start(self.handshake_timeout);
+        let stream = Deadline::new(stream, timer.clone());
+        self.handshake(stream, peer, timer)
+    }
+
+    /// Handshake и аутентификация по уже установленному потоку;
+    /// `peer` - описание клиента для журналов.
+    /// Сроки на handshake и запросы для такого потока задает вызывающая сторона.
+    pub fn accept_stream<S>(
+        &self,
+        stream: S,
+        peer: impl Into<String>,
+    ) -> Result<StpConnection, ConnectError>
+    where
+        S: Read + Write + Send + 'static,
+    {
+        self.handshake(stream, peer.into(), Timer::default())?
+            .complete()
+    }
+
+    /// принятое соединение вместе с настройками сервера, нужными для handshake
+    fn handshake<S>(&self, stream: S, peer: String, timer: Timer) -> Result<Handshake, ConnectError>
+    where
+        S: Read + Write + Send + 'static,
+    {
+        Ok(Handshake {
+            stream: self.wrap(stream)?,
+            peer,
+            open: self.open_principal(),
+            auth: self.auth.clone(),
+            timer,
+            request_timeout: self.request_timeout,
+        })
+    }
+
+    /// TLS поверх принятого соединения, если он включен;
+    /// TLS handshake проходит при первом обмене данными.
+    fn wrap<S>(&self, stream: S) -> Result<StpStream, ConnectError>
+    where
+        S: Read + Write + Send + 'static,
+    {
+        #[cfg(feature = "tls")]
+        if let Some(config) = &self.tls {
+            let conn = ServerConnection::new(config.clone()).map_err(TlsError::from)?;
+            return Ok(Box::new(StreamOwned::new(conn, stream)));
+        }
+        Ok(Box::new(stream))
+    }
+
+    /// кто подключился без учетных данных: клиентам Unix-сокета их не нужно,
+    /// доступ к сокету ограничен правами на его файл; None - нужна аутентификация
+    fn open_principal(&self) -> Option<Principal> {
+        match &self.listener {
+            Listener::Tcp(_) => self.auth.is_open().then_some(Principal::Anonymous),
+            #[cfg(unix)]
+            Listener::Unix(..) => Some(Principal::Local),
+        }
+    }
+}
+
+/// Принятое соединение, которому еще предстоят handshake и аутентификация.
+pub struct Handshake {
+    stream: StpStream,
+    peer: String,
+    /// кто подключился без учетных данных; None - нужна аутентификация
+    open: Option<Principal>,
+    auth: Arc<AuthConfig>,
+    timer: Timer,
+    request_timeout: Duration,
+}
+
+impl Handshake {
+    /// Проводим handshake и аутентификацию клиента; после них срок снимается.
+    pub fn complete(mut self) -> Result<StpConnection, ConnectError> {
+        self.try_handshake()?;
+        let principal = self.try_auth()?;
+        self.timer.stop();
+        Ok(StpConnection {
+            stream: self.stream,
+            principal,
+            peer: self.peer,
+            timer: self.timer,
+            request_timeout: self.request_timeout,
+        })
+    }
+
+    /// Кто на другой стороне: ip:port для TCP, unix:<путь сокета> для Unix-сокета
+    pub fn peer(&self) -> &str {
+        &self.peer
     }
 
     /// Проводим handshake, чтобы убедиться, что клиент поддерживает STP:
     /// 1) ожидаем байты "clnt",
     /// 1) отправляем байты "serv" в ответ.
-    fn try_handshake(mut stream: TcpStream) -> Result<StpConnection, ConnectError> {
+    fn try_handshake(&mut self) -> Result<(), ConnectError> {
         let mut buf = [0; 4];
-        stream.read_exact(&mut buf)?;
+        self.stream.read_exact(&mut buf)?;
         if &buf != b"clnt" {
             return Err(ConnectError::BadHandshake);
         }
-        stream.write_all(b"serv")?;
-        Ok(StpConnection { stream })
+        self.stream.write_all(b"serv")?;
+        Ok(())
+    }
+
+    /// Аутентификация клиента после handshake:
+    /// 1) отправляем "open", если аутентификация не требуется, иначе "auth",
+    /// 2) для "auth" ожидаем учетные данные и отвечаем "okay" или "deny".
+    fn try_auth(&mut self) -> Result<Principal, ConnectError> {
+        if let Some(principal) = self.open.take() {
+            self.stream.write_all(b"open")?;
+            return Ok(principal);
+        }
+
+        self.stream.write_all(b"auth")?;
+        let credentials = match super::recv_bounded(&mut self.stream, MAX_CREDENTIALS_LEN) {
+            Ok(data) => Credentials::decode(&data),
+            Err(RecvError::BadEncoding | RecvError::TooLong(_)) => None,
+            Err(RecvError::Io(e)) => return Err(e.into()),
+        };
+        match credentials.and_then(|c| self.auth.authenticate(&c)) {
+            Some(principal) => {
+                self.stream.write_all(b"okay")?;
+                Ok(principal)
+            }
+            None => {
+                self.stream.write_all(b"deny")?;
+                Err(ConnectError::AuthFailed)
+            }
+        }
+    }
+}
+
+/// Файл Unix-сокета удаляется вместе с сервером.
+#[cfg(unix)]
+impl Drop for StpServer {
+    fn drop(&mut self) {
+        if let Listener::Unix(_, path) = &self.listener {
+            let _ = fs::remove_file(path);
+        }
     }
 }
 
 /// Соединение с клиентом.
 /// Позволяет обрабатывать запросы.
 pub struct StpConnection {
-    stream: TcpStream,
+    stream: StpStream,
+    principal: Principal,
+    peer: String,
+    timer: Timer,
+    request_timeout: Duration,
 }
 
 impl StpConnection {
     /// Обрабатываем запрос и возвращаем ответ используя логику
     /// предоставленную вызывающей стороной.
+    /// На чтение запроса и на отправку ответа у клиента по сроку,
+    /// запрос длиннее `MAX_REQUEST_LEN` не читается.
     pub fn process_request<F>(&mut self, handler: F) -> Result<(), RequestError>
     where
         F: FnOnce(String) -> String,
     {
-        let request = super::recv_string(&mut self.stream)?;
-        let response = handler(request);
-        super::send_string(&response, &mut self.stream)?;
-        Ok(())
+        self.timer.start(self.request_timeout);
+        let request = super::recv_bounded(&mut self.stream, MAX_REQUEST_LEN);
+        self.timer.stop();
+        let response = handler(request?);
+        self.timer.start(self.request_timeout);
+        let sent = super::send_string(&response, &mut self.stream);
+        self.timer.stop();
+        Ok(sent?)
+    }
+
+    /// Кто подключился: пользователь, токен или аноним на открытом сервере
+    pub fn principal(&self) -> &Principal {
+        &self.principal
+    }
+
+    /// Кто на другой стороне: ip:port для TCP, unix:<путь сокета> для Unix-сокета
+    pub fn peer(&self) -> &str {
+        &self.peer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StpServer;
+    use crate::auth::{AuthConfig, Credentials, Principal, SecretHash, TokenEntry, UserEntry};
+    use crate::client::StpClient;
+    use crate::error::{ConnectError, RecvError, RequestError};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_auth_handshake() {
+        let mut server = StpServer::bind("127.0.0.1:0").unwrap();
+        server.set_auth(AuthConfig {
+            open: false,
+            tokens: vec![],
+            users: vec![UserEntry {
+                user: "anna".into(),
+                secret: SecretHash::password_with_rounds("secret", 1000),
+            }],
+        });
+        let addr = server.local_addr().unwrap();
+
+        let serving = thread::spawn(move || {
+            let mut results = vec![];
+            for _ in 0..3 {
+                results.push(server.accept().map(|mut conn| {
+                    conn.process_request(|req| req.to_uppercase()).unwrap();
+                    conn.principal().clone()
+                }));
+            }
+            results
+        });
+
+        let res = StpClient::connect(addr);
+        assert!(matches!(res, Err(ConnectError::AuthRequired)));
+
+        let wrong = Credentials::Password {
+            user: "anna".into(),
+            password: "guess".into(),
+        };
+        let res = StpClient::connect_with(addr, Some(&wrong));
+        assert!(matches!(res, Err(ConnectError::AuthFailed)));
+
+        let right = Credentials::Password {
+            user: "anna".into(),
+            password: "secret".into(),
+        };
+        let mut client = StpClient::connect_with(addr, Some(&right)).unwrap();
+        assert_eq!(client.send_request("ping").unwrap(), "PING");
+
+        let results = serving.join().unwrap();
+        assert!(results[0].is_err() && results[1].is_err());
+        assert_eq!(
+            results[2].as_ref().unwrap(),
+            &Principal::User("anna".into())
+        );
     }
 
-    /// Address of connected client
-    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
-        self.stream.peer_addr()
+    #[test]
+    fn test_handshake_limits() {
+        let mut server = StpServer::bind("127.0.0.1:0").unwrap();
+        server.set_auth(AuthConfig {
+            open: false,
+            tokens: vec![TokenEntry {
+                name: "ci".into(),
+                secret: SecretHash::new("s3cr3t"),
+            }],
+            users: vec![],
+        });
+        server.set_handshake_timeout(Duration::from_millis(300));
+        let addr = server.local_addr().unwrap();
+
+        let serving = thread::spawn(move || {
+            let mut results = vec![];
+            for _ in 0..3 {
+                let started = Instant::now();
+                let res = server.accept().map(|mut conn| {
+                    conn.process_request(|req| req.to_uppercase()).unwrap();
+                });
+                results.push((res, started.elapsed()));
+            }
+            results
+        });
+
+        // молчащий клиент
+        let silent = TcpStream::connect(addr).unwrap();
+        // клиент с огромными учетными данными
+        let mut greedy = TcpStream::connect(addr).unwrap();
+        greedy.write_all(b"clnt").unwrap();
+        let mut reply = [0; 8];
+        greedy.read_exact(&mut reply).unwrap();
+        assert_eq!(&reply, b"servauth");
+        greedy.write_all(&(1u32 << 30).to_be_bytes()).unwrap();
+        greedy.read_exact(&mut reply[..4]).unwrap();
+        assert_eq!(&reply[..4], b"deny");
+
+        // после аутентификации срок снят
+        let token = Credentials::Token("s3cr3t".into());
+        let mut client = StpClient::connect_with(addr, Some(&token)).unwrap();
+        thread::sleep(Duration::from_millis(500));
+        assert_eq!(client.send_request("ping").unwrap(), "PING");
+
+        let results = serving.join().unwrap();
+        assert!(results[0].0.is_err());
+        assert!(results[0].1 < Duration::from_secs(2));
+        assert!(matches!(results[1].0, Err(ConnectError::AuthFailed)));
+        assert!(results[2].0.is_ok());
+        drop(silent);
+    }
+
+    #[test]
+    fn test_request_limits() {
+        let mut server = StpServer::bind("127.0.0.1:0").unwrap();
+        server.set_auth(AuthConfig {
+            open: true,
+            ..Default::default()
+        });
+        server.set_request_timeout(Duration::from_millis(300));
+        let addr = server.local_addr().unwrap();
+
+        let serving = thread::spawn(move || {
+            let mut results = vec![];
+            for _ in 0..2 {
+                let mut conn = server.accept().unwrap();
+                let started = Instant::now();
+                let res = conn.process_request(|req| req.to_uppercase());
+                results.push((res, started.elapsed()));
+            }
+            results
+        });
+
+        let open = |stream: &mut TcpStream| {
+            stream.write_all(b"clnt").unwrap();
+            let mut reply = [0; 8];
+            stream.read_exact(&mut reply).unwrap();
+            assert_eq!(&reply, b"servopen");
+        };
+        // клиент молчит после handshake
+        let mut silent = TcpStream::connect(addr).unwrap();
+        open(&mut silent);
+        // клиент с огромным запросом
+        let mut greedy = TcpStream::connect(addr).unwrap();
+        open(&mut greedy);
+        greedy.write_all(&(1u32 << 30).to_be_bytes()).unwrap();
+
+        let results = serving.join().unwrap();
+        assert!(matches!(
+            &results[0].0,
+            Err(RequestError::Recv(RecvError::Io(_)))
+        ));
+        assert!(results[0].1 < Duration::from_secs(2));
+        assert!(matches!(
+            results[1].0,
+            Err(RequestError::Recv(RecvError::TooLong(_)))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("stp_{}.sock", std::process::id()));
+        let server = StpServer::bind_unix(&path, 0o600).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let serving = thread::spawn(move || {
+            let mut conn = server.accept().unwrap();
+            conn.process_request(|req| req.to_uppercase()).unwrap();
+            (conn.principal().clone(), conn.peer().to_string())
+        });
+
+        let mut client = StpClient::connect_unix(&path, None).unwrap();
+        assert_eq!(client.send_request("ping").unwrap(), "PING");
+
+        let (principal, peer) = serving.join().unwrap();
+        assert_eq!(principal, Principal::Local);
+        assert!(peer.starts_with("unix:"));
+        // сервер удален вместе с потоком, а с ним и файл сокета
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket_replace() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("stp_replace_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("server.sock");
+
+        let err = StpServer::bind_unix(&path, 0o1777).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+        // сокет работающего сервера не заменяется
+        let server = StpServer::bind_unix(&path, 0o600).unwrap();
+        let err = StpServer::bind_unix(&path, 0o600).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::AddrInUse);
+
+        // оставшийся от упавшего сервера - заменяется
+        drop(server);
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+        let server = StpServer::bind_unix(&path, 0o660).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o660);
+        drop(server);
+
+        // обычный файл не заменяется
+        std::fs::write(&path, "data").unwrap();
+        let err = StpServer::bind_unix(&path, 0o600).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+
+        // временных каталогов не остается
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_any_stream() {
+        let (server_end, client_end) = std::os::unix::net::UnixStream::pair().unwrap();
+        let mut server = StpServer::bind("127.0.0.1:0").unwrap();
+        server.set_auth(AuthConfig {
+            open: true,
+            ..Default::default()
+        });
+
+        let serving = thread::spawn(move || {
+            let mut conn = server.accept_stream(server_end, "pair").unwrap();
+            conn.process_request(|req| req.chars().rev().collect())
+                .unwrap();
+            conn.peer().to_string()
+        });
+
+        let mut client = StpClient::connect_stream(client_end, None).unwrap();
+        assert_eq!(client.send_request("stp").unwrap(), "pts");
+        assert_eq!(serving.join().unwrap(), "pair");
     }
 }
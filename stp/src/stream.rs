@@ -0,0 +1,115 @@
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Поток, поверх которого работает протокол STP: TCP, Unix-сокет,
+/// TLS поверх них или любой другой двусторонний поток.
+pub(crate) trait Stream: Read + Write + Send {}
+
+impl<S: Read + Write + Send> Stream for S {}
+
+/// Поток соединения; протокол не зависит от того, что под ним.
+pub(crate) type StpStream = Box<dyn Stream>;
+
+/// Сокет, у которого можно ограничить время чтения и записи.
+pub(crate) trait Timeouts {
+    fn set_timeouts(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl Timeouts for TcpStream {
+    fn set_timeouts(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.set_read_timeout(timeout)?;
+        self.set_write_timeout(timeout)
+    }
+}
+
+#[cfg(unix)]
+impl Timeouts for UnixStream {
+    fn set_timeouts(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.set_read_timeout(timeout)?;
+        self.set_write_timeout(timeout)
+    }
+}
+
+/// Срок текущего этапа соединения, общий для сервера и сокета:
+/// сервер переставляет его между handshake, чтением запроса и отправкой ответа.
+#[derive(Clone, Default)]
+pub(crate) struct Timer(Arc<Mutex<Option<Instant>>>);
+
+impl Timer {
+    /// этап должен закончиться не позже чем через `timeout`
+    pub(crate) fn start(&self, timeout: Duration) {
+        *self.0.lock().unwrap() = Some(Instant::now() + timeout);
+    }
+
+    /// срок снят: например, пока сервер выполняет запрос
+    pub(crate) fn stop(&self) {
+        *self.0.lock().unwrap() = None;
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// Сокет со сроком из `Timer`: каждое чтение и запись ограничены оставшимся
+/// до срока временем, так что медленный клиент не задержит сервер.
+/// Пока срока нет, ограничения тоже нет.
+pub(crate) struct Deadline<S> {
+    stream: S,
+    timer: Timer,
+    /// у сокета выставлены таймауты
+    limited: bool,
+}
+
+impl<S: Timeouts> Deadline<S> {
+    pub(crate) fn new(stream: S, timer: Timer) -> Self {
+        Self {
+            stream,
+            timer,
+            limited: false,
+        }
+    }
+
+    /// выставить таймауты до срока или снять их, если срока нет
+    fn limit(&mut self) -> io::Result<()> {
+        let Some(deadline) = self.timer.deadline() else {
+            if self.limited {
+                self.stream.set_timeouts(None)?;
+                self.limited = false;
+            }
+            return Ok(());
+        };
+        match deadline.saturating_duration_since(Instant::now()) {
+            Duration::ZERO => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "stp deadline exceeded",
+            )),
+            left => {
+                self.limited = true;
+                self.stream.set_timeouts(Some(left))
+            }
+        }
+    }
+}
+
+impl<S: Read + Timeouts> Read for Deadline<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.limit()?;
+        self.stream.read(buf)
+    }
+}
+
+impl<S: Write + Timeouts> Write for Deadline<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.limit()?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
@@ -0,0 +1,246 @@
+use crate::error::TlsError;
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{ClientConfig, RootCertStore, ServerConfig};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Настройки TLS сервера, все файлы в формате PEM.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TlsServerConfig {
+    /// цепочка сертификатов сервера
+    pub cert: PathBuf,
+    /// закрытый ключ сервера
+    pub key: PathBuf,
+    /// корневые сертификаты клиентов; если заданы - клиент обязан предъявить сертификат
+    #[serde(default)]
+    pub client_ca: Option<PathBuf>,
+}
+
+impl TlsServerConfig {
+    pub fn build(&self) -> Result<Arc<ServerConfig>, TlsError> {
+        let provider = provider();
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+        let builder = match &self.client_ca {
+            Some(ca) => {
+                let verifier = WebPkiClientVerifier::builder_with_provider(
+                    Arc::new(load_roots(ca)?),
+                    provider,
+                )
+                .build()?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let config = builder.with_single_cert(load_certs(&self.cert)?, load_key(&self.key)?)?;
+        Ok(Arc::new(config))
+    }
+}
+
+/// Настройки TLS клиента, все файлы в формате PEM.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TlsClientConfig {
+    /// корневые сертификаты для проверки сервера
+    pub ca: PathBuf,
+    /// имя сервера, на которое выписан его сертификат
+    pub server_name: String,
+    /// сертификат клиента для mutual TLS
+    #[serde(default)]
+    pub cert: Option<PathBuf>,
+    /// закрытый ключ клиента для mutual TLS
+    #[serde(default)]
+    pub key: Option<PathBuf>,
+}
+
+impl TlsClientConfig {
+    pub fn build(&self) -> Result<(Arc<ClientConfig>, ServerName<'static>), TlsError> {
+        let builder = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(load_roots(&self.ca)?);
+        let config = match (&self.cert, &self.key) {
+            (Some(cert), Some(key)) => {
+                builder.with_client_auth_cert(load_certs(cert)?, load_key(key)?)?
+            }
+            _ => builder.with_no_client_auth(),
+        };
+        let name = ServerName::try_from(self.server_name.clone())
+            .map_err(|_| TlsError::InvalidServerName(self.server_name.clone()))?;
+        Ok((Arc::new(config), name))
+    }
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| TlsError::Pem(format!("{}: {e}", path.display())))?;
+    match certs.is_empty() {
+        true => Err(TlsError::Pem(format!(
+            "{}: no certificates",
+            path.display()
+        ))),
+        false => Ok(certs),
+    }
+}
+
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>, TlsError> {
+    PrivateKeyDer::from_pem_file(path)
+        .map_err(|e| TlsError::Pem(format!("{}: {e}", path.display())))
+}
+
+fn load_roots(path: &Path) -> Result<RootCertStore, TlsError> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(cert)?;
+    }
+    Ok(roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TlsClientConfig, TlsServerConfig};
+    use crate::auth::{AuthConfig, Credentials, SecretHash, TokenEntry};
+    use crate::client::StpClient;
+    use crate::server::StpServer;
+    use rcgen::{BasicConstraints, Certificate, CertificateParams, IsCa, KeyPair};
+    use std::path::{Path, PathBuf};
+    use std::{env, fs, thread};
+
+    /// самоподписанный CA и выписанные им сертификаты, в PEM-файлах
+    struct TestPki {
+        dir: PathBuf,
+        ca: Certificate,
+        ca_key: KeyPair,
+    }
+
+    impl TestPki {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!("stp_tls_{name}_{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            let ca_key = KeyPair::generate().unwrap();
+            let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            let ca = params.self_signed(&ca_key).unwrap();
+            fs::write(dir.join("ca.pem"), ca.pem()).unwrap();
+            Self { dir, ca, ca_key }
+        }
+
+        /// сертификат и ключ для имени, пути к файлам
+        fn issue(&self, name: &str) -> (PathBuf, PathBuf) {
+            let key = KeyPair::generate().unwrap();
+            let params = CertificateParams::new(vec![name.to_string()]).unwrap();
+            let cert = params.signed_by(&key, &self.ca, &self.ca_key).unwrap();
+            let (cert_path, key_path) = (self.file(name, "crt"), self.file(name, "key"));
+            fs::write(&cert_path, cert.pem()).unwrap();
+            fs::write(&key_path, key.serialize_pem()).unwrap();
+            (cert_path, key_path)
+        }
+
+        fn file(&self, name: &str, ext: &str) -> PathBuf {
+            self.dir.join(format!("{name}.{ext}"))
+        }
+
+        fn ca(&self) -> PathBuf {
+            self.dir.join("ca.pem")
+        }
+    }
+
+    impl Drop for TestPki {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn client_config(ca: &Path, identity: Option<(PathBuf, PathBuf)>) -> TlsClientConfig {
+        let (cert, key) = identity.unzip();
+        TlsClientConfig {
+            ca: ca.to_path_buf(),
+            server_name: "localhost".into(),
+            cert,
+            key,
+        }
+    }
+
+    /// сервер, отвечающий эхом в верхнем регистре на каждое из `clients` соединений
+    fn serve(server: StpServer, clients: usize) -> thread::JoinHandle<Vec<bool>> {
+        thread::spawn(move || {
+            (0..clients)
+                .map(|_| match server.accept() {
+                    Ok(mut conn) => conn.process_request(|req| req.to_uppercase()).is_ok(),
+                    Err(_) => false,
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn test_tls_with_auth() {
+        let pki = TestPki::new("auth");
+        let (cert, key) = pki.issue("localhost");
+
+        let mut server = StpServer::bind("127.0.0.1:0").unwrap();
+        let config = TlsServerConfig {
+            cert,
+            key,
+            client_ca: None,
+        };
+        server.set_tls(&config).unwrap();
+        server.set_auth(AuthConfig {
+            open: false,
+            tokens: vec![TokenEntry {
+                name: "ci".into(),
+                secret: SecretHash::new("s3cr3t"),
+            }],
+            users: vec![],
+        });
+        let addr = server.local_addr().unwrap();
+        let serving = serve(server, 2);
+
+        // открытый клиент не проходит TLS handshake
+        assert!(StpClient::connect(addr).is_err());
+
+        let token = Credentials::Token("s3cr3t".into());
+        let tls = client_config(&pki.ca(), None);
+        let mut client = StpClient::connect_tls(addr, &tls, Some(&token)).unwrap();
+        assert_eq!(client.send_request("ping").unwrap(), "PING");
+
+        assert_eq!(serving.join().unwrap(), vec![false, true]);
+    }
+
+    #[test]
+    fn test_mutual_tls() {
+        let pki = TestPki::new("mutual");
+        let (cert, key) = pki.issue("localhost");
+
+        let mut server = StpServer::bind("127.0.0.1:0").unwrap();
+        let config = TlsServerConfig {
+            cert,
+            key,
+            client_ca: Some(pki.ca()),
+        };
+        server.set_tls(&config).unwrap();
+        // клиента проверяет mutual TLS, учетные данные поверх него не нужны
+        server.set_auth(AuthConfig {
+            open: true,
+            ..Default::default()
+        });
+        let addr = server.local_addr().unwrap();
+        let serving = serve(server, 2);
+
+        let anonymous = client_config(&pki.ca(), None);
+        assert!(StpClient::connect_tls(addr, &anonymous, None).is_err());
+
+        let identity = client_config(&pki.ca(), Some(pki.issue("sensor-1")));
+        let mut client = StpClient::connect_tls(addr, &identity, None).unwrap();
+        assert_eq!(client.send_request("ping").unwrap(), "PING");
+
+        assert_eq!(serving.join().unwrap(), vec![false, true]);
+    }
+}